@@ -3,7 +3,7 @@ use std::path::Path;
 use std::thread::sleep;
 
 use rog_anime::usb::get_maybe_anime_type;
-use rog_anime::{ActionData, ActionLoader, Sequences};
+use rog_anime::{ActionData, ActionLoader, AnimeFrames, Sequences};
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
 use zbus::blocking::Connection;
 
@@ -33,11 +33,15 @@ fn main() {
 
     loop {
         for action in seq.iter() {
-            if let ActionData::Animation(frames) = action {
-                for frame in frames.frames() {
-                    proxy.write(frame.frame().clone()).unwrap();
-                    sleep(frame.delay());
-                }
+            let frames: &dyn AnimeFrames = match action {
+                ActionData::Animation(frames) => frames,
+                ActionData::Stream(frames) => frames,
+                _ => continue,
+            };
+            for index in 0..frames.frame_count() {
+                let frame = frames.frame(index).unwrap();
+                proxy.write(frame.frame().clone()).unwrap();
+                sleep(frame.delay());
             }
         }
     }
@@ -5,7 +5,7 @@ use std::path::Path;
 use std::process::exit;
 
 use rog_anime::usb::get_maybe_anime_type;
-use rog_anime::{AnimeDataBuffer, AnimeImage, Vec2};
+use rog_anime::{AnimeDataBuffer, AnimeImage, ColourOptions, Vec2};
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
 use zbus::blocking::Connection;
 
@@ -30,6 +30,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             args[5].parse::<f32>().unwrap(),
         ),
         args[6].parse::<f32>().unwrap(),
+        ColourOptions::default(),
         anime_type,
     )?;
 
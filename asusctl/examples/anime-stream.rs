@@ -0,0 +1,38 @@
+use std::convert::TryFrom;
+use std::io::Write;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::thread::sleep;
+use std::time::Duration;
+
+use rog_anime::usb::get_maybe_anime_type;
+use rog_anime::{AnimeDataBuffer, AnimeGrid};
+use rog_dbus::zbus_anime::AnimeProxyBlocking;
+use zbus::blocking::Connection;
+
+// Sweeps a bar across the display at 60 frames a second for ten seconds, then
+// closes the stream so asusd goes back to the system animation
+
+fn main() {
+    let conn = Connection::system().unwrap();
+    let proxy = AnimeProxyBlocking::new(&conn).unwrap();
+    let anime_type = get_maybe_anime_type().unwrap();
+
+    let fd: OwnedFd = proxy.open_stream().unwrap().into();
+    let mut stream = UnixStream::from(fd);
+
+    for step in 0..600 {
+        let mut matrix = AnimeGrid::new(anime_type);
+        for row in matrix.get_mut().iter_mut() {
+            let x = step % row.len();
+            row[x] = 0xff;
+            row[(x + row.len() - 1) % row.len()] = 0x44;
+        }
+        let buffer = <AnimeDataBuffer>::try_from(matrix).unwrap();
+        if stream.write_all(buffer.data()).is_err() {
+            // Another program opened a stream
+            break;
+        }
+        sleep(Duration::from_millis(16));
+    }
+}
@@ -1,6 +1,6 @@
 use gumdrop::Options;
 use rog_anime::usb::{AnimAwake, AnimBooting, AnimShutdown, AnimSleeping, Brightness};
-use rog_anime::AnimeType;
+use rog_anime::{AnimeType, ScrollDirection};
 
 #[derive(Options)]
 pub struct AnimeCommand {
@@ -49,10 +49,12 @@ pub enum AnimeActions {
     Image(AnimeImage),
     #[options(help = "display a diagonal/pixel-perfect PNG")]
     PixelImage(AnimeImageDiagonal),
-    #[options(help = "display an animated GIF")]
+    #[options(help = "display an animated GIF, APNG or WebP")]
     Gif(AnimeGif),
     #[options(help = "display an animated diagonal/pixel-perfect GIF")]
     PixelGif(AnimeGifDiagonal),
+    #[options(help = "display a line of static or scrolling text")]
+    Text(AnimeText),
     #[options(help = "change which builtin animations are shown")]
     SetBuiltins(Builtins),
 }
@@ -117,7 +119,7 @@ pub struct AnimeImageDiagonal {
 pub struct AnimeGif {
     #[options(help = "print help message")]
     pub help: bool,
-    #[options(meta = "", help = "full path to the png to display")]
+    #[options(meta = "", help = "full path to the gif, apng or webp to display")]
     pub path: String,
     #[options(meta = "", default = "1.0", help = "scale 1.0 == normal")]
     pub scale: f32,
@@ -152,3 +154,33 @@ pub struct AnimeGifDiagonal {
     )]
     pub loops: u32,
 }
+
+#[derive(Options)]
+pub struct AnimeText {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(meta = "", help = "the text to display")]
+    pub text: String,
+    #[options(
+        meta = "",
+        default = "1.0",
+        help = "font size 1.0 == one LED per pixel"
+    )]
+    pub font_size: f32,
+    #[options(
+        meta = "",
+        default = "left",
+        help = "scroll direction <left, right, up, down, static>"
+    )]
+    pub direction: ScrollDirection,
+    #[options(meta = "", default = "10.0", help = "scroll speed in LEDs per second")]
+    pub speed: f32,
+    #[options(meta = "", default = "1.0", help = "brightness 0.0-1.0")]
+    pub bright: f32,
+    #[options(
+        meta = "",
+        default = "1",
+        help = "how many loops to play - 0 is infinite"
+    )]
+    pub loops: u32,
+}
@@ -20,6 +20,49 @@ pub struct LedPowerCommand1 {
     pub sleep: Option<bool>,
 }
 
+#[derive(Options, Debug)]
+pub struct AuraSupportCheckCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(
+        meta = "",
+        default = "/usr/share/asusd/aura_support.ron",
+        help = "the aura_support.ron file to check"
+    )]
+    pub file: String,
+    #[options(
+        meta = "",
+        default = "/usr/share/rog-gui/layouts",
+        help = "the directory of layout files"
+    )]
+    pub layouts: String,
+    #[options(help = "exit with an error code if there are any issues")]
+    pub strict: bool,
+}
+
+#[derive(Options, Debug)]
+pub struct KleImportCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(
+        meta = "",
+        help = "the JSON file downloaded from keyboard-layout-editor.com"
+    )]
+    pub file: String,
+    #[options(meta = "", default = "US", help = "the locale of the layout")]
+    pub locale: String,
+    #[options(
+        meta = "",
+        help = "a RON file of extra legend to LedCode mappings, e.g. {\"Shift\": [LShift, Rshift]}"
+    )]
+    pub legends: Option<String>,
+    #[options(
+        meta = "",
+        help = "write the layout to this file instead of printing it"
+    )]
+    pub output: Option<String>,
+}
+
 #[derive(Options, Debug)]
 pub struct LedPowerCommand2 {
     #[options(help = "print help message")]
@@ -134,7 +177,7 @@ pub struct SingleSpeedDirection {
 pub struct SingleColour {
     #[options(help = "print help message")]
     help: bool,
-    #[options(no_long, meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(no_long, meta = "", help = "set the RGB value e.g, ff00ff or magenta")]
     pub colour: Colour,
     #[options(
         no_long,
@@ -148,7 +191,7 @@ pub struct SingleColour {
 pub struct SingleColourSpeed {
     #[options(help = "print help message")]
     help: bool,
-    #[options(no_long, meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(no_long, meta = "", help = "set the RGB value e.g, ff00ff or magenta")]
     pub colour: Colour,
     #[options(no_long, meta = "", help = "set the speed: low, med, high")]
     pub speed: Speed,
@@ -164,9 +207,17 @@ pub struct SingleColourSpeed {
 pub struct TwoColourSpeed {
     #[options(help = "print help message")]
     help: bool,
-    #[options(no_long, meta = "", help = "set the first RGB value e.g, ff00ff")]
+    #[options(
+        no_long,
+        meta = "",
+        help = "set the first RGB value e.g, ff00ff or magenta"
+    )]
     pub colour: Colour,
-    #[options(no_long, meta = "", help = "set the second RGB value e.g, ff00ff")]
+    #[options(
+        no_long,
+        meta = "",
+        help = "set the second RGB value e.g, ff00ff or magenta"
+    )]
     pub colour2: Colour,
     #[options(no_long, meta = "", help = "set the speed: low, med, high")]
     pub speed: Speed,
@@ -182,13 +233,29 @@ pub struct TwoColourSpeed {
 pub struct MultiZone {
     #[options(help = "print help message")]
     help: bool,
-    #[options(short = "a", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "a",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour1: Colour,
-    #[options(short = "b", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "b",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour2: Colour,
-    #[options(short = "c", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "c",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour3: Colour,
-    #[options(short = "d", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "d",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour4: Colour,
 }
 
@@ -196,13 +263,29 @@ pub struct MultiZone {
 pub struct MultiColourSpeed {
     #[options(help = "print help message")]
     help: bool,
-    #[options(short = "a", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "a",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour1: Colour,
-    #[options(short = "b", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "b",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour2: Colour,
-    #[options(short = "c", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "c",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour3: Colour,
-    #[options(short = "d", meta = "", help = "set the RGB value e.g, ff00ff")]
+    #[options(
+        short = "d",
+        meta = "",
+        help = "set the RGB value e.g, ff00ff or magenta"
+    )]
     pub colour4: Colour,
     #[options(no_long, meta = "", help = "set the speed: low, med, high")]
     pub speed: Speed,
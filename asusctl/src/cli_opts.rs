@@ -2,9 +2,13 @@ use gumdrop::Options;
 use rog_platform::platform::ThrottlePolicy;
 
 use crate::anime_cli::AnimeCommand;
-use crate::aura_cli::{LedBrightness, LedPowerCommand1, LedPowerCommand2, SetAuraBuiltin};
+use crate::aura_cli::{
+    AuraSupportCheckCommand, KleImportCommand, LedBrightness, LedPowerCommand1, LedPowerCommand2,
+    SetAuraBuiltin,
+};
 use crate::fan_curve_cli::FanCurveCommand;
 use crate::slash_cli::SlashCommand;
+use crate::user_config_cli::UserConfigCommand;
 
 #[derive(Default, Options)]
 pub struct CliStart {
@@ -46,6 +50,12 @@ pub enum CliCommand {
     Slash(SlashCommand),
     #[options(help = "Change bios settings")]
     Bios(BiosCommand),
+    #[options(help = "Convert a keyboard-layout-editor JSON file to a keyboard layout")]
+    KleImport(KleImportCommand),
+    #[options(help = "Check an aura_support.ron file for shadowed or inconsistent entries")]
+    AuraSupportCheck(AuraSupportCheckCommand),
+    #[options(help = "Manage the aura and AniMe configs of asusd-user")]
+    UserConfig(UserConfigCommand),
 }
 
 #[derive(Debug, Clone, Options)]
@@ -74,6 +84,11 @@ pub struct LedModeCommand {
     pub next_mode: bool,
     #[options(help = "switch to previous aura mode")]
     pub prev_mode: bool,
+    #[options(
+        meta = "",
+        help = "time in milliseconds to fade between modes and brightness, 0 to disable"
+    )]
+    pub transition_time: Option<u32>,
     #[options(command)]
     pub command: Option<SetAuraBuiltin>,
 }
@@ -5,13 +5,17 @@ use std::process::Command;
 use std::thread::sleep;
 
 use anime_cli::{AnimeActions, AnimeCommand};
-use aura_cli::{LedPowerCommand1, LedPowerCommand2};
+use aura_cli::{AuraSupportCheckCommand, KleImportCommand, LedPowerCommand1, LedPowerCommand2};
 use dmi_id::DMIID;
 use fan_curve_cli::FanCurveCommand;
 use gumdrop::{Opt, Options};
 use rog_anime::usb::get_maybe_anime_type;
-use rog_anime::{AnimTime, AnimeDataBuffer, AnimeDiagonal, AnimeGif, AnimeImage, AnimeType, Vec2};
-use rog_aura::keyboard::{AuraPowerState, LaptopAuraPower};
+use rog_anime::{
+    AnimTime, AnimeDataBuffer, AnimeDiagonal, AnimeGif, AnimeImage, AnimeType, ColourOptions,
+    Placement, Vec2,
+};
+use rog_aura::aura_detection::LedSupportFile;
+use rog_aura::keyboard::{AuraPowerState, KeyLayout, KleLegendMap, LaptopAuraPower};
 use rog_aura::{self, AuraDeviceType, AuraEffect, PowerZones};
 use rog_dbus::list_iface_blocking;
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
@@ -19,6 +23,7 @@ use rog_dbus::zbus_aura::AuraProxyBlocking;
 use rog_dbus::zbus_fan_curves::FanCurvesProxyBlocking;
 use rog_dbus::zbus_platform::PlatformProxyBlocking;
 use rog_dbus::zbus_slash::SlashProxyBlocking;
+use rog_dbus::zbus_user_config::UserConfigProxyBlocking;
 use rog_platform::platform::{GpuMode, Properties, ThrottlePolicy};
 use rog_profiles::error::ProfileError;
 use rog_slash::SlashMode;
@@ -28,12 +33,14 @@ use zbus::blocking::Connection;
 use crate::aura_cli::{AuraPowerStates, LedBrightness};
 use crate::cli_opts::*;
 use crate::slash_cli::SlashCommand;
+use crate::user_config_cli::{UserConfigCommand, UserConfigKind};
 
 mod anime_cli;
 mod aura_cli;
 mod cli_opts;
 mod fan_curve_cli;
 mod slash_cli;
+mod user_config_cli;
 
 fn main() {
     let self_version = env!("CARGO_PKG_VERSION");
@@ -53,6 +60,28 @@ fn main() {
         }
     };
 
+    // Doesn't need the daemon
+    if let Some(CliCommand::KleImport(cmd)) = &parsed.command {
+        if let Err(err) = handle_kle_import(cmd) {
+            println!("Error: {err}");
+        }
+        return;
+    }
+    if let Some(CliCommand::AuraSupportCheck(cmd)) = &parsed.command {
+        match handle_aura_support_check(cmd) {
+            Ok(clean) => {
+                if !clean && cmd.strict {
+                    std::process::exit(1);
+                }
+            }
+            Err(err) => {
+                println!("Error: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let conn = Connection::system().unwrap();
     if let Ok(platform_proxy) = PlatformProxyBlocking::new(&conn).map_err(|e| {
         check_service("asusd");
@@ -180,6 +209,9 @@ fn do_parsed(
         Some(CliCommand::Bios(cmd)) => {
             handle_platform_properties(&conn, supported_properties, cmd)?
         }
+        Some(CliCommand::UserConfig(cmd)) => handle_user_config(cmd)?,
+        // Run in main() before connecting, as they don't need the daemon
+        Some(CliCommand::KleImport(_) | CliCommand::AuraSupportCheck(_)) => {}
         None => {
             if (!parsed.show_supported
                 && parsed.kbd_bright.is_none()
@@ -300,10 +332,12 @@ fn do_parsed(
             let modes = aura.first().unwrap().supported_basic_modes()?;
             let zones = aura.first().unwrap().supported_basic_zones()?;
             let power = aura.first().unwrap().supported_power_zones()?;
+            let sources = aura.first().unwrap().supported_data_sources()?;
             println!("Supported Keyboard Brightness:\n{:#?}", bright);
             println!("Supported Aura Modes:\n{:#?}", modes);
             println!("Supported Aura Zones:\n{:#?}", zones);
             println!("Supported Aura Power Zones:\n{:#?}", power);
+            println!("Aura Support Data From:\n{:#?}", sources);
         } else {
             println!("No aura interface found");
         }
@@ -396,6 +430,7 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
                     image.angle,
                     Vec2::new(image.x_pos, image.y_pos),
                     image.bright,
+                    ColourOptions::default(),
                     anime_type,
                 )?;
 
@@ -430,14 +465,17 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
                 }
                 verify_brightness(gif.bright);
 
-                let matrix = AnimeGif::from_gif(
+                let matrix = AnimeGif::from_file(
                     Path::new(&gif.path),
-                    gif.scale,
-                    gif.angle,
-                    Vec2::new(gif.x_pos, gif.y_pos),
+                    Placement {
+                        scale: gif.scale,
+                        angle: gif.angle,
+                        translation: Vec2::new(gif.x_pos, gif.y_pos),
+                        brightness: gif.bright,
+                        colour: ColourOptions::default(),
+                        anime_type,
+                    },
                     AnimTime::Count(1),
-                    gif.bright,
-                    anime_type,
                 )?;
 
                 let mut loops = gif.loops as i32;
@@ -485,6 +523,40 @@ fn handle_anime(conn: &Connection, cmd: &AnimeCommand) -> Result<(), Box<dyn std
                     }
                 }
             }
+            AnimeActions::Text(text) => {
+                if text.help_requested() || text.text.is_empty() {
+                    println!("Missing arg or command\n\n{}", text.self_usage());
+                    if let Some(lst) = text.self_command_list() {
+                        println!("\n{}", lst);
+                    }
+                    return Ok(());
+                }
+                verify_brightness(text.bright);
+
+                let matrix = AnimeGif::from_text(
+                    &text.text,
+                    text.font_size,
+                    text.direction,
+                    text.speed,
+                    AnimTime::Count(1),
+                    text.bright,
+                    anime_type,
+                )?;
+
+                let mut loops = text.loops as i32;
+                loop {
+                    for frame in matrix.frames() {
+                        proxy.write(frame.frame().clone())?;
+                        sleep(frame.delay());
+                    }
+                    if loops >= 0 {
+                        loops -= 1;
+                    }
+                    if loops == 0 {
+                        break;
+                    }
+                }
+            }
             AnimeActions::SetBuiltins(builtins) => {
                 if builtins.help_requested() || builtins.set.is_none() {
                     println!("\nAny unspecified args will be set to default (first shown var)\n");
@@ -516,6 +588,63 @@ fn verify_brightness(brightness: f32) {
     }
 }
 
+fn handle_kle_import(cmd: &KleImportCommand) -> Result<(), Box<dyn std::error::Error>> {
+    if cmd.help || cmd.file.is_empty() {
+        println!("Missing arg or command\n\n{}", cmd.self_usage());
+        return Ok(());
+    }
+
+    let mut legends = KleLegendMap::default();
+    if let Some(file) = cmd.legends.as_ref() {
+        legends.extend(ron::from_str(&std::fs::read_to_string(file)?)?);
+    }
+    let json = std::fs::read_to_string(&cmd.file)?;
+    let import = KeyLayout::from_kle(&json, &cmd.locale, &legends)?;
+
+    for legend in &import.unmapped {
+        println!("Warning: no LedCode for key \"{legend}\", it was added as Blocking");
+    }
+    for led in &import.issues.unaddressable {
+        println!("Warning: {led:?} has no per-key USB address and will not light");
+    }
+    for led in &import.issues.duplicates {
+        println!("Warning: {led:?} is used by more than one key");
+    }
+
+    let ron = ron::ser::to_string_pretty(&import.layout, PrettyConfig::new().depth_limit(4))?;
+    if let Some(output) = cmd.output.as_ref() {
+        std::fs::write(output, ron)?;
+        println!("Layout written to {output}");
+    } else {
+        println!("{ron}");
+    }
+    Ok(())
+}
+
+/// Print the issues in an `aura_support.ron` file, returns `false` if there
+/// were any
+fn handle_aura_support_check(
+    cmd: &AuraSupportCheckCommand,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if cmd.help {
+        println!("{}", cmd.self_usage());
+        return Ok(true);
+    }
+
+    let data = LedSupportFile::from_file(Path::new(&cmd.file))?;
+    let issues = data.check(Path::new(&cmd.layouts));
+    for issue in &issues {
+        println!("{issue}");
+    }
+    println!(
+        "Checked {} entries in {}, {} issues found",
+        data.get().len(),
+        cmd.file,
+        issues.len()
+    );
+    Ok(issues.is_empty())
+}
+
 fn handle_slash(conn: &Connection, cmd: &SlashCommand) -> Result<(), Box<dyn std::error::Error>> {
     if (cmd.brightness.is_none()
         && cmd.interval.is_none()
@@ -556,10 +685,97 @@ fn handle_slash(conn: &Connection, cmd: &SlashCommand) -> Result<(), Box<dyn std
     Ok(())
 }
 
+fn handle_user_config(cmd: &UserConfigCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let (is_aura, actions) = match &cmd.command {
+        Some(UserConfigKind::Aura(actions)) if !cmd.help => (true, actions),
+        Some(UserConfigKind::Anime(actions)) if !cmd.help => (false, actions),
+        _ => {
+            println!("Missing arg or command\n\n{}", cmd.self_usage());
+            if let Some(lst) = cmd.self_command_list() {
+                println!("\n{}", lst);
+            }
+            return Ok(());
+        }
+    };
+    if (!actions.list
+        && actions.create.is_none()
+        && actions.copy.is_none()
+        && actions.delete.is_none()
+        && actions.activate.is_none()
+        && !actions.deactivate)
+        || actions.help
+    {
+        println!("Missing arg or command\n\n{}", actions.self_usage());
+        return Ok(());
+    }
+
+    // asusd-user runs in the user session
+    let conn = Connection::session()?;
+    let proxy = UserConfigProxyBlocking::new(&conn)?;
+    if let Some(name) = actions.create.as_ref() {
+        if is_aura {
+            proxy.create_aura_config(name)?;
+        } else {
+            proxy.create_anime_config(name)?;
+        }
+    }
+    if let Some(from) = actions.copy.as_ref() {
+        let to = actions
+            .to
+            .as_ref()
+            .ok_or("--copy needs a name set with --to")?;
+        if is_aura {
+            proxy.copy_aura_config(from, to)?;
+        } else {
+            proxy.copy_anime_config(from, to)?;
+        }
+    }
+    if let Some(name) = actions.delete.as_ref() {
+        if is_aura {
+            proxy.delete_aura_config(name)?;
+        } else {
+            proxy.delete_anime_config(name)?;
+        }
+    }
+    // An empty name deactivates
+    if let Some(name) = actions
+        .activate
+        .as_deref()
+        .or(actions.deactivate.then_some(""))
+    {
+        if is_aura {
+            proxy.activate_aura_config(name)?;
+        } else {
+            proxy.activate_anime_config(name)?;
+        }
+    }
+    if actions.list {
+        let (configs, active) = if is_aura {
+            (proxy.aura_configs()?, proxy.active_aura()?)
+        } else {
+            (proxy.anime_configs()?, proxy.active_anime()?)
+        };
+        for name in configs {
+            let mark = if name == active { "*" } else { " " };
+            println!("{mark} {name}");
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_led_mode(
     aura: &[AuraProxyBlocking],
     mode: &LedModeCommand,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(time) = mode.transition_time {
+        for aura in aura {
+            aura.set_transition_time(time)?;
+        }
+        if mode.command.is_none() && !mode.prev_mode && !mode.next_mode {
+            return Ok(());
+        }
+    }
     if mode.command.is_none() && !mode.prev_mode && !mode.next_mode {
         if !mode.help {
             println!("Missing arg or command\n");
@@ -0,0 +1,41 @@
+use gumdrop::Options;
+
+#[derive(Options)]
+pub struct UserConfigCommand {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(command)]
+    pub command: Option<UserConfigKind>,
+}
+
+#[derive(Options)]
+pub enum UserConfigKind {
+    #[options(help = "manage the per-key effect configs")]
+    Aura(UserConfigActions),
+    #[options(help = "manage the AniMe configs")]
+    Anime(UserConfigActions),
+}
+
+#[derive(Options)]
+pub struct UserConfigActions {
+    #[options(help = "print help message")]
+    pub help: bool,
+    #[options(help = "list the configs, the active one is marked with *")]
+    pub list: bool,
+    #[options(meta = "", help = "create a config with the default settings")]
+    pub create: Option<String>,
+    #[options(
+        no_short,
+        meta = "",
+        help = "copy a config, the new name is set with --to"
+    )]
+    pub copy: Option<String>,
+    #[options(no_short, meta = "", help = "the name of the copy")]
+    pub to: Option<String>,
+    #[options(meta = "", help = "delete a config, it must not be active")]
+    pub delete: Option<String>,
+    #[options(meta = "", help = "run a config now and on the next start")]
+    pub activate: Option<String>,
+    #[options(no_short, help = "stop running the active config")]
+    pub deactivate: bool,
+}
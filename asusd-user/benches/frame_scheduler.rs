@@ -0,0 +1,81 @@
+//! CPU use of the frame scheduler. Run with `cargo bench -p asusd-user`.
+//!
+//! Each case runs frame tasks that do no work for a few seconds and prints the
+//! CPU time used by the whole process as a percentage of one core.
+
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use asusd_user::scheduler::{FrameTask, FrameWaker, Next, PauseReason, Scheduler};
+use smol::{Executor, Timer};
+
+const RUN_TIME: Duration = Duration::from_secs(3);
+
+struct Counter {
+    frames: Arc<AtomicUsize>,
+    next: Next,
+}
+
+impl FrameTask for Counter {
+    fn frame(&mut self) -> Next {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.next
+    }
+}
+
+/// CPU time of all threads in this process
+fn cpu_time() -> Duration {
+    let mut total = 0;
+    if let Ok(tasks) = fs::read_dir("/proc/self/task") {
+        for task in tasks.flatten() {
+            let stat = fs::read_to_string(task.path().join("schedstat")).unwrap_or_default();
+            total += stat
+                .split_whitespace()
+                .next()
+                .and_then(|ns| ns.parse::<u64>().ok())
+                .unwrap_or_default();
+        }
+    }
+    Duration::from_nanos(total)
+}
+
+fn bench(name: &str, tasks: usize, fps: u32, next: Next, paused: bool) {
+    let executor = Executor::new();
+    let scheduler = Scheduler::new();
+    let frames = Arc::new(AtomicUsize::new(0));
+    for _ in 0..tasks {
+        scheduler.spawn(
+            &executor,
+            fps,
+            FrameWaker::new(),
+            Counter {
+                frames: frames.clone(),
+                next,
+            },
+        );
+    }
+    scheduler.set_paused(PauseReason::SessionLocked, paused);
+
+    let cpu_start = cpu_time();
+    let start = Instant::now();
+    smol::block_on(executor.run(Timer::after(RUN_TIME)));
+    let wall = start.elapsed();
+    let cpu = cpu_time() - cpu_start;
+
+    println!(
+        "{name:<24} {:>6} frames {:>7.3}% cpu",
+        frames.load(Ordering::Relaxed),
+        cpu.as_secs_f64() / wall.as_secs_f64() * 100.0
+    );
+}
+
+fn main() {
+    let frame = Next::After(Duration::ZERO);
+    bench("aura 30 fps", 1, 30, frame, false);
+    bench("anime 60 fps", 1, 60, frame, false);
+    bench("two tasks 60 fps", 2, 60, frame, false);
+    bench("idle", 2, 60, Next::Idle, false);
+    bench("paused", 2, 60, frame, true);
+}
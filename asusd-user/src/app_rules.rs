@@ -0,0 +1,307 @@
+//! Change the keyboard lighting, `AniMe`, and throttle policy while an
+//! application is running or focused.
+//!
+//! Running processes are read from procfs, and the focused window from the
+//! compositor where it can be queried. The first rule to match is applied, and
+//! the previous state is restored once no rule matches.
+
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+use std::{env, fs};
+
+use config_traits::StdConfig;
+use log::{info, warn};
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use rog_platform::platform::ThrottlePolicy;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigAnime, ConfigAura};
+use crate::ctrl_anime::{AnimeRunner, Swapped};
+use crate::ctrl_aura::AuraRunner;
+
+/// How often the running processes and focused window are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A running process as seen in procfs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    /// From `/proc/<pid>/comm`, this is cut to 15 characters by the kernel
+    pub name: String,
+    /// The file name of the first argument in `/proc/<pid>/cmdline`, which
+    /// is the full name of the program. Windows paths are also split so that
+    /// Wine and Proton games match by their `.exe` name.
+    pub exe_name: String,
+}
+
+impl Process {
+    fn read(pid: u32) -> Option<Self> {
+        let name = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+        let cmdline = fs::read(format!("/proc/{pid}/cmdline")).unwrap_or_default();
+        let argv0 = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+        let argv0 = String::from_utf8_lossy(argv0);
+        let exe_name = argv0.rsplit(['/', '\\']).next().unwrap_or_default();
+        Some(Self {
+            pid,
+            name: name.trim_end().to_owned(),
+            exe_name: exe_name.to_owned(),
+        })
+    }
+
+    /// All processes that can be read by this user
+    pub fn all() -> Vec<Self> {
+        let Ok(dir) = fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        dir.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter_map(Self::read)
+            .collect()
+    }
+}
+
+/// Find the process of the focused window. Hyprland and X11 sessions are
+/// supported, any other session returns `None`.
+pub fn focused_process() -> Option<Process> {
+    let pid = if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        // The output has a line such as `pid: 1234`
+        let out = Command::new("hyprctl").arg("activewindow").output().ok()?;
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("pid: ")?.parse().ok())
+    } else if env::var_os("WAYLAND_DISPLAY").is_none() && env::var_os("DISPLAY").is_some() {
+        // `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`
+        let out = Command::new("xprop")
+            .args(["-root", "_NET_ACTIVE_WINDOW"])
+            .output()
+            .ok()?;
+        let id = String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .last()?
+            .to_owned();
+        // `_NET_WM_PID(CARDINAL) = 1234`
+        let out = Command::new("xprop")
+            .args(["-id", &id, "_NET_WM_PID"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .last()?
+            .parse()
+            .ok()
+    } else {
+        None
+    }?;
+    Process::read(pid)
+}
+
+/// Settings to use while an application is running or focused
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AppRule {
+    /// Matched exactly against the process name or the program file name,
+    /// such as `steam` or `eldenring.exe`
+    pub process: String,
+    /// Only match while a window of the process is focused instead of while
+    /// it is running
+    #[serde(default)]
+    pub focused: bool,
+    /// Name of an aura config file in the user config directory to run in
+    /// place of `active_aura`
+    #[serde(default)]
+    pub aura: Option<String>,
+    /// Name of an anime config file in the user config directory to run in
+    /// place of `active_anime`
+    #[serde(default)]
+    pub anime: Option<String>,
+    #[serde(default)]
+    pub throttle_policy: Option<ThrottlePolicy>,
+}
+
+impl AppRule {
+    pub fn matches_process(&self, process: &Process) -> bool {
+        self.process == process.name || self.process == process.exe_name
+    }
+
+    pub fn matches(&self, running: &[Process], focused: Option<&Process>) -> bool {
+        if self.focused {
+            focused.is_some_and(|p| self.matches_process(p))
+        } else {
+            running.iter().any(|p| self.matches_process(p))
+        }
+    }
+}
+
+/// State to return to once no rule matches
+enum Restore {
+    Aura,
+    Anime(Swapped),
+    /// The policy before the rule, and the policy the rule set
+    ThrottlePolicy(ThrottlePolicy, ThrottlePolicy),
+}
+
+pub struct AppRules {
+    rules: Vec<AppRule>,
+    /// Plays the aura config of a matched rule in place of the active config
+    aura: Option<AuraRunner>,
+    anime: Option<AnimeRunner>,
+    platform: Option<PlatformProxyBlocking<'static>>,
+    /// Index of the rule currently applied
+    active: Option<usize>,
+    restore: Vec<Restore>,
+}
+
+impl AppRules {
+    pub fn new(
+        rules: Vec<AppRule>,
+        aura: Option<AuraRunner>,
+        anime: Option<AnimeRunner>,
+        platform: Option<PlatformProxyBlocking<'static>>,
+    ) -> Self {
+        Self {
+            rules,
+            aura,
+            anime,
+            platform,
+            active: None,
+            restore: Vec::new(),
+        }
+    }
+
+    /// Check the rules every `POLL_INTERVAL`. Blocks forever.
+    pub fn run(mut self) {
+        let need_focus = self.rules.iter().any(|r| r.focused);
+        if need_focus && focused_process().is_none() {
+            warn!(
+                "AppRules: the focused window can not be found in this session, rules with \
+                 `focused: true` will not match"
+            );
+        }
+        loop {
+            let running = Process::all();
+            let focused = if need_focus { focused_process() } else { None };
+            let matched = self
+                .rules
+                .iter()
+                .position(|r| r.matches(&running, focused.as_ref()));
+            if matched != self.active {
+                self.restore();
+                if let Some(index) = matched {
+                    info!("AppRules: {} matched", self.rules[index].process);
+                    self.apply(index);
+                }
+                self.active = matched;
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn apply(&mut self, index: usize) {
+        let rule = &self.rules[index];
+        if let Some(name) = rule.aura.as_ref() {
+            if let Some(runner) = self.aura.as_ref() {
+                // Reading rather than loading, so a missing config is not
+                // created with the default effects
+                match ConfigAura::new().set_name(name.clone()).read_new() {
+                    Some(config) => {
+                        runner.set_app_effects(Some(config.aura));
+                        self.restore.push(Restore::Aura);
+                    }
+                    None => warn!("AppRules: no aura config named {name}, skipping it"),
+                }
+            } else {
+                warn!("AppRules: {name} needs an Aura keyboard");
+            }
+        }
+        if let Some(name) = rule.anime.as_ref() {
+            if let Some(runner) = self.anime.as_ref() {
+                match ConfigAnime::new().set_name(name.clone()).read_new() {
+                    Some(config) => match config.create(runner.anime_type) {
+                        Ok(sequences) => {
+                            if let Some(previous) = runner.swap(sequences) {
+                                self.restore.push(Restore::Anime(previous));
+                            }
+                        }
+                        Err(e) => warn!("AppRules: could not create anime {name}: {e}"),
+                    },
+                    None => warn!("AppRules: no anime config named {name}, skipping it"),
+                }
+            } else {
+                warn!("AppRules: {name} needs an AniMe display");
+            }
+        }
+        if let (Some(policy), Some(platform)) = (rule.throttle_policy, self.platform.as_ref()) {
+            let previous = platform.throttle_thermal_policy();
+            match platform.set_throttle_thermal_policy(policy) {
+                Ok(()) => {
+                    if let Ok(previous) = previous {
+                        self.restore.push(Restore::ThrottlePolicy(previous, policy));
+                    }
+                }
+                Err(e) => warn!("AppRules: set throttle policy: {e}"),
+            }
+        }
+    }
+
+    fn restore(&mut self) {
+        for r in self.restore.drain(..) {
+            match r {
+                Restore::Aura => {
+                    if let Some(runner) = self.aura.as_ref() {
+                        runner.set_app_effects(None);
+                    }
+                }
+                Restore::Anime(swapped) => {
+                    // Keep a config the user activated while the rule matched
+                    if let Some(runner) = self.anime.as_ref() {
+                        runner.swap_back(swapped);
+                    }
+                }
+                Restore::ThrottlePolicy(previous, set) => {
+                    // Leave a policy that was changed while the rule matched
+                    if let Some(platform) = self.platform.as_ref() {
+                        if platform.throttle_thermal_policy().is_ok_and(|p| p == set) {
+                            platform.set_throttle_thermal_policy(previous).ok();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppRule, Process};
+
+    #[test]
+    fn match_rules() {
+        let process = |pid, name: &str, exe_name: &str| Process {
+            pid,
+            name: name.to_owned(),
+            exe_name: exe_name.to_owned(),
+        };
+        let running = [
+            process(10, "steam", "steam"),
+            process(11, "eldenring.exe", "eldenring.exe"),
+            process(12, "java", "java"),
+            process(13, "code", "code"),
+        ];
+        let mut rule = AppRule {
+            process: "eldenring.exe".to_owned(),
+            focused: false,
+            aura: None,
+            anime: None,
+            throttle_policy: None,
+        };
+        assert!(rule.matches(&running, None));
+        rule.focused = true;
+        assert!(!rule.matches(&running, None));
+        assert!(!rule.matches(&running, Some(&running[3])));
+        assert!(rule.matches(&running, Some(&running[1])));
+
+        // Long names are cut short in comm
+        let long = process(14, "jetbrains-toolb", "jetbrains-toolbox");
+        rule.process = "jetbrains-toolbox".to_owned();
+        assert!(rule.matches_process(&long));
+    }
+}
@@ -1,14 +1,24 @@
+use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use config_traits::{StdConfig, StdConfigLoad};
-use rog_anime::{ActionLoader, AnimTime, AnimeType, Fade, Sequences as AnimeSequences, Vec2};
+use rog_anime::{
+    ActionLoader, AnimTime, AnimeType, ColourOptions, Fade, PlayOrder, Sequences as AnimeSequences,
+    Vec2,
+};
 use rog_aura::effects::{AdvancedEffects as AuraSequences, Breathe, DoomFlicker, Effect, Static};
 use rog_aura::keyboard::LedCode;
 use rog_aura::{Colour, Speed};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use crate::app_rules::AppRule;
 use crate::error::Error;
+use crate::indicators::Indicator;
+use crate::notify::NotifyRule;
+use crate::scheduler::SchedulerConfig;
+use crate::session::SessionConfig;
 
 const ROOT_CONF_DIR: &str = "rog";
 
@@ -18,15 +28,51 @@ fn root_conf_dir() -> PathBuf {
     dir
 }
 
+/// Names of the configs in the user config directory that can be read as a
+/// `T`, such as all the `ConfigAura` files
+pub fn list_configs<T: DeserializeOwned>() -> Vec<String> {
+    let Ok(dir) = fs::read_dir(root_conf_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dir
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "ron" {
+                return None;
+            }
+            let data = fs::read_to_string(&path).ok()?;
+            ron::from_str::<T>(&data).ok()?;
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Check if a config file with this name is in the user config directory
+pub fn config_exists(name: &str) -> bool {
+    root_conf_dir().join(format!("{name}.ron")).exists()
+}
+
+/// Remove a config file from the user config directory
+pub fn remove_config(name: &str) -> Result<(), Error> {
+    fs::remove_file(root_conf_dir().join(format!("{name}.ron")))?;
+    Ok(())
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ConfigAnime {
     pub name: String,
     pub anime: Vec<ActionLoader>,
+    /// The order the actions are played in
+    #[serde(default)]
+    pub order: PlayOrder,
 }
 
 impl ConfigAnime {
     pub fn create(&self, anime_type: AnimeType) -> Result<AnimeSequences, Error> {
         let mut seq = AnimeSequences::new(anime_type);
+        seq.set_order(self.order);
 
         for (idx, action) in self.anime.iter().enumerate() {
             seq.insert(idx, action)?;
@@ -39,6 +85,15 @@ impl ConfigAnime {
         self.name = name;
         self
     }
+
+    /// A config with no name or actions, used while no config is active
+    pub fn inactive() -> Self {
+        Self {
+            name: String::new(),
+            anime: Vec::new(),
+            order: PlayOrder::default(),
+        }
+    }
 }
 
 impl Default for ConfigAnime {
@@ -75,6 +130,7 @@ impl Default for ConfigAnime {
                         Some(Duration::from_secs(2)),
                         Duration::from_secs(2),
                     )),
+                    colour: ColourOptions::default(),
                 },
                 ActionLoader::Image {
                     file: "/usr/share/asusd/anime/custom/rust.png".into(),
@@ -87,6 +143,7 @@ impl Default for ConfigAnime {
                         Duration::from_secs(2),
                     )),
                     brightness: 0.6,
+                    colour: ColourOptions::default(),
                 },
                 ActionLoader::Pause(Duration::from_secs(1)),
                 ActionLoader::ImageAnimation {
@@ -96,8 +153,10 @@ impl Default for ConfigAnime {
                     translation: Vec2::new(3.0, 2.0),
                     brightness: 0.5,
                     time: AnimTime::Count(2),
+                    colour: ColourOptions::default(),
                 },
             ],
+            order: PlayOrder::default(),
         }
     }
 }
@@ -210,6 +269,23 @@ pub struct ConfigBase {
     pub active_anime: Option<String>,
     /// Name of active aura config file in the user config directory
     pub active_aura: Option<String>,
+    /// Rules for mirroring desktop notifications, checked in order
+    pub notifications: Vec<NotifyRule>,
+    /// System state shown over the per-key effects of `active_aura`
+    pub indicators: Vec<Indicator>,
+    /// Run an `OpenRGB` SDK server on this localhost port, `OpenRGB` expects
+    /// 6742
+    pub openrgb_port: Option<u16>,
+    /// Keyboard layout locale such as `UK` or `DE`, detected from the system
+    /// keyboard settings if not set
+    pub keyboard_locale: Option<String>,
+    /// Settings to use while an application is running or focused, checked in
+    /// order
+    pub app_rules: Vec<AppRule>,
+    /// Frame rates of the effects, and when to pause them
+    pub scheduler: SchedulerConfig,
+    /// What to do while the session is locked or idle
+    pub session: SessionConfig,
 }
 
 impl StdConfig for ConfigBase {
@@ -217,6 +293,13 @@ impl StdConfig for ConfigBase {
         Self {
             active_anime: Some("anime-default".to_owned()),
             active_aura: Some("aura-default".to_owned()),
+            notifications: Vec::new(),
+            indicators: Vec::new(),
+            openrgb_port: None,
+            keyboard_locale: None,
+            app_rules: Vec::new(),
+            scheduler: SchedulerConfig::default(),
+            session: SessionConfig::default(),
         }
     }
 
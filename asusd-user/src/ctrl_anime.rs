@@ -1,13 +1,17 @@
+use std::ops::{Deref, DerefMut};
 use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use config_traits::StdConfig;
+use log::warn;
 use rog_anime::error::AnimeError;
-use rog_anime::{ActionData, ActionLoader, AnimTime, Fade, Sequences, Vec2};
+use rog_anime::{
+    ActionLoader, AnimTime, AnimeDataBuffer, AnimeType, ColourOptions, Fade, Generator,
+    GeneratorOptions, PowerState, SequencePlayer, Sequences, Step, Vec2,
+};
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
+use rog_platform::power::AsusPower;
 use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 use zbus::interface;
@@ -15,6 +19,10 @@ use zbus::zvariant::{ObjectPath, Type};
 
 use crate::config::ConfigAnime;
 use crate::error::Error;
+use crate::scheduler::{FrameTask, Hold, Next};
+
+/// How often the power state is read for the play rules of the actions
+const POWER_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
 pub struct Timer {
@@ -58,70 +66,239 @@ pub enum TimeType {
     Infinite,
 }
 
-/// The inner object exists to allow the zbus proxy to share it with a runner
-/// thread and a zbus server behind `Arc<Mutex<T>>`
-pub struct CtrlAnimeInner<'a> {
+/// The sequences being played. Each swap is numbered so that a temporary swap
+/// is only undone while its sequences are still the ones playing, and not
+/// after something else such as the user activating a config has replaced
+/// them.
+pub struct ActiveSequences {
+    sequences: Sequences,
+    /// The swap that put `sequences` in place, `0` if none has
+    current: u64,
+    swaps: u64,
+}
+
+/// Sequences replaced by `ActiveSequences::swap()`
+pub struct Swapped {
     sequences: Sequences,
+    /// The swap that had put `sequences` in place
+    previous: u64,
+    /// The swap that replaced them
+    swap: u64,
+}
+
+impl ActiveSequences {
+    pub fn new(sequences: Sequences) -> Self {
+        Self {
+            sequences,
+            current: 0,
+            swaps: 0,
+        }
+    }
+
+    /// Replace the sequences, returning the old ones
+    pub fn swap(&mut self, sequences: Sequences) -> Swapped {
+        self.swaps += 1;
+        let swapped = Swapped {
+            sequences: std::mem::replace(&mut self.sequences, sequences),
+            previous: self.current,
+            swap: self.swaps,
+        };
+        self.current = self.swaps;
+        swapped
+    }
+
+    /// Put back the sequences replaced by `swap()` if the ones it swapped in
+    /// are still in place. Returns `false` and drops `swapped` if not.
+    pub fn swap_back(&mut self, swapped: Swapped) -> bool {
+        if self.current != swapped.swap {
+            return false;
+        }
+        self.sequences = swapped.sequences;
+        self.current = swapped.previous;
+        true
+    }
+}
+
+impl Deref for ActiveSequences {
+    type Target = Sequences;
+
+    fn deref(&self) -> &Sequences {
+        &self.sequences
+    }
+}
+
+impl DerefMut for ActiveSequences {
+    fn deref_mut(&mut self) -> &mut Sequences {
+        &mut self.sequences
+    }
+}
+
+/// The inner object exists to allow the zbus proxy to share it with the frame
+/// task and a zbus server behind `Arc<Mutex<T>>`
+pub struct CtrlAnimeInner<'a> {
+    sequences: ActiveSequences,
+    player: SequencePlayer,
     client: AnimeProxyBlocking<'a>,
-    do_early_return: Arc<AtomicBool>,
+    do_early_return: Hold,
+    power: Option<AsusPower>,
+    /// When the power state was last read
+    power_read: Option<Instant>,
+    /// When the current frame or wait is over. The task can be woken before
+    /// then, such as when a hold is released, and carries on waiting.
+    due: Option<Instant>,
 }
 
-impl<'a> CtrlAnimeInner<'static> {
+impl CtrlAnimeInner<'static> {
     pub fn new(
         sequences: Sequences,
         client: AnimeProxyBlocking<'static>,
-        do_early_return: Arc<AtomicBool>,
+        do_early_return: Hold,
     ) -> Result<Self, Error> {
         Ok(Self {
-            sequences,
+            sequences: ActiveSequences::new(sequences),
+            player: SequencePlayer::new(),
             client,
             do_early_return,
+            power: AsusPower::new()
+                .map_err(|e| warn!("CtrlAnimeInner: power state not available: {e}"))
+                .ok(),
+            power_read: None,
+            due: None,
         })
     }
 
-    /// To be called on each main loop iteration to pump out commands to the
-    /// anime
-    pub fn run(&'a self) -> Result<(), Error> {
-        if self.do_early_return.load(Ordering::SeqCst) {
-            return Ok(());
+    /// Replace the running sequences, returning the old ones. They are played
+    /// from the start.
+    pub fn replace_sequences(&mut self, sequences: Sequences) -> Swapped {
+        self.restart();
+        self.sequences.swap(sequences)
+    }
+
+    /// Undo `replace_sequences()` if nothing has replaced the sequences since,
+    /// see `ActiveSequences::swap_back()`
+    pub fn restore_sequences(&mut self, swapped: Swapped) -> bool {
+        let restored = self.sequences.swap_back(swapped);
+        if restored {
+            self.restart();
         }
+        restored
+    }
 
-        for action in self.sequences.iter() {
-            match action {
-                ActionData::Animation(frames) => {
-                    rog_anime::run_animation(frames, &|output| {
-                        if self.do_early_return.load(Ordering::Acquire) {
-                            return Ok(true); // Do safe exit
-                        }
-                        self.client
-                            .write(output)
-                            .map_err(|e| AnimeError::Dbus(format!("{}", e)))
-                            .map(|_| false)
-                    });
-                }
-                ActionData::Image(image) => {
-                    self.client.write(image.as_ref().clone()).ok();
-                }
-                ActionData::Pause(duration) => {
-                    let start = Instant::now();
-                    'pause: loop {
-                        if self.do_early_return.load(Ordering::SeqCst) {
-                            return Ok(());
-                        }
-                        if Instant::now().duration_since(start) > *duration {
-                            break 'pause;
-                        }
-                        sleep(Duration::from_millis(1));
-                    }
-                }
-                ActionData::AudioEq
-                | ActionData::SystemInfo
-                | ActionData::TimeDate
-                | ActionData::Matrix => {}
+    /// Play the sequences from the start, this must be called after they are
+    /// changed
+    pub fn restart(&mut self) {
+        self.player.restart();
+        self.due = None;
+    }
+
+    /// The next frame of the sequences to write, if any, and when the one
+    /// after it is due
+    pub fn next_frame(&mut self) -> (Option<AnimeDataBuffer>, Next) {
+        if self.do_early_return.is_held() {
+            return (None, Next::Idle);
+        }
+        let now = Instant::now();
+        if let Some(due) = self.due.filter(|&due| due > now) {
+            return (None, Next::After(due - now));
+        }
+        let stale = match self.power_read {
+            Some(last) => now.duration_since(last) >= POWER_INTERVAL,
+            None => true,
+        };
+        if stale {
+            self.player.set_power(
+                self.power
+                    .as_ref()
+                    .map(PowerState::from)
+                    .unwrap_or_default(),
+            );
+            self.power_read = Some(now);
+        }
+        let (frame, delay) = match self.player.next_step(&self.sequences, now) {
+            Some(Step::Frame(frame, delay)) => (Some(frame), delay),
+            Some(Step::Wait(delay)) => (None, Some(delay)),
+            // Nothing to run until a config is activated
+            None => (None, None),
+        };
+        self.due = delay.map(|delay| now + delay);
+        (frame, delay.map_or(Next::Idle, Next::After))
+    }
+}
+
+/// The frame task for the `AniMe` display
+pub struct AnimeFrames(pub Arc<Mutex<CtrlAnimeInner<'static>>>);
+
+impl FrameTask for AnimeFrames {
+    fn frame(&mut self) -> Next {
+        // Written after letting go of the lock, so that the D-Bus methods are
+        // not held up by a slow write
+        let (frame, next, client) = match self.0.lock() {
+            Ok(mut inner) => {
+                let (frame, next) = inner.next_frame();
+                (frame, next, inner.client.clone())
             }
+            Err(_) => return Next::Idle,
+        };
+        if let Some(frame) = frame {
+            client
+                .write(frame)
+                .map_err(|e| warn!("CtrlAnimeInner: write frame: {e}"))
+                .ok();
         }
+        next
+    }
 
-        Ok(())
+    fn paused(&mut self) {
+        if let Ok(inner) = self.0.lock() {
+            inner.client.run_main_loop(true).ok();
+        }
+    }
+}
+
+/// A handle to the user anime runner so that its sequences can be changed
+/// while it runs
+#[derive(Clone)]
+pub struct AnimeRunner {
+    pub inner: Arc<Mutex<CtrlAnimeInner<'static>>>,
+    /// Must be the same `Hold` as in `CtrlAnimeInner`
+    pub early_return: Hold,
+    /// Shared with `CtrlAnime`, this is the active config
+    pub config: Arc<Mutex<ConfigAnime>>,
+    pub client: AnimeProxyBlocking<'static>,
+    pub anime_type: AnimeType,
+}
+
+impl AnimeRunner {
+    /// Swap in `sequences` and play them from the start. Returns the previous
+    /// sequences.
+    pub fn swap(&self, sequences: Sequences) -> Option<Swapped> {
+        self.early_return.set(true);
+        let old = self
+            .inner
+            .lock()
+            .ok()
+            .map(|mut inner| inner.replace_sequences(sequences));
+        self.early_return.set(false);
+        old
+    }
+
+    /// Put back the sequences replaced by `swap()`, unless something else has
+    /// swapped them since
+    pub fn swap_back(&self, swapped: Swapped) -> bool {
+        self.early_return.set(true);
+        let restored = self
+            .inner
+            .lock()
+            .is_ok_and(|mut inner| inner.restore_sequences(swapped));
+        self.early_return.set(false);
+        restored
+    }
+
+    /// If the active config has any actions to play
+    pub fn has_sequences(&self) -> bool {
+        self.inner
+            .lock()
+            .is_ok_and(|inner| !inner.sequences.is_empty())
     }
 }
 
@@ -129,8 +306,8 @@ pub struct CtrlAnime<'a> {
     config: Arc<Mutex<ConfigAnime>>,
     client: AnimeProxyBlocking<'a>,
     inner: Arc<Mutex<CtrlAnimeInner<'a>>>,
-    /// Must be the same Atomic as in CtrlAnimeInner
-    inner_early_return: Arc<AtomicBool>,
+    /// Must be the same `Hold` as in `CtrlAnimeInner`
+    inner_early_return: Hold,
 }
 
 impl CtrlAnime<'static> {
@@ -138,7 +315,7 @@ impl CtrlAnime<'static> {
         config: Arc<Mutex<ConfigAnime>>,
         inner: Arc<Mutex<CtrlAnimeInner<'static>>>,
         client: AnimeProxyBlocking<'static>,
-        inner_early_return: Arc<AtomicBool>,
+        inner_early_return: Hold,
     ) -> Result<Self, Error> {
         Ok(CtrlAnime {
             config,
@@ -162,11 +339,60 @@ impl CtrlAnime<'static> {
             })
             .ok();
     }
+
+    /// Insert `action` at `index` in the playing sequences and the config,
+    /// returning the config as RON. The frame task is held while the
+    /// sequences change and always released again, and the config only
+    /// changes if the sequences took the action.
+    fn insert_action(
+        &self,
+        config: &mut ConfigAnime,
+        index: u32,
+        action: ActionLoader,
+    ) -> zbus::fdo::Result<String> {
+        let index = index as usize;
+        // Hold the frame task while the sequences change
+        self.inner_early_return.set(true);
+
+        let inserted = match self.inner.lock() {
+            Ok(mut controller) => {
+                let len = controller.sequences.len();
+                if index > len {
+                    Err(zbus::fdo::Error::InvalidArgs(format!(
+                        "Index {index} is past the end of the {len} actions"
+                    )))
+                } else {
+                    let inserted = controller
+                        .sequences
+                        .insert(index, &action)
+                        .map_err(|err| zbus::fdo::Error::Failed(err.to_string()));
+                    if inserted.is_ok() {
+                        controller.restart();
+                    }
+                    inserted
+                }
+            }
+            Err(_) => Ok(()),
+        };
+        if inserted.is_ok() {
+            let index = index.min(config.anime.len());
+            config.anime.insert(index, action);
+            config.write();
+        }
+        // Release the frame task again, whether or not the insert worked
+        self.inner_early_return.set(false);
+        inserted?;
+
+        Ok(
+            ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
+                .expect("Parse config to RON failed"),
+        )
+    }
 }
 
 // The pattern for a zbus method is:
 // - Get config lock if required
-// - Set inner_early_return to stop the inner run loop temporarily
+// - Set inner_early_return to hold the frame task temporarily
 // - Do actions
 // - Write config if required
 // - Unset inner_early_return
@@ -180,6 +406,9 @@ impl CtrlAnime<'static> {
         brightness: f32,
     ) -> zbus::fdo::Result<String> {
         if let Ok(mut config) = self.config.try_lock() {
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
+            }
             let time: AnimTime = time.into();
             let file = Path::new(&file);
             let action = ActionLoader::AsusAnimation {
@@ -188,24 +417,7 @@ impl CtrlAnime<'static> {
                 time,
             };
 
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
-
-            if let Ok(mut controller) = self.inner.lock() {
-                controller
-                    .sequences
-                    .insert(index as usize, &action)
-                    .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
-            }
-            config.anime.push(action);
-            config.write();
-
-            let ron = ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
-                .expect("Parse config to RON failed");
-
-            // Release the inner run loop again
-            self.inner_early_return.store(false, Ordering::SeqCst);
-            return Ok(ron);
+            return self.insert_action(&mut config, index, action);
         }
         Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
     }
@@ -222,6 +434,9 @@ impl CtrlAnime<'static> {
         brightness: f32,
     ) -> zbus::fdo::Result<String> {
         if let Ok(mut config) = self.config.try_lock() {
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
+            }
             let time: AnimTime = time.into();
             let file = Path::new(&file);
             let translation = Vec2::new(xy.0, xy.1);
@@ -232,25 +447,10 @@ impl CtrlAnime<'static> {
                 translation,
                 brightness,
                 time,
+                colour: ColourOptions::default(),
             };
 
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
-
-            if let Ok(mut controller) = self.inner.lock() {
-                controller
-                    .sequences
-                    .insert(index as usize, &action)
-                    .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
-            }
-            config.anime.push(action);
-            config.write();
-
-            let ron = ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
-                .expect("Parse config to RON failed");
-            // Release the inner run loop again
-            self.inner_early_return.store(false, Ordering::SeqCst);
-            return Ok(ron);
+            return self.insert_action(&mut config, index, action);
         }
         Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
     }
@@ -267,6 +467,9 @@ impl CtrlAnime<'static> {
         brightness: f32,
     ) -> zbus::fdo::Result<String> {
         if let Ok(mut config) = self.config.try_lock() {
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
+            }
             let file = Path::new(&file);
             let time = time.into();
             let action = ActionLoader::Image {
@@ -276,60 +479,71 @@ impl CtrlAnime<'static> {
                 translation: Vec2::new(xy.0, xy.1),
                 brightness,
                 time,
+                colour: ColourOptions::default(),
             };
 
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
+            return self.insert_action(&mut config, index, action);
+        }
+        Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
+    }
 
-            if let Ok(mut controller) = self.inner.lock() {
-                controller
-                    .sequences
-                    .insert(index as usize, &action)
-                    .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+    #[allow(clippy::too_many_arguments)]
+    pub fn insert_generator(
+        &mut self,
+        index: u32,
+        generator: &str,
+        time: Timer,
+        speed: f32,
+        density: f32,
+        brightness: f32,
+        seed: u64,
+    ) -> zbus::fdo::Result<String> {
+        if let Ok(mut config) = self.config.try_lock() {
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
             }
-            config.anime.push(action);
-            config.write();
+            let generator: Generator = generator
+                .parse()
+                .map_err(|err: AnimeError| zbus::fdo::Error::Failed(err.to_string()))?;
+            let action = ActionLoader::Generator {
+                generator,
+                options: GeneratorOptions {
+                    speed,
+                    density,
+                    brightness,
+                    seed,
+                },
+                time: time.into(),
+            };
 
-            let ron = ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
-                .expect("Parse config to RON failed");
-            // Release the inner run loop again
-            self.inner_early_return.store(false, Ordering::SeqCst);
-            return Ok(ron);
+            return self.insert_action(&mut config, index, action);
         }
         Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
     }
 
     pub fn insert_pause(&mut self, index: u32, millis: u64) -> zbus::fdo::Result<String> {
         if let Ok(mut config) = self.config.try_lock() {
-            let action = ActionLoader::Pause(Duration::from_millis(millis));
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
-
-            if let Ok(mut controller) = self.inner.lock() {
-                controller
-                    .sequences
-                    .insert(index as usize, &action)
-                    .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
             }
-            config.anime.push(action);
-            config.write();
+            let action = ActionLoader::Pause(Duration::from_millis(millis));
 
-            let ron = ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
-                .expect("Parse config to RON failed");
-            // Release the inner run loop again
-            self.inner_early_return.store(false, Ordering::SeqCst);
-            return Ok(ron);
+            return self.insert_action(&mut config, index, action);
         }
         Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
     }
 
     pub fn remove_item(&mut self, index: u32) -> zbus::fdo::Result<String> {
         if let Ok(mut config) = self.config.try_lock() {
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
+            if config.name.is_empty() {
+                return Err(zbus::fdo::Error::Failed("No anime config is active".into()));
+            }
+            // Hold the frame task while the sequences change
+            self.inner_early_return.set(true);
 
             if let Ok(mut controller) = self.inner.lock() {
                 controller.sequences.remove_item(index as usize);
+                controller.restart();
             }
             if (index as usize) < config.anime.len() {
                 config.anime.remove(index as usize);
@@ -338,8 +552,8 @@ impl CtrlAnime<'static> {
 
             let ron = ron::ser::to_string_pretty(&*config, PrettyConfig::new().depth_limit(4))
                 .expect("Parse config to RON failed");
-            // Release the inner run loop again
-            self.inner_early_return.store(false, Ordering::SeqCst);
+            // Release the frame task again
+            self.inner_early_return.set(false);
             return Ok(ron);
         }
         Err(zbus::fdo::Error::Failed("UserConfig lock fail".into()))
@@ -349,13 +563,50 @@ impl CtrlAnime<'static> {
         // Operations here need to be in specific order
         if on {
             self.client.set_enable_display(on).ok();
-            // Let the inner loop run
-            self.inner_early_return.store(false, Ordering::SeqCst);
+            // Let the frame task run
+            self.inner_early_return.set(false);
         } else {
-            // Must make the inner run loop return early
-            self.inner_early_return.store(true, Ordering::SeqCst);
+            // Hold the frame task while the display is off
+            self.inner_early_return.set(true);
             self.client.set_enable_display(on).ok();
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rog_anime::{ActionLoader, AnimeType, Sequences};
+
+    use super::ActiveSequences;
+
+    fn sequences(pauses: usize) -> Sequences {
+        let mut sequences = Sequences::new(AnimeType::GA401);
+        for i in 0..pauses {
+            sequences
+                .insert(i, &ActionLoader::Pause(Duration::from_secs(1)))
+                .unwrap();
+        }
+        sequences
+    }
+
+    #[test]
+    fn swaps_are_undone_only_while_current() {
+        let mut active = ActiveSequences::new(sequences(0));
+        // Nested swaps undone in order
+        let app = active.swap(sequences(1));
+        let locked = active.swap(sequences(2));
+        assert!(active.swap_back(locked));
+        assert_eq!(active.len(), 1);
+        assert!(active.swap_back(app));
+        assert_eq!(active.len(), 0);
+
+        // A config activated while locked is kept when unlocked
+        let locked = active.swap(sequences(2));
+        active.swap(sequences(3));
+        assert!(!active.swap_back(locked));
+        assert_eq!(active.len(), 3);
+    }
+}
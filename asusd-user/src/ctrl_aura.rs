@@ -0,0 +1,186 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rog_aura::effects::AdvancedEffects as AuraSequences;
+use rog_aura::keyboard::{KeyLayout, LedCode, UsbPackets};
+use rog_aura::Colour;
+use rog_dbus::zbus_aura::AuraProxyBlocking;
+
+use crate::config::ConfigAura;
+use crate::indicators::Indicators;
+use crate::scheduler::{FrameTask, FrameWaker, Next};
+
+/// A handle to the user per-key effect runner so that what it shows can be
+/// changed while it runs. Each setter wakes the runner.
+#[derive(Clone)]
+pub struct AuraRunner {
+    /// The active config, `None` puts back the builtin mode
+    pub config: Arc<Mutex<Option<ConfigAura>>>,
+    /// Effects from an app rule, run in place of the config while set
+    pub app_effects: Arc<Mutex<Option<AuraSequences>>>,
+    /// A single colour over the whole keyboard, such as for a notification
+    pub flash: Arc<Mutex<Option<Colour>>>,
+    pub waker: FrameWaker,
+}
+
+impl AuraRunner {
+    pub fn new(config: Option<ConfigAura>) -> Self {
+        Self {
+            config: Arc::new(Mutex::new(config)),
+            app_effects: Arc::new(Mutex::new(None)),
+            flash: Arc::new(Mutex::new(None)),
+            waker: FrameWaker::new(),
+        }
+    }
+
+    pub fn set_config(&self, config: Option<ConfigAura>) {
+        if let Ok(mut lock) = self.config.lock() {
+            *lock = config;
+        }
+        self.waker.wake();
+    }
+
+    pub fn set_app_effects(&self, effects: Option<AuraSequences>) {
+        if let Ok(mut lock) = self.app_effects.lock() {
+            *lock = effects;
+        }
+        self.waker.wake();
+    }
+
+    pub fn set_flash(&self, colour: Option<Colour>) {
+        if let Ok(mut lock) = self.flash.lock() {
+            *lock = colour;
+        }
+        self.waker.wake();
+    }
+}
+
+/// The frame task for the keyboard
+pub struct AuraFrames {
+    runner: AuraRunner,
+    layout: KeyLayout,
+    /// Used for the flash when there is no active config
+    blank: AuraSequences,
+    indicators: Indicators,
+    client: AuraProxyBlocking<'static>,
+    interval: Duration,
+    /// Set while packets are being written over the builtin mode
+    direct: bool,
+}
+
+impl AuraFrames {
+    pub fn new(
+        runner: AuraRunner,
+        layout: KeyLayout,
+        blank: AuraSequences,
+        indicators: Indicators,
+        client: AuraProxyBlocking<'static>,
+        fps: u32,
+    ) -> Self {
+        Self {
+            runner,
+            layout,
+            blank,
+            indicators,
+            client,
+            interval: Duration::from_secs(1) / fps.max(1),
+            direct: false,
+        }
+    }
+
+    /// Put back the builtin mode if packets were being written
+    fn restore(&mut self) {
+        if self.direct {
+            if let Ok(mode) = self.client.led_mode_data() {
+                self.client.set_led_mode_data(mode).ok();
+            }
+            self.direct = false;
+        }
+    }
+}
+
+impl FrameTask for AuraFrames {
+    fn frame(&mut self) -> Next {
+        let packets = if let Some(colour) = self.runner.flash.lock().ok().and_then(|l| *l) {
+            Some(self.blank.create_fill_packets(&self.layout, colour))
+        } else {
+            let overlay = self.indicators.overlay();
+            // Effects from an app rule are run in place of the config
+            let mut app_effects = self.runner.app_effects.lock().ok();
+            let mut active = self.runner.config.lock().ok();
+            let aura = match (app_effects.as_deref_mut(), active.as_deref_mut()) {
+                (Some(Some(aura)), _) => Some(aura),
+                (_, Some(Some(config))) => Some(&mut config.aura),
+                _ => None,
+            };
+            let packets = aura.map(|aura| {
+                aura.next_state(&self.layout);
+                aura.create_packets_with_overlay(&overlay)
+            });
+            drop((app_effects, active));
+            // With no effects running the indicators are shown over the
+            // colour of the builtin mode
+            packets.or_else(|| {
+                indicator_packets(&self.blank, &self.layout, &overlay, || {
+                    self.client
+                        .led_mode_data()
+                        .map(|mode| mode.colour1)
+                        .unwrap_or_default()
+                })
+            })
+        };
+
+        if let Some(packets) = packets {
+            self.client.direct_addressing_raw(packets).ok();
+            self.direct = true;
+            Next::After(self.interval)
+        } else {
+            // Nothing left to show until one of the layers is set
+            self.restore();
+            Next::Idle
+        }
+    }
+
+    fn paused(&mut self) {
+        self.restore();
+    }
+}
+
+/// The indicators on their own, over every LED filled with the colour from
+/// `base`. `None` if there are no indicators to show, `base` is only read when
+/// there are.
+fn indicator_packets(
+    blank: &AuraSequences,
+    layout: &KeyLayout,
+    overlay: &[(LedCode, Colour)],
+    base: impl FnOnce() -> Colour,
+) -> Option<UsbPackets> {
+    if overlay.is_empty() {
+        return None;
+    }
+    Some(blank.create_fill_packets_with_overlay(layout, base(), overlay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indicators_without_aura() {
+        let layout = KeyLayout::default_layout();
+        let blank = AuraSequences::new(false);
+        let base = Colour { r: 0, g: 0, b: 255 };
+        let red = Colour { r: 255, g: 0, b: 0 };
+
+        let packets = indicator_packets(&blank, &layout, &[(LedCode::F1, red)], || base).unwrap();
+        assert!(!packets.is_empty());
+        assert_ne!(packets, blank.create_fill_packets(&layout, base));
+        assert_eq!(
+            packets,
+            blank.create_fill_packets_with_overlay(&layout, base, &[(LedCode::F1, red)])
+        );
+
+        // Idle with nothing to show, without reading the builtin colour
+        assert!(indicator_packets(&blank, &layout, &[], || unreachable!()).is_none());
+    }
+}
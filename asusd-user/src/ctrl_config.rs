@@ -0,0 +1,234 @@
+//! Manage the named aura and anime configs in the user config directory, and
+//! change the active ones without restarting.
+
+use config_traits::{StdConfig, StdConfigLoad};
+use log::info;
+use rog_anime::Sequences;
+use zbus::interface;
+use zbus::zvariant::ObjectPath;
+
+use crate::config::{
+    config_exists, list_configs, remove_config, ConfigAnime, ConfigAura, ConfigBase,
+};
+use crate::ctrl_anime::AnimeRunner;
+use crate::ctrl_aura::AuraRunner;
+
+pub const CONFIG_ZBUS_PATH: &str = "/org/asuslinux/UserConfig";
+
+fn failed(msg: String) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(msg)
+}
+
+/// Config names are file names in the user config directory
+fn check_name(name: &str) -> zbus::fdo::Result<()> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(failed(format!("{name:?} is not a valid config name")));
+    }
+    if name == ConfigBase::new().file_name().trim_end_matches(".ron") {
+        return Err(failed(format!("{name} is the base config")));
+    }
+    Ok(())
+}
+
+pub struct CtrlConfig {
+    /// Runs the activated aura config, `None` if asusd has no Aura device
+    aura: Option<AuraRunner>,
+    /// Runs the activated `AniMe` config, `None` if there is no `AniMe`
+    anime: Option<AnimeRunner>,
+}
+
+impl CtrlConfig {
+    pub fn new(aura: Option<AuraRunner>, anime: Option<AnimeRunner>) -> Self {
+        Self { aura, anime }
+    }
+
+    pub async fn add_to_server(self, server: &mut zbus::Connection) {
+        server
+            .object_server()
+            .at(&ObjectPath::from_str_unchecked(CONFIG_ZBUS_PATH), self)
+            .await
+            .map_err(|err| {
+                println!("CtrlConfig: add_to_server {}", err);
+                err
+            })
+            .ok();
+    }
+
+    /// Remember the active configs for the next start
+    fn write_base(aura: Option<Option<String>>, anime: Option<Option<String>>) {
+        let mut base = ConfigBase::new().load();
+        if let Some(aura) = aura {
+            base.active_aura = aura;
+        }
+        if let Some(anime) = anime {
+            base.active_anime = anime;
+        }
+        base.write();
+    }
+}
+
+#[interface(name = "org.asuslinux.UserConfig")]
+impl CtrlConfig {
+    /// The name of the active aura config, or empty if there is none
+    #[zbus(property)]
+    fn active_aura(&self) -> String {
+        self.aura
+            .as_ref()
+            .and_then(|a| a.config.lock().ok()?.as_ref().map(|c| c.name.clone()))
+            .unwrap_or_default()
+    }
+
+    /// The name of the active anime config, or empty if there is none
+    #[zbus(property)]
+    fn active_anime(&self) -> String {
+        self.anime
+            .as_ref()
+            .and_then(|a| a.config.lock().ok().map(|c| c.name.clone()))
+            .unwrap_or_default()
+    }
+
+    fn aura_configs(&self) -> Vec<String> {
+        list_configs::<ConfigAura>()
+    }
+
+    fn anime_configs(&self) -> Vec<String> {
+        list_configs::<ConfigAnime>()
+    }
+
+    /// Create a config with the default effects
+    fn create_aura_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        check_name(name)?;
+        if config_exists(name) {
+            return Err(failed(format!("{name} already exists")));
+        }
+        ConfigAura::new().set_name(name.to_owned()).write();
+        Ok(())
+    }
+
+    /// Create a config with the default actions
+    fn create_anime_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        check_name(name)?;
+        if config_exists(name) {
+            return Err(failed(format!("{name} already exists")));
+        }
+        ConfigAnime::new().set_name(name.to_owned()).write();
+        Ok(())
+    }
+
+    fn copy_aura_config(&self, from: &str, to: &str) -> zbus::fdo::Result<()> {
+        check_name(from)?;
+        check_name(to)?;
+        if config_exists(to) {
+            return Err(failed(format!("{to} already exists")));
+        }
+        let config = ConfigAura::new()
+            .set_name(from.to_owned())
+            .read_new()
+            .ok_or_else(|| failed(format!("No aura config named {from}")))?;
+        config.set_name(to.to_owned()).write();
+        Ok(())
+    }
+
+    fn copy_anime_config(&self, from: &str, to: &str) -> zbus::fdo::Result<()> {
+        check_name(from)?;
+        check_name(to)?;
+        if config_exists(to) {
+            return Err(failed(format!("{to} already exists")));
+        }
+        let config = ConfigAnime::new()
+            .set_name(from.to_owned())
+            .read_new()
+            .ok_or_else(|| failed(format!("No anime config named {from}")))?;
+        config.set_name(to.to_owned()).write();
+        Ok(())
+    }
+
+    /// Delete a config, which must not be active
+    fn delete_aura_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        check_name(name)?;
+        if self.active_aura() == name {
+            return Err(failed(format!("{name} is active")));
+        }
+        if !list_configs::<ConfigAura>().iter().any(|n| n == name) {
+            return Err(failed(format!("No aura config named {name}")));
+        }
+        remove_config(name).map_err(|e| failed(e.to_string()))
+    }
+
+    /// Delete a config, which must not be active
+    fn delete_anime_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        check_name(name)?;
+        if self.active_anime() == name {
+            return Err(failed(format!("{name} is active")));
+        }
+        if !list_configs::<ConfigAnime>().iter().any(|n| n == name) {
+            return Err(failed(format!("No anime config named {name}")));
+        }
+        remove_config(name).map_err(|e| failed(e.to_string()))
+    }
+
+    /// Run the effects in this config now and on the next start. An empty
+    /// name stops the effects and puts back the builtin mode.
+    fn activate_aura_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        let runner = self
+            .aura
+            .as_ref()
+            .ok_or_else(|| failed("No Aura keyboard found".to_owned()))?;
+        let config = if name.is_empty() {
+            None
+        } else {
+            check_name(name)?;
+            let config = ConfigAura::new()
+                .set_name(name.to_owned())
+                .read_new()
+                .ok_or_else(|| failed(format!("No aura config named {name}")))?;
+            Some(config.set_name(name.to_owned()))
+        };
+
+        // The runner puts back the builtin mode once it has nothing to show
+        runner.set_config(config);
+        info!("Activated aura config {name:?}");
+        Self::write_base(Some((!name.is_empty()).then(|| name.to_owned())), None);
+        Ok(())
+    }
+
+    /// Run the actions in this config now and on the next start. An empty
+    /// name stops the actions and gives the display back to asusd.
+    fn activate_anime_config(&self, name: &str) -> zbus::fdo::Result<()> {
+        let runner = self
+            .anime
+            .as_ref()
+            .ok_or_else(|| failed("No AniMe display found".to_owned()))?;
+        let config = if name.is_empty() {
+            ConfigAnime::inactive()
+        } else {
+            check_name(name)?;
+            let config = ConfigAnime::new()
+                .set_name(name.to_owned())
+                .read_new()
+                .ok_or_else(|| failed(format!("No anime config named {name}")))?;
+            config.set_name(name.to_owned())
+        };
+        let sequences = if name.is_empty() {
+            Sequences::new(runner.anime_type)
+        } else {
+            config
+                .create(runner.anime_type)
+                .map_err(|e| failed(e.to_string()))?
+        };
+
+        let mut active = runner
+            .config
+            .lock()
+            .map_err(|_| failed("UserConfig lock fail".to_owned()))?;
+        runner.swap(sequences);
+        *active = config;
+        drop(active);
+        if name.is_empty() {
+            runner.client.run_main_loop(true).ok();
+        }
+        info!("Activated anime config {name:?}");
+        Self::write_base(None, Some((!name.is_empty()).then(|| name.to_owned())));
+        Ok(())
+    }
+}
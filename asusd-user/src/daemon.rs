@@ -1,16 +1,27 @@
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 
+use asusd_user::app_rules::AppRules;
 use asusd_user::config::*;
-use asusd_user::ctrl_anime::{CtrlAnime, CtrlAnimeInner};
+use asusd_user::ctrl_anime::{AnimeFrames, AnimeRunner, CtrlAnime, CtrlAnimeInner};
+use asusd_user::ctrl_aura::{AuraFrames, AuraRunner};
+use asusd_user::ctrl_config::CtrlConfig;
+use asusd_user::indicators::Indicators;
+use asusd_user::notify::{watch_notifications, NotifyReactor};
+use asusd_user::openrgb::{find_devices, OrgbServer};
+use asusd_user::scheduler::{FrameWaker, Hold, Scheduler};
+use asusd_user::session::SessionReactor;
 use config_traits::{StdConfig, StdConfigLoad};
 use rog_anime::usb::get_maybe_anime_type;
 use rog_aura::aura_detection::LedSupportData;
-use rog_aura::keyboard::KeyLayout;
+use rog_aura::effects::AdvancedEffects as AuraSequences;
+use rog_aura::keyboard::{system_keyboard_locale, AdvancedAuraType, KeyLayout};
 use rog_dbus::zbus_anime::AnimeProxyBlocking;
 use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use rog_dbus::zbus_slash::SlashProxyBlocking;
 use rog_dbus::{list_iface_blocking, DBUS_NAME};
 use smol::Executor;
 use zbus::Connection;
@@ -39,78 +50,212 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let supported = list_iface_blocking()?;
     let config = ConfigBase::new().load();
     let executor = Executor::new();
+    let keyboard_locale = config
+        .keyboard_locale
+        .clone()
+        .or_else(system_keyboard_locale)
+        .unwrap_or_else(|| "US".to_owned());
 
-    let early_return = Arc::new(AtomicBool::new(false));
-    // Set up the anime data and run loop/thread
+    // Serves the user anime and config interfaces
+    let session = smol::block_on(async {
+        let connection = Connection::session().await?;
+        connection.request_name(DBUS_NAME).await?;
+        Ok::<_, zbus::Error>(connection)
+    })?;
+
+    let scheduler = Scheduler::new();
+    let mut anime_runner = None;
+    // Set up the anime data and frame task. This runs without an active config
+    // so that one can be activated later.
     if supported.contains(&"org.asuslinux.Anime".to_string()) {
-        if let Some(cfg) = config.active_anime {
-            let anime_type = get_maybe_anime_type()?;
-            let anime_config = ConfigAnime::new().set_name(cfg).load();
-            let anime = anime_config.create(anime_type)?;
-            let anime_config = Arc::new(Mutex::new(anime_config));
-
-            let anime_proxy_blocking = AnimeProxyBlocking::new(&conn).unwrap();
-            executor
-                .spawn(async move {
-                    // Create server
-                    let mut connection = Connection::session().await.unwrap();
-                    connection.request_name(DBUS_NAME).await.unwrap();
-
-                    // Inner behind mutex required for thread safety
-                    let inner = Arc::new(Mutex::new(
-                        CtrlAnimeInner::new(
-                            anime,
-                            anime_proxy_blocking.clone(),
-                            early_return.clone(),
-                        )
-                        .unwrap(),
-                    ));
-                    // Need new client object for dbus control part
-                    let anime_control = CtrlAnime::new(
-                        anime_config,
-                        inner.clone(),
-                        anime_proxy_blocking,
-                        early_return,
-                    )
-                    .unwrap();
-                    anime_control.add_to_server(&mut connection).await;
-                    loop {
-                        if let Ok(inner) = inner.clone().try_lock() {
-                            inner.run().ok();
-                        }
-                    }
-                })
-                .detach();
-        }
+        let anime_type = get_maybe_anime_type()?;
+        let anime_config = match config.active_anime {
+            Some(cfg) => ConfigAnime::new().set_name(cfg).load(),
+            None => ConfigAnime::inactive(),
+        };
+        let anime = anime_config.create(anime_type)?;
+        let anime_config = Arc::new(Mutex::new(anime_config));
+
+        let waker = FrameWaker::new();
+        let early_return = Hold::new(waker.clone());
+        let anime_proxy_blocking = AnimeProxyBlocking::new(&conn).unwrap();
+        // Inner behind mutex required for thread safety
+        let inner = Arc::new(Mutex::new(
+            CtrlAnimeInner::new(anime, anime_proxy_blocking.clone(), early_return.clone()).unwrap(),
+        ));
+        anime_runner = Some(AnimeRunner {
+            inner: inner.clone(),
+            early_return: early_return.clone(),
+            config: anime_config.clone(),
+            client: anime_proxy_blocking.clone(),
+            anime_type,
+        });
+        scheduler.spawn(
+            &executor,
+            config.scheduler.anime_fps,
+            waker,
+            AnimeFrames(inner.clone()),
+        );
+
+        let mut connection = session.clone();
+        executor
+            .spawn(async move {
+                // Need new client object for dbus control part
+                let anime_control =
+                    CtrlAnime::new(anime_config, inner, anime_proxy_blocking, early_return)
+                        .unwrap();
+                anime_control.add_to_server(&mut connection).await;
+            })
+            .detach();
     }
 
-    // if supported.keyboard_led.per_key_led_mode {
-    if let Some(cfg) = config.active_aura {
-        let mut aura_config = ConfigAura::new().set_name(cfg).load();
+    let mut aura_runner = None;
+    // Runs without an active config so that one can be activated later
+    if supported.contains(&"org.asuslinux.Aura".to_string()) {
+        let runner = AuraRunner::new(
+            config
+                .active_aura
+                .map(|cfg| ConfigAura::new().set_name(cfg).load()),
+        );
+        aura_runner = Some(runner.clone());
         // let baord_name = std::fs::read_to_string(BOARD_NAME)?;
 
         let led_support = LedSupportData::get_data("");
+        // Used for the notification flash when there is no active config
+        let blank = AuraSequences::new(matches!(
+            led_support.advanced_type,
+            AdvancedAuraType::Zoned(_)
+        ));
 
-        let layout = KeyLayout::find_layout(led_support, PathBuf::from(DATA_DIR))
-            .map_err(|e| {
-                println!("{BOARD_NAME}, {e}");
-            })
-            .unwrap_or_else(|_| KeyLayout::default_layout());
+        let layout = KeyLayout::find_layout_for_locale(
+            led_support,
+            PathBuf::from(DATA_DIR),
+            &keyboard_locale,
+        )
+        .map_err(|e| {
+            println!("{BOARD_NAME}, {e}");
+        })
+        .unwrap_or_else(|_| KeyLayout::default_layout());
 
         let aura_proxy_blocking = AuraProxyBlocking::new(&conn).unwrap();
+        let platform = if supported.contains(&"org.asuslinux.Platform".to_string()) {
+            PlatformProxyBlocking::new(&conn).ok()
+        } else {
+            None
+        };
+        let indicators = Indicators::new(config.indicators, platform);
+        let fps = config.scheduler.aura_fps;
+        scheduler.spawn(
+            &executor,
+            fps,
+            runner.waker.clone(),
+            AuraFrames::new(runner, layout, blank, indicators, aura_proxy_blocking, fps),
+        );
+    }
+
+    let aura = if supported.contains(&"org.asuslinux.Aura".to_string()) {
+        Some(AuraProxyBlocking::new(&conn)?)
+    } else {
+        None
+    };
+    let session_reactor = SessionReactor::new(
+        config.session,
+        scheduler.clone(),
+        aura,
+        anime_runner.clone(),
+    );
+    executor
+        .spawn(async move {
+            session_reactor
+                .run()
+                .await
+                .map_err(|e| println!("Session watch failed: {e}"))
+                .ok();
+        })
+        .detach();
+
+    executor
+        .spawn(async move {
+            scheduler
+                .watch_logind(config.scheduler)
+                .await
+                .map_err(|e| println!("Scheduler: logind watch failed: {e}"))
+                .ok();
+        })
+        .detach();
+
+    let mut connection = session.clone();
+    let config_control = CtrlConfig::new(aura_runner.clone(), anime_runner.clone());
+    executor
+        .spawn(async move {
+            config_control.add_to_server(&mut connection).await;
+        })
+        .detach();
+
+    if !config.notifications.is_empty() {
+        let anime = if supported.contains(&"org.asuslinux.Anime".to_string()) {
+            Some((AnimeProxyBlocking::new(&conn)?, get_maybe_anime_type()?))
+        } else {
+            None
+        };
+        let slash = if supported.contains(&"org.asuslinux.Slash".to_string()) {
+            Some(SlashProxyBlocking::new(&conn)?)
+        } else {
+            None
+        };
+        let aura = if supported.contains(&"org.asuslinux.Aura".to_string()) {
+            Some(AuraProxyBlocking::new(&conn)?)
+        } else {
+            None
+        };
+        let reactor = NotifyReactor::new(
+            anime,
+            anime_runner.clone(),
+            slash,
+            aura,
+            aura_runner.clone(),
+        );
+
+        // Reactions block for their duration so are played one at a time on
+        // their own thread
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            while let Ok((rule, notification)) = rx.recv() {
+                reactor.react(&rule, &notification);
+            }
+        });
+
+        let rules = config.notifications;
         executor
             .spawn(async move {
-                loop {
-                    aura_config.aura.next_state(&layout);
-                    let packets = aura_config.aura.create_packets();
-
-                    aura_proxy_blocking.direct_addressing_raw(packets).unwrap();
-                    std::thread::sleep(std::time::Duration::from_millis(33));
-                }
+                watch_notifications(rules, tx)
+                    .await
+                    .map_err(|e| println!("Notification bridge stopped: {e}"))
+                    .ok();
             })
             .detach();
     }
-    // }
+
+    if let Some(port) = config.openrgb_port {
+        let devices = find_devices(&conn, PathBuf::from(DATA_DIR), &keyboard_locale)?;
+        let server = OrgbServer::new(devices);
+        std::thread::spawn(move || {
+            server
+                .listen(("127.0.0.1", port))
+                .map_err(|e| println!("OpenRGB server stopped: {e}"))
+                .ok();
+        });
+    }
+
+    if !config.app_rules.is_empty() {
+        let platform = if supported.contains(&"org.asuslinux.Platform".to_string()) {
+            Some(PlatformProxyBlocking::new(&conn)?)
+        } else {
+            None
+        };
+        let rules = AppRules::new(config.app_rules, aura_runner, anime_runner, platform);
+        std::thread::spawn(move || rules.run());
+    }
 
     loop {
         smol::block_on(executor.tick());
@@ -0,0 +1,131 @@
+//! Show system state such as battery level, CPU temperature, or the current
+//! throttle policy on the keyboard lighting.
+//!
+//! Each indicator maps a reading to a colour which is laid over the running
+//! per-key effects on its LEDs. Sources are only read again once the
+//! indicator interval has passed, the cached colour is used in between.
+
+use std::time::{Duration, Instant};
+
+use log::warn;
+use rog_aura::keyboard::LedCode;
+use rog_aura::Colour;
+use rog_dbus::zbus_platform::PlatformProxyBlocking;
+use rog_platform::cpu::cpu_temperature;
+use rog_platform::platform::ThrottlePolicy;
+use rog_platform::power::AsusPower;
+use serde::{Deserialize, Serialize};
+
+/// Colour stops as `(value, colour)` in ascending order of value. Readings
+/// between two stops are blended, readings outside the stops use the nearest
+/// one.
+pub type Gradient = Vec<(f32, Colour)>;
+
+/// Where an indicator gets its reading from, and how that is turned into a
+/// colour
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum IndicatorSource {
+    /// Battery charge in percent
+    Battery { gradient: Gradient },
+    /// CPU package temperature in degrees celsius
+    CpuTemperature { gradient: Gradient },
+    /// A fixed colour for each throttle policy
+    ThrottlePolicy {
+        balanced: Colour,
+        performance: Colour,
+        quiet: Colour,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Indicator {
+    pub source: IndicatorSource,
+    /// The LEDs or zones to show the colour on
+    pub leds: Vec<LedCode>,
+    /// Minimum time between reads of the source
+    pub interval: Duration,
+}
+
+/// Reads the indicator sources and tracks the last colour of each
+pub struct Indicators {
+    indicators: Vec<Indicator>,
+    /// The time of the last read and resulting colour for each indicator
+    state: Vec<Option<(Instant, Colour)>>,
+    power: Option<AsusPower>,
+    platform: Option<PlatformProxyBlocking<'static>>,
+}
+
+impl Indicators {
+    pub fn new(
+        indicators: Vec<Indicator>,
+        platform: Option<PlatformProxyBlocking<'static>>,
+    ) -> Self {
+        let power = AsusPower::new()
+            .map_err(|e| warn!("Indicators: battery not available: {e}"))
+            .ok();
+        Self {
+            state: vec![None; indicators.len()],
+            indicators,
+            power,
+            platform,
+        }
+    }
+
+    /// Re-read any indicator with an elapsed interval, and return the colours
+    /// to set over the running effects
+    pub fn overlay(&mut self) -> Vec<(LedCode, Colour)> {
+        let now = Instant::now();
+        let mut overlay = Vec::new();
+        for (indicator, state) in self.indicators.iter().zip(self.state.iter_mut()) {
+            let stale = match state {
+                Some((last, _)) => now.duration_since(*last) >= indicator.interval,
+                None => true,
+            };
+            if stale {
+                if let Some(colour) = Self::read(&self.power, &self.platform, &indicator.source) {
+                    *state = Some((now, colour));
+                } else if let Some((last, _)) = state.as_mut() {
+                    // Keep the old colour rather than retrying every frame
+                    *last = now;
+                }
+            }
+            if let Some((_, colour)) = state {
+                overlay.extend(indicator.leds.iter().map(|led| (*led, *colour)));
+            }
+        }
+        overlay
+    }
+
+    fn read(
+        power: &Option<AsusPower>,
+        platform: &Option<PlatformProxyBlocking<'static>>,
+        source: &IndicatorSource,
+    ) -> Option<Colour> {
+        match source {
+            IndicatorSource::Battery { gradient } => power
+                .as_ref()?
+                .get_capacity()
+                .map_err(|e| warn!("Indicators: battery capacity: {e}"))
+                .ok()
+                .map(|c| Colour::gradient(gradient, c as f32)),
+            IndicatorSource::CpuTemperature { gradient } => cpu_temperature()
+                .map_err(|e| warn!("Indicators: CPU temperature: {e}"))
+                .ok()
+                .map(|t| Colour::gradient(gradient, t)),
+            IndicatorSource::ThrottlePolicy {
+                balanced,
+                performance,
+                quiet,
+            } => platform
+                .as_ref()?
+                .throttle_thermal_policy()
+                .map_err(|e| warn!("Indicators: throttle policy: {e}"))
+                .ok()
+                .map(|p| match p {
+                    ThrottlePolicy::Balanced => *balanced,
+                    ThrottlePolicy::Performance => *performance,
+                    ThrottlePolicy::Quiet => *quiet,
+                }),
+        }
+    }
+}
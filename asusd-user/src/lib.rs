@@ -4,6 +4,22 @@ pub mod error;
 
 pub mod ctrl_anime;
 
+pub mod ctrl_aura;
+
+pub mod ctrl_config;
+
 pub mod zbus_anime;
 
+pub mod notify;
+
+pub mod indicators;
+
+pub mod openrgb;
+
+pub mod app_rules;
+
+pub mod scheduler;
+
+pub mod session;
+
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,468 @@
+//! Mirror desktop notifications to the `AniMe`, Slash, and keyboard lighting.
+//!
+//! The session bus is monitored for calls to `org.freedesktop.Notifications`,
+//! each notification is checked against the user rules, and the first rule to
+//! match has its reactions played before the previous state is restored.
+
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rog_anime::{ActionData, ActionLoader, AnimTime, AnimeFrames, AnimeType, ScrollDirection};
+use rog_aura::{AuraEffect, AuraModeNum, Colour};
+use rog_dbus::zbus_anime::AnimeProxyBlocking;
+use rog_dbus::zbus_aura::AuraProxyBlocking;
+use rog_dbus::zbus_slash::SlashProxyBlocking;
+use rog_slash::SlashMode;
+use serde::{Deserialize, Serialize};
+use zbus::export::futures_util::StreamExt;
+use zbus::fdo::MonitoringProxy;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, MatchRule, MessageStream};
+
+use crate::ctrl_anime::AnimeRunner;
+use crate::ctrl_aura::AuraRunner;
+use crate::error::Error;
+
+const NOTIFY_IFACE: &str = "org.freedesktop.Notifications";
+
+/// Urgency levels as defined by the desktop notifications spec
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl From<u8> for Urgency {
+    fn from(u: u8) -> Self {
+        match u {
+            0 => Self::Low,
+            2 => Self::Critical,
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// The parts of a notification that rules can match on
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+/// Body of the `Notify` method call
+type NotifyArgs = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    HashMap<String, OwnedValue>,
+    i32,
+);
+
+impl From<NotifyArgs> for Notification {
+    fn from(args: NotifyArgs) -> Self {
+        let (app_name, _, _, summary, body, _, hints, _) = args;
+        let urgency = hints
+            .get("urgency")
+            .and_then(|u| u8::try_from(u).ok())
+            .map(Urgency::from)
+            .unwrap_or_default();
+        Self {
+            app_name,
+            summary,
+            body,
+            urgency,
+        }
+    }
+}
+
+/// What to do when a notification matches a rule
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum NotifyReaction {
+    /// Show the notification summary as text on the `AniMe`
+    AnimeText {
+        font_size: f32,
+        direction: ScrollDirection,
+        speed: f32,
+        brightness: f32,
+    },
+    /// Play any `AniMe` action, such as an image or gif
+    Anime(ActionLoader),
+    /// Switch the Slash to this mode
+    Slash(SlashMode),
+    /// Light the keyboard with a single colour
+    AuraFlash(Colour),
+}
+
+/// A filter for notifications along with the reactions to play
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotifyRule {
+    /// Exact application name to match, `None` matches all applications
+    pub app_name: Option<String>,
+    /// Text the summary must contain, `None` matches all summaries
+    pub summary: Option<String>,
+    /// The lowest urgency this rule will match
+    pub min_urgency: Urgency,
+    /// How long the reactions are shown before the previous state is restored
+    pub duration: Duration,
+    pub reactions: Vec<NotifyReaction>,
+}
+
+impl NotifyRule {
+    pub fn matches(&self, notification: &Notification) -> bool {
+        if let Some(app_name) = self.app_name.as_ref() {
+            if *app_name != notification.app_name {
+                return false;
+            }
+        }
+        if let Some(summary) = self.summary.as_ref() {
+            if !notification.summary.contains(summary.as_str()) {
+                return false;
+            }
+        }
+        notification.urgency >= self.min_urgency
+    }
+}
+
+/// Monitor the session bus for notifications and send the first matching rule
+/// to `tx`. This runs until the connection closes.
+pub async fn watch_notifications(
+    rules: Vec<NotifyRule>,
+    tx: Sender<(NotifyRule, Notification)>,
+) -> Result<(), Error> {
+    // A monitoring connection can not be used for anything else
+    let connection = Connection::session().await?;
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::MethodCall)
+        .interface(NOTIFY_IFACE)?
+        .member("Notify")?
+        .build();
+    MonitoringProxy::new(&connection)
+        .await?
+        .become_monitor(&[rule], 0)
+        .await?;
+
+    let mut stream = MessageStream::from(&connection);
+    while let Some(msg) = stream.next().await {
+        let Ok(msg) = msg else {
+            continue;
+        };
+        let header = msg.header();
+        if msg.message_type() != MessageType::MethodCall
+            || header.interface().map(|i| i.as_str()) != Some(NOTIFY_IFACE)
+            || header.member().map(|m| m.as_str()) != Some("Notify")
+        {
+            continue;
+        }
+
+        let notification: Notification = match msg.body().deserialize::<NotifyArgs>() {
+            Ok(args) => args.into(),
+            Err(e) => {
+                warn!("Could not parse notification: {e}");
+                continue;
+            }
+        };
+
+        if let Some(rule) = rules.iter().find(|r| r.matches(&notification)) {
+            info!("Notification from {} matched a rule", notification.app_name);
+            if tx.send((rule.clone(), notification)).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// State to return to once a reaction is finished
+enum Restore {
+    Slash(SlashMode),
+    AuraMode(AuraEffect),
+    AuraLayer,
+}
+
+/// Plays the reactions for a notification using the system daemon
+pub struct NotifyReactor {
+    anime: Option<(AnimeProxyBlocking<'static>, AnimeType)>,
+    /// Held while a reaction plays. If it has nothing to play once the
+    /// reaction is over, asusd's system animation is started again instead.
+    anime_runner: Option<AnimeRunner>,
+    slash: Option<SlashProxyBlocking<'static>>,
+    aura: Option<AuraProxyBlocking<'static>>,
+    /// Shows the flash colour of a reaction in place of the user effects.
+    /// Without it the flash is set as the asusd mode instead.
+    aura_runner: Option<AuraRunner>,
+}
+
+impl NotifyReactor {
+    pub fn new(
+        anime: Option<(AnimeProxyBlocking<'static>, AnimeType)>,
+        anime_runner: Option<AnimeRunner>,
+        slash: Option<SlashProxyBlocking<'static>>,
+        aura: Option<AuraProxyBlocking<'static>>,
+        aura_runner: Option<AuraRunner>,
+    ) -> Self {
+        Self {
+            anime,
+            anime_runner,
+            slash,
+            aura,
+            aura_runner,
+        }
+    }
+
+    /// Play all reactions in the rule for `rule.duration`, then restore the
+    /// previous state. Blocks until finished.
+    pub fn react(&self, rule: &NotifyRule, notification: &Notification) {
+        let mut restore = Vec::new();
+        let mut anime = None;
+
+        for reaction in &rule.reactions {
+            match reaction {
+                NotifyReaction::AnimeText {
+                    font_size,
+                    direction,
+                    speed,
+                    brightness,
+                } => {
+                    anime = Some(ActionLoader::Text {
+                        text: notification.summary.clone(),
+                        font_size: *font_size,
+                        direction: *direction,
+                        speed: *speed,
+                        time: AnimTime::Infinite,
+                        brightness: *brightness,
+                    });
+                }
+                NotifyReaction::Anime(action) => anime = Some(action.clone()),
+                NotifyReaction::Slash(mode) => {
+                    if let Some(slash) = self.slash.as_ref() {
+                        if let Ok(previous) = slash.slash_mode() {
+                            restore.push(Restore::Slash(previous));
+                        }
+                        slash
+                            .set_slash_mode(*mode)
+                            .map_err(|e| warn!("Notify: set Slash mode: {e}"))
+                            .ok();
+                    }
+                }
+                NotifyReaction::AuraFlash(colour) => {
+                    if let Some(runner) = self.aura_runner.as_ref() {
+                        runner.set_flash(Some(*colour));
+                        restore.push(Restore::AuraLayer);
+                    } else if let Some(aura) = self.aura.as_ref() {
+                        if let Ok(previous) = aura.led_mode_data() {
+                            let effect = AuraEffect {
+                                mode: AuraModeNum::Static,
+                                colour1: *colour,
+                                ..previous.clone()
+                            };
+                            restore.push(Restore::AuraMode(previous));
+                            aura.set_led_mode_data(effect)
+                                .map_err(|e| warn!("Notify: set aura mode: {e}"))
+                                .ok();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(action) = anime {
+            self.play_anime(&action, rule.duration);
+        } else {
+            sleep(rule.duration);
+        }
+
+        for r in restore {
+            match r {
+                Restore::Slash(mode) => {
+                    if let Some(slash) = self.slash.as_ref() {
+                        slash.set_slash_mode(mode).ok();
+                    }
+                }
+                Restore::AuraMode(effect) => {
+                    if let Some(aura) = self.aura.as_ref() {
+                        aura.set_led_mode_data(effect).ok();
+                    }
+                }
+                Restore::AuraLayer => {
+                    if let Some(runner) = self.aura_runner.as_ref() {
+                        runner.set_flash(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Play the action for `duration`, looping animations, then hand the
+    /// display back to whatever was running before
+    fn play_anime(&self, action: &ActionLoader, duration: Duration) {
+        let Some((proxy, anime_type)) = self.anime.as_ref() else {
+            sleep(duration);
+            return;
+        };
+        if !proxy.enable_display().unwrap_or(false) {
+            sleep(duration);
+            return;
+        }
+        let data = match ActionData::from_anime_action(*anime_type, action) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Notify: could not create anime action: {e}");
+                sleep(duration);
+                return;
+            }
+        };
+
+        if let Some(runner) = self.anime_runner.as_ref() {
+            runner.early_return.set(true);
+        }
+
+        match data {
+            ActionData::Animation(frames) => play_frames(proxy, &frames, duration),
+            ActionData::Stream(frames) => play_frames(proxy, &frames, duration),
+            ActionData::Generator(frames) => play_frames(proxy, &frames, duration),
+            ActionData::Image(image) => {
+                proxy.write(*image).ok();
+                sleep(duration);
+            }
+            ActionData::Pause(_)
+            | ActionData::AudioEq
+            | ActionData::SystemInfo
+            | ActionData::TimeDate => sleep(duration),
+        }
+
+        // Writing stopped asusd's system animation. Give the display back to
+        // the user runner if it has something to play, otherwise to asusd.
+        if let Some(runner) = self.anime_runner.as_ref() {
+            runner.early_return.set(false);
+        }
+        if !self
+            .anime_runner
+            .as_ref()
+            .is_some_and(AnimeRunner::has_sequences)
+        {
+            proxy.run_main_loop(true).ok();
+        }
+    }
+}
+
+/// Write the frames in a loop until `duration` has passed
+fn play_frames(proxy: &AnimeProxyBlocking<'_>, frames: &dyn AnimeFrames, duration: Duration) {
+    let start = Instant::now();
+    if frames.frame_count() == 0 {
+        sleep(duration);
+        return;
+    }
+    'outer: loop {
+        for index in 0..frames.frame_count() {
+            if start.elapsed() >= duration {
+                break 'outer;
+            }
+            match frames.frame(index) {
+                Ok(frame) => {
+                    proxy.write(frame.frame().clone()).ok();
+                    sleep(frame.delay());
+                }
+                Err(e) => {
+                    warn!("Notify: could not get anime frame: {e}");
+                    sleep(duration.saturating_sub(start.elapsed()));
+                    break 'outer;
+                }
+            }
+        }
+    }
+    frames.finished();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use zbus::zvariant::{OwnedValue, Value};
+
+    use super::{Notification, NotifyArgs, NotifyRule, Urgency};
+
+    fn rule(app_name: Option<&str>, summary: Option<&str>, min_urgency: Urgency) -> NotifyRule {
+        NotifyRule {
+            app_name: app_name.map(str::to_owned),
+            summary: summary.map(str::to_owned),
+            min_urgency,
+            duration: Duration::from_secs(1),
+            reactions: Vec::new(),
+        }
+    }
+
+    fn notification(app_name: &str, summary: &str, urgency: Urgency) -> Notification {
+        Notification {
+            app_name: app_name.to_owned(),
+            summary: summary.to_owned(),
+            body: String::new(),
+            urgency,
+        }
+    }
+
+    #[test]
+    fn rule_matches() {
+        let mail = notification("Thunderbird", "New mail from Alex", Urgency::Normal);
+        assert!(rule(None, None, Urgency::Low).matches(&mail));
+        assert!(rule(Some("Thunderbird"), None, Urgency::Low).matches(&mail));
+        assert!(rule(None, Some("New mail"), Urgency::Low).matches(&mail));
+        assert!(rule(Some("Thunderbird"), Some("mail"), Urgency::Normal).matches(&mail));
+    }
+
+    #[test]
+    fn rule_does_not_match() {
+        let mail = notification("Thunderbird", "New mail from Alex", Urgency::Normal);
+        // App names must match exactly
+        assert!(!rule(Some("thunderbird"), None, Urgency::Low).matches(&mail));
+        assert!(!rule(Some("Thunder"), None, Urgency::Low).matches(&mail));
+        assert!(!rule(None, Some("Calendar"), Urgency::Low).matches(&mail));
+        assert!(!rule(None, None, Urgency::Critical).matches(&mail));
+        assert!(!rule(Some("Thunderbird"), Some("mail"), Urgency::Critical).matches(&mail));
+    }
+
+    fn args(urgency: Option<u8>) -> NotifyArgs {
+        let mut hints = HashMap::new();
+        if let Some(urgency) = urgency {
+            hints.insert(
+                "urgency".to_owned(),
+                OwnedValue::try_from(Value::from(urgency)).unwrap(),
+            );
+        }
+        (
+            "Thunderbird".to_owned(),
+            0,
+            "mail-icon".to_owned(),
+            "New mail".to_owned(),
+            "Hello".to_owned(),
+            Vec::new(),
+            hints,
+            -1,
+        )
+    }
+
+    #[test]
+    fn notification_from_args() {
+        let notification = Notification::from(args(Some(2)));
+        assert_eq!(notification.app_name, "Thunderbird");
+        assert_eq!(notification.summary, "New mail");
+        assert_eq!(notification.body, "Hello");
+        assert_eq!(notification.urgency, Urgency::Critical);
+
+        assert_eq!(Notification::from(args(Some(0))).urgency, Urgency::Low);
+        // A missing or unknown urgency is normal
+        assert_eq!(Notification::from(args(None)).urgency, Urgency::Normal);
+        assert_eq!(Notification::from(args(Some(7))).urgency, Urgency::Normal);
+    }
+}
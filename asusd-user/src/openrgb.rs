@@ -0,0 +1,645 @@
+//! An `OpenRGB` SDK compatible server so that `OpenRGB`, or any other SDK
+//! client, can control the Aura devices through `asusd` instead of fighting
+//! over the hidraw node.
+//!
+//! Only the parts of the protocol needed for direct control are supported.
+//! Each device presents a single `Direct` mode, with zones and LEDs built from
+//! the `KeyLayout` of the device. LED updates from a client are turned in to
+//! `direct_addressing_raw` calls.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::{debug, info, warn};
+use rog_aura::aura_detection::LedSupportData;
+use rog_aura::keyboard::{AdvancedAuraType, KeyLayout, LedCode, LedUsbPackets, UsbPackets};
+use rog_aura::Colour;
+use rog_dbus::zbus_aura::AuraProxyBlocking;
+
+use crate::error::Error;
+
+/// The port `OpenRGB` uses by default
+pub const OPENRGB_PORT: u16 = 6742;
+/// The highest protocol version supported by this server
+const PROTOCOL_VERSION: u32 = 3;
+const MAGIC: &[u8; 4] = b"ORGB";
+const HEADER_LEN: usize = 16;
+/// More LEDs than any device has
+const MAX_LEDS: u32 = 1024;
+/// The largest packet a client may send, an `UpdateZoneLeds` for `MAX_LEDS`
+/// with room to spare. Anything longer is dropped before it is read.
+const MAX_PACKET_LEN: u32 = 64 + 4 * MAX_LEDS;
+
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const REQUEST_CONTROLLER_DATA: u32 = 1;
+const REQUEST_PROTOCOL_VERSION: u32 = 40;
+const SET_CLIENT_NAME: u32 = 50;
+const REQUEST_PROFILE_LIST: u32 = 150;
+const RGBCONTROLLER_UPDATELEDS: u32 = 1050;
+const RGBCONTROLLER_UPDATEZONELEDS: u32 = 1051;
+const RGBCONTROLLER_UPDATESINGLELED: u32 = 1052;
+
+const DEVICE_TYPE_KEYBOARD: i32 = 5;
+const ZONE_TYPE_LINEAR: i32 = 1;
+const ZONE_TYPE_MATRIX: i32 = 2;
+const MODE_FLAG_HAS_PER_LED_COLOR: u32 = 1 << 5;
+const MODE_COLORS_PER_LED: u32 = 1;
+/// Marks a position in a zone matrix that has no LED
+const MATRIX_NO_LED: u32 = u32::MAX;
+
+/// Anything that can take the raw packets built from client updates. It is
+/// shared with the client threads so that they can write without holding the
+/// devices lock.
+pub trait DirectAddressing: Send + Sync {
+    fn write_packets(&self, packets: UsbPackets) -> Result<(), Error>;
+}
+
+impl DirectAddressing for AuraProxyBlocking<'static> {
+    fn write_packets(&self, packets: UsbPackets) -> Result<(), Error> {
+        Ok(self.direct_addressing_raw(packets)?)
+    }
+}
+
+struct Zone {
+    name: &'static str,
+    kind: i32,
+    /// Index of the first LED of this zone in the device LEDs
+    start: usize,
+    len: usize,
+    /// Height, width, and the zone LED index of each position
+    matrix: Option<(u32, u32, Vec<u32>)>,
+}
+
+/// An Aura device as seen by `OpenRGB` clients
+pub struct OrgbDevice {
+    name: String,
+    description: String,
+    location: String,
+    zoned: bool,
+    zones: Vec<Zone>,
+    leds: Vec<LedCode>,
+    colours: Vec<Colour>,
+    writer: Arc<dyn DirectAddressing>,
+}
+
+impl OrgbDevice {
+    /// Build the zones and LEDs from `layout`. Returns `None` if the layout
+    /// has no direct addressing.
+    pub fn new(
+        name: String,
+        description: String,
+        location: String,
+        layout: &KeyLayout,
+        writer: Arc<dyn DirectAddressing>,
+    ) -> Option<Self> {
+        let mut device = Self {
+            name,
+            description,
+            location,
+            zoned: false,
+            zones: Vec::new(),
+            leds: Vec::new(),
+            colours: Vec::new(),
+            writer,
+        };
+
+        match layout.advanced_type() {
+            AdvancedAuraType::None => return None,
+            AdvancedAuraType::Zoned(codes) => {
+                device.zoned = true;
+                let (lightbar, keyboard): (Vec<LedCode>, Vec<LedCode>) =
+                    codes.iter().partition(|c| c.is_lightbar_zone());
+                device.push_linear("Keyboard", keyboard);
+                device.push_linear("Lightbar", lightbar);
+            }
+            AdvancedAuraType::PerKey => {
+                let mut rows = Vec::new();
+                let mut lightbar = Vec::new();
+                let mut lid = Vec::new();
+                for row in layout.rows() {
+                    let mut keys = Vec::new();
+                    for (led, _) in row.row() {
+                        if led.is_placeholder() {
+                            continue;
+                        } else if led.is_lightbar_zone() {
+                            lightbar.push(*led);
+                        } else if matches!(
+                            led,
+                            LedCode::LidLogo | LedCode::LidLeft | LedCode::LidRight
+                        ) {
+                            lid.push(*led);
+                        } else {
+                            keys.push(*led);
+                        }
+                    }
+                    if !keys.is_empty() {
+                        rows.push(keys);
+                    }
+                }
+                device.push_matrix("Keyboard", rows);
+                device.push_linear("Lightbar", lightbar);
+                device.push_linear("Lid", lid);
+            }
+        }
+        Some(device)
+    }
+
+    fn push_linear(&mut self, name: &'static str, leds: Vec<LedCode>) {
+        let start = self.leds.len();
+        for led in leds {
+            if !self.leds.contains(&led) {
+                self.leds.push(led);
+            }
+        }
+        if self.leds.len() > start {
+            self.zones.push(Zone {
+                name,
+                kind: ZONE_TYPE_LINEAR,
+                start,
+                len: self.leds.len() - start,
+                matrix: None,
+            });
+        }
+        self.colours.resize(self.leds.len(), Colour::default());
+    }
+
+    fn push_matrix(&mut self, name: &'static str, rows: Vec<Vec<LedCode>>) {
+        let start = self.leds.len();
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let mut map = Vec::with_capacity(width * rows.len());
+        for row in &rows {
+            for led in row {
+                if self.leds.contains(led) {
+                    map.push(MATRIX_NO_LED);
+                } else {
+                    map.push((self.leds.len() - start) as u32);
+                    self.leds.push(*led);
+                }
+            }
+            map.resize(map.len() + width - row.len(), MATRIX_NO_LED);
+        }
+        if self.leds.len() > start {
+            self.zones.push(Zone {
+                name,
+                kind: ZONE_TYPE_MATRIX,
+                start,
+                len: self.leds.len() - start,
+                matrix: Some((rows.len() as u32, width as u32, map)),
+            });
+        }
+        self.colours.resize(self.leds.len(), Colour::default());
+    }
+
+    /// The controller description for the requested protocol `version`
+    fn controller_data(&self, version: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(DEVICE_TYPE_KEYBOARD.to_le_bytes());
+        put_str(&mut buf, &self.name);
+        if version >= 1 {
+            put_str(&mut buf, "ASUS");
+        }
+        put_str(&mut buf, &self.description);
+        put_str(&mut buf, crate::VERSION);
+        put_str(&mut buf, "");
+        put_str(&mut buf, &self.location);
+
+        // A single mode, which is direct control of each LED
+        buf.extend(1u16.to_le_bytes());
+        buf.extend(0i32.to_le_bytes());
+        put_str(&mut buf, "Direct");
+        buf.extend(0i32.to_le_bytes());
+        buf.extend(MODE_FLAG_HAS_PER_LED_COLOR.to_le_bytes());
+        // speed min/max, then brightness min/max, then colours min/max
+        let fields = if version >= 3 { 6 } else { 4 };
+        for _ in 0..fields {
+            buf.extend(0u32.to_le_bytes());
+        }
+        // speed, brightness, then direction
+        let fields = if version >= 3 { 3 } else { 2 };
+        for _ in 0..fields {
+            buf.extend(0u32.to_le_bytes());
+        }
+        buf.extend(MODE_COLORS_PER_LED.to_le_bytes());
+        buf.extend(0u16.to_le_bytes());
+
+        buf.extend((self.zones.len() as u16).to_le_bytes());
+        for zone in &self.zones {
+            put_str(&mut buf, zone.name);
+            buf.extend(zone.kind.to_le_bytes());
+            for _ in 0..3 {
+                buf.extend((zone.len as u32).to_le_bytes());
+            }
+            if let Some((height, width, map)) = zone.matrix.as_ref() {
+                buf.extend(((8 + map.len() * 4) as u16).to_le_bytes());
+                buf.extend(height.to_le_bytes());
+                buf.extend(width.to_le_bytes());
+                for idx in map {
+                    buf.extend(idx.to_le_bytes());
+                }
+            } else {
+                buf.extend(0u16.to_le_bytes());
+            }
+        }
+
+        buf.extend((self.leds.len() as u16).to_le_bytes());
+        for (idx, led) in self.leds.iter().enumerate() {
+            put_str(&mut buf, <&str>::from(led));
+            buf.extend((idx as u32).to_le_bytes());
+        }
+
+        buf.extend((self.colours.len() as u16).to_le_bytes());
+        for c in &self.colours {
+            buf.extend([c.r, c.g, c.b, 0]);
+        }
+
+        let mut data = ((buf.len() + 4) as u32).to_le_bytes().to_vec();
+        data.append(&mut buf);
+        data
+    }
+
+    /// Set colours starting from the LED at `start`, extra colours are ignored
+    fn set_colours(&mut self, start: usize, colours: impl Iterator<Item = Colour>) {
+        for (c, colour) in self.colours.iter_mut().skip(start).zip(colours) {
+            *c = colour;
+        }
+    }
+
+    /// The packets for the current colours, and where to write them once the
+    /// devices are unlocked
+    fn update(&self) -> Reply {
+        let mut packets = if self.zoned {
+            LedUsbPackets::new_zoned(true)
+        } else {
+            LedUsbPackets::new_per_key()
+        };
+        for (led, c) in self.leds.iter().zip(&self.colours) {
+            packets.set(*led, c.r, c.g, c.b);
+        }
+        Reply::Update(self.writer.clone(), packets.into())
+    }
+}
+
+/// Find all Aura devices on the system daemon. The layouts are searched for in
+/// `data_dir` for `locale`.
+pub fn find_devices(
+    conn: &zbus::blocking::Connection,
+    data_dir: PathBuf,
+    locale: &str,
+) -> Result<Vec<OrgbDevice>, Error> {
+    let manager = zbus::blocking::fdo::ObjectManagerProxy::new(conn, "org.asuslinux.Daemon", "/")?;
+    let mut devices = Vec::new();
+    for (path, ifaces) in manager.get_managed_objects()? {
+        if !ifaces.keys().any(|k| k.as_str() == "org.asuslinux.Aura") {
+            continue;
+        }
+        // Paths are named by the product ID first
+        let prod_id = path
+            .rsplit('/')
+            .next()
+            .and_then(|p| p.split('_').next())
+            .unwrap_or_default()
+            .to_owned();
+        let support = LedSupportData::get_data(&prod_id);
+        let description = support.device_name.clone();
+        let layout = match KeyLayout::find_layout_for_locale(support, data_dir.clone(), locale) {
+            Ok(layout) => layout,
+            Err(e) => {
+                warn!("OpenRGB: no layout for {path}: {e}");
+                continue;
+            }
+        };
+        let proxy = AuraProxyBlocking::builder(conn)
+            .path(path.clone())?
+            .destination("org.asuslinux.Daemon")?
+            .build()?;
+        if let Some(device) = OrgbDevice::new(
+            format!("ASUS Aura {prod_id}"),
+            description,
+            path.to_string(),
+            &layout,
+            Arc::new(proxy),
+        ) {
+            info!("OpenRGB: added {path}");
+            devices.push(device);
+        }
+    }
+    Ok(devices)
+}
+
+pub struct OrgbServer {
+    devices: Arc<Mutex<Vec<OrgbDevice>>>,
+}
+
+impl OrgbServer {
+    pub fn new(devices: Vec<OrgbDevice>) -> Self {
+        Self {
+            devices: Arc::new(Mutex::new(devices)),
+        }
+    }
+
+    /// Listen for clients on `addr`. This blocks while the listener is open.
+    pub fn listen(&self, addr: impl ToSocketAddrs) -> Result<(), Error> {
+        self.serve(TcpListener::bind(addr)?)
+    }
+
+    /// Accept clients from `listener`, each client is handled on its own
+    /// thread. This blocks while the listener is open.
+    pub fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("OpenRGB: failed to accept client: {e}");
+                    continue;
+                }
+            };
+            let devices = self.devices.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handle_client(stream, &devices) {
+                    info!("OpenRGB: client disconnected: {e}");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_client(mut stream: TcpStream, devices: &Mutex<Vec<OrgbDevice>>) -> Result<(), Error> {
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        stream.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(Error::Io(ErrorKind::InvalidData.into()));
+        }
+        let mut fields = Reader(&header[4..]);
+        let (Some(dev_idx), Some(id), Some(len)) = (fields.u32(), fields.u32(), fields.u32())
+        else {
+            return Err(Error::Io(ErrorKind::InvalidData.into()));
+        };
+        if len > MAX_PACKET_LEN {
+            return Err(Error::Io(ErrorKind::InvalidData.into()));
+        }
+        let mut data = vec![0u8; len as usize];
+        stream.read_exact(&mut data)?;
+        let data = Reader(&data);
+
+        let reply = respond(
+            &mut devices.lock().unwrap_or_else(PoisonError::into_inner),
+            dev_idx,
+            id,
+            data,
+        );
+        // The lock is let go before any I/O, so a slow client or device
+        // doesn't hold up the others
+        match reply {
+            Reply::Packet(dev_idx, data) => write_packet(&mut stream, dev_idx, id, &data)?,
+            Reply::Update(writer, packets) => writer.write_packets(packets)?,
+            Reply::None => {}
+        }
+    }
+}
+
+/// What to do for a request once the devices are unlocked
+enum Reply {
+    /// Send a packet back to the client for the device index
+    Packet(u32, Vec<u8>),
+    /// Write the packets to the device
+    Update(Arc<dyn DirectAddressing>, UsbPackets),
+    None,
+}
+
+fn respond(devices: &mut [OrgbDevice], dev_idx: u32, id: u32, mut data: Reader) -> Reply {
+    match id {
+        REQUEST_CONTROLLER_COUNT => {
+            let count = devices.len() as u32;
+            Reply::Packet(0, count.to_le_bytes().to_vec())
+        }
+        REQUEST_CONTROLLER_DATA => match devices.get(dev_idx as usize) {
+            Some(device) => {
+                let version = data.u32().unwrap_or(0).min(PROTOCOL_VERSION);
+                Reply::Packet(dev_idx, device.controller_data(version))
+            }
+            None => Reply::None,
+        },
+        REQUEST_PROTOCOL_VERSION => Reply::Packet(0, PROTOCOL_VERSION.to_le_bytes().to_vec()),
+        SET_CLIENT_NAME => {
+            let name = String::from_utf8_lossy(data.0);
+            info!("OpenRGB: client connected: {}", name.trim_end_matches('\0'));
+            Reply::None
+        }
+        REQUEST_PROFILE_LIST => {
+            // No profiles, the size includes itself
+            let mut reply = 6u32.to_le_bytes().to_vec();
+            reply.extend(0u16.to_le_bytes());
+            Reply::Packet(0, reply)
+        }
+        RGBCONTROLLER_UPDATELEDS => match devices.get_mut(dev_idx as usize) {
+            Some(device) => {
+                data.u32();
+                let colours = data.colours();
+                device.set_colours(0, colours.into_iter());
+                device.update()
+            }
+            None => Reply::None,
+        },
+        RGBCONTROLLER_UPDATEZONELEDS => {
+            let Some(device) = devices.get_mut(dev_idx as usize) else {
+                return Reply::None;
+            };
+            data.u32();
+            let zone = data.u32().unwrap_or(u32::MAX) as usize;
+            let Some((start, len)) = device.zones.get(zone).map(|z| (z.start, z.len)) else {
+                return Reply::None;
+            };
+            let colours = data.colours();
+            device.set_colours(start, colours.into_iter().take(len));
+            device.update()
+        }
+        RGBCONTROLLER_UPDATESINGLELED => {
+            let Some(device) = devices.get_mut(dev_idx as usize) else {
+                return Reply::None;
+            };
+            let (Some(led), Some(c)) = (data.u32(), data.u32()) else {
+                return Reply::None;
+            };
+            device.set_colours(led as usize, std::iter::once(colour_from(c)));
+            device.update()
+        }
+        _ => {
+            debug!("OpenRGB: ignoring packet {id} for device {dev_idx}");
+            Reply::None
+        }
+    }
+}
+
+fn write_packet(stream: &mut TcpStream, dev_idx: u32, id: u32, data: &[u8]) -> Result<(), Error> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + data.len());
+    packet.extend(MAGIC);
+    packet.extend(dev_idx.to_le_bytes());
+    packet.extend(id.to_le_bytes());
+    packet.extend((data.len() as u32).to_le_bytes());
+    packet.extend(data);
+    stream.write_all(&packet)?;
+    Ok(())
+}
+
+/// Strings are sent with their length, including a null terminator
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(((s.len() + 1) as u16).to_le_bytes());
+    buf.extend(s.as_bytes());
+    buf.push(0);
+}
+
+fn colour_from(c: u32) -> Colour {
+    let [r, g, b, _] = c.to_le_bytes();
+    Colour { r, g, b }
+}
+
+/// Reads little endian values from the front of a packet
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.0.get(..N)?.try_into().ok()?;
+        self.0 = &self.0[N..];
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    /// A `u16` count followed by that many colours
+    fn colours(&mut self) -> Vec<Colour> {
+        let count = self.u16().unwrap_or(0);
+        (0..count)
+            .map_while(|_| self.u32())
+            .map(colour_from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Arc<Mutex<Vec<UsbPackets>>>);
+
+    impl DirectAddressing for Recorder {
+        fn write_packets(&self, packets: UsbPackets) -> Result<(), Error> {
+            self.0.lock().unwrap().push(packets);
+            Ok(())
+        }
+    }
+
+    fn request(stream: &mut TcpStream, dev_idx: u32, id: u32, data: &[u8]) -> Vec<u8> {
+        write_packet(stream, dev_idx, id, data).unwrap();
+        let mut header = [0u8; HEADER_LEN];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..4], MAGIC);
+        assert_eq!(u32::from_le_bytes(header[8..12].try_into().unwrap()), id);
+        let len = u32::from_le_bytes(header[12..].try_into().unwrap());
+        let mut data = vec![0u8; len as usize];
+        stream.read_exact(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn local_client() {
+        let support = LedSupportData {
+            layout_name: "g513i-per-key".to_owned(),
+            advanced_type: AdvancedAuraType::PerKey,
+            ..Default::default()
+        };
+        let data = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../rog-aura/data"));
+        let layout = KeyLayout::find_layout_for_locale(support, data, "US").unwrap();
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let device = OrgbDevice::new(
+            "Test".to_owned(),
+            String::new(),
+            String::new(),
+            &layout,
+            Arc::new(Recorder(written.clone())),
+        )
+        .unwrap();
+        let led_count = device.leds.len();
+        assert!(led_count < MAX_LEDS as usize);
+        assert!(device.zones[0].matrix.is_some());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = OrgbServer::new(vec![device]);
+        std::thread::spawn(move || server.serve(listener));
+        let mut client = TcpStream::connect(addr).unwrap();
+
+        let version = request(
+            &mut client,
+            0,
+            REQUEST_PROTOCOL_VERSION,
+            &3u32.to_le_bytes(),
+        );
+        assert_eq!(version, PROTOCOL_VERSION.to_le_bytes());
+        let count = request(&mut client, 0, REQUEST_CONTROLLER_COUNT, &[]);
+        assert_eq!(count, 1u32.to_le_bytes());
+
+        let data = request(&mut client, 0, REQUEST_CONTROLLER_DATA, &3u32.to_le_bytes());
+        assert_eq!(
+            u32::from_le_bytes(data[..4].try_into().unwrap()) as usize,
+            data.len()
+        );
+        assert_eq!(&data[8..10], &5u16.to_le_bytes());
+        assert_eq!(&data[10..15], b"Test\0");
+
+        // Set every LED to red, then sync on a reply
+        let mut update = Vec::new();
+        update.extend(0u32.to_le_bytes());
+        update.extend((led_count as u16).to_le_bytes());
+        for _ in 0..led_count {
+            update.extend([0xff, 0, 0, 0]);
+        }
+        write_packet(&mut client, 0, RGBCONTROLLER_UPDATELEDS, &update).unwrap();
+        request(&mut client, 0, REQUEST_CONTROLLER_COUNT, &[]);
+
+        let written = written.lock().unwrap();
+        assert_eq!(written.len(), 1);
+        let mut expected = LedUsbPackets::new_per_key();
+        for led in &layout_leds(&layout) {
+            expected.set(*led, 0xff, 0, 0);
+        }
+        assert_eq!(written[0], UsbPackets::from(expected));
+    }
+
+    #[test]
+    fn oversized_packet() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = OrgbServer::new(Vec::new());
+        std::thread::spawn(move || server.serve(listener));
+        let mut client = TcpStream::connect(addr).unwrap();
+
+        // The header asks for far more data than any request needs, so the
+        // client is dropped instead of the server waiting for it
+        let mut header = MAGIC.to_vec();
+        header.extend(0u32.to_le_bytes());
+        header.extend(RGBCONTROLLER_UPDATELEDS.to_le_bytes());
+        header.extend(u32::MAX.to_le_bytes());
+        client.write_all(&header).unwrap();
+        let mut buf = [0u8; 1];
+        assert!(matches!(client.read(&mut buf), Ok(0) | Err(_)));
+    }
+
+    fn layout_leds(layout: &KeyLayout) -> Vec<LedCode> {
+        layout
+            .rows()
+            .flat_map(|r| r.row().map(|(led, _)| *led))
+            .filter(|led| !led.is_placeholder())
+            .collect()
+    }
+}
@@ -0,0 +1,388 @@
+//! Timer driven frame tasks for the user effects.
+//!
+//! Each device gets one task on the executor which shows a frame and then
+//! waits on a timer for the next one to be due, or on a `FrameWaker` if it has
+//! nothing to show. Frames are written with blocking D-Bus calls, so they are
+//! run on the blocking thread pool to keep the executor free for the servers.
+//! All tasks are paused together while the laptop suspends or the lid is
+//! closed, and can also be paused while the session is locked or idle.
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use logind_zbus::manager::ManagerProxy;
+use serde::{Deserialize, Serialize};
+use smol::channel::{bounded, Receiver, Sender};
+use smol::{future, unblock, Executor, Timer};
+use zbus::export::futures_util::StreamExt;
+use zbus::{CacheProperties, Connection};
+
+use crate::error::Error;
+
+/// The lid state has no change signal so is polled, as in asusd
+const LID_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Frames per second of the per-key effects
+    pub aura_fps: u32,
+    /// Highest frames per second of `AniMe` animations, frames due sooner are
+    /// held back
+    pub anime_fps: u32,
+    pub pause_on_suspend: bool,
+    pub pause_on_lid_closed: bool,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            aura_fps: 30,
+            anime_fps: 60,
+            pause_on_suspend: true,
+            pause_on_lid_closed: true,
+        }
+    }
+}
+
+/// Why the frame tasks are paused, they run again once all are cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Suspend = 1,
+    LidClosed = 1 << 1,
+    SessionLocked = 1 << 2,
+    SessionIdle = 1 << 3,
+}
+
+/// When a frame task is next run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Next {
+    /// After this long from the start of the frame
+    After(Duration),
+    /// Only once woken
+    Idle,
+}
+
+/// Something that shows frames on a device
+pub trait FrameTask {
+    /// Show the next frame and say when the one after it is due
+    fn frame(&mut self) -> Next;
+
+    /// Called once when the tasks are paused, to hand the device back to
+    /// asusd
+    fn paused(&mut self) {}
+}
+
+/// Wakes a frame task before its next frame is due, such as when what it
+/// shows has changed. Wakes are merged if the task is busy.
+#[derive(Debug, Clone)]
+pub struct FrameWaker {
+    tx: Sender<()>,
+    rx: Receiver<()>,
+}
+
+impl Default for FrameWaker {
+    fn default() -> Self {
+        let (tx, rx) = bounded(1);
+        Self { tx, rx }
+    }
+}
+
+impl FrameWaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wake(&self) {
+        self.tx.try_send(()).ok();
+    }
+
+    async fn wait(&self) {
+        self.rx.recv().await.ok();
+    }
+
+    /// Wait until `deadline` or until woken, whichever is first
+    async fn wait_until(&self, deadline: Instant) {
+        future::or(self.wait(), async {
+            Timer::at(deadline).await;
+        })
+        .await;
+    }
+}
+
+/// Holds a frame task on its current frame while set, for example while its
+/// content is changed or something else is using the device. Releasing the
+/// hold wakes the task.
+#[derive(Debug, Clone)]
+pub struct Hold {
+    held: Arc<AtomicBool>,
+    waker: FrameWaker,
+}
+
+impl Hold {
+    pub fn new(waker: FrameWaker) -> Self {
+        Self {
+            held: Arc::new(AtomicBool::new(false)),
+            waker,
+        }
+    }
+
+    pub fn set(&self, held: bool) {
+        self.held.store(held, Ordering::SeqCst);
+        if !held {
+            self.waker.wake();
+        }
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// A bit for each `PauseReason`
+    paused: AtomicU8,
+    wakers: Mutex<Vec<FrameWaker>>,
+}
+
+/// Runs the frame tasks and pauses them all together
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    shared: Arc<Shared>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_paused(&self, reason: PauseReason, paused: bool) {
+        let bit = reason as u8;
+        let old = if paused {
+            self.shared.paused.fetch_or(bit, Ordering::SeqCst)
+        } else {
+            self.shared.paused.fetch_and(!bit, Ordering::SeqCst)
+        };
+        if (old & bit != 0) != paused {
+            info!(
+                "Scheduler: {reason:?} {}",
+                if paused { "set" } else { "cleared" }
+            );
+            if let Ok(wakers) = self.shared.wakers.lock() {
+                wakers.iter().for_each(FrameWaker::wake);
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.paused.load(Ordering::SeqCst) != 0
+    }
+
+    /// Run `task` on the executor. Frames are never shown faster than
+    /// `max_fps`, and `waker` wakes the task early.
+    pub fn spawn<T: FrameTask + Send + 'static>(
+        &self,
+        executor: &Executor<'static>,
+        max_fps: u32,
+        waker: FrameWaker,
+        task: T,
+    ) {
+        if let Ok(mut wakers) = self.shared.wakers.lock() {
+            wakers.push(waker.clone());
+        }
+        executor
+            .spawn(self.clone().run(max_fps, waker, task))
+            .detach();
+    }
+
+    async fn run<T: FrameTask + Send + 'static>(
+        self,
+        max_fps: u32,
+        waker: FrameWaker,
+        mut task: T,
+    ) {
+        let min_interval = Duration::from_secs(1) / max_fps.max(1);
+        let mut was_paused = false;
+        loop {
+            if self.is_paused() {
+                if !was_paused {
+                    task = unblock(move || {
+                        task.paused();
+                        task
+                    })
+                    .await;
+                    was_paused = true;
+                }
+                waker.wait().await;
+                continue;
+            }
+            was_paused = false;
+
+            let start = Instant::now();
+            let next;
+            (task, next) = unblock(move || {
+                let next = task.frame();
+                (task, next)
+            })
+            .await;
+            match next {
+                Next::After(delay) => waker.wait_until(start + delay.max(min_interval)).await,
+                Next::Idle => waker.wait().await,
+            }
+        }
+    }
+
+    /// Pause the tasks on the logind events enabled in `config`. Runs for as
+    /// long as any of the enabled events are watched.
+    pub async fn watch_logind(self, config: SchedulerConfig) -> Result<(), Error> {
+        let connection = Connection::system().await?;
+        // The lid state is polled so must not be cached
+        let manager = ManagerProxy::builder(&connection)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+
+        let suspend = async {
+            if !config.pause_on_suspend {
+                return Ok::<(), Error>(());
+            }
+            let mut stream = manager.receive_prepare_for_sleep().await?;
+            while let Some(signal) = stream.next().await {
+                if let Ok(args) = signal.args() {
+                    self.set_paused(PauseReason::Suspend, args.start);
+                }
+            }
+            Ok(())
+        };
+
+        let lid = async {
+            if !config.pause_on_lid_closed {
+                return Ok::<(), Error>(());
+            }
+            loop {
+                if let Ok(closed) = manager.lid_closed().await {
+                    self.set_paused(PauseReason::LidClosed, closed);
+                }
+                Timer::after(LID_POLL_INTERVAL).await;
+            }
+        };
+
+        let (suspend, lid) = future::zip(suspend, lid).await;
+        for result in [suspend, lid] {
+            if let Err(e) = result {
+                warn!("Scheduler: logind watch stopped: {e}");
+            }
+        }
+        debug!("Scheduler: all logind watches stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use smol::{Executor, Timer};
+
+    use super::{FrameTask, FrameWaker, Next, PauseReason, Scheduler};
+
+    struct Counter {
+        frames: Arc<AtomicUsize>,
+        next: Next,
+    }
+
+    impl FrameTask for Counter {
+        fn frame(&mut self) -> Next {
+            self.frames.fetch_add(1, Ordering::SeqCst);
+            self.next
+        }
+    }
+
+    /// Blocks like a frame written to a slow daemon
+    struct Slow(Duration);
+
+    impl FrameTask for Slow {
+        fn frame(&mut self) -> Next {
+            std::thread::sleep(self.0);
+            Next::Idle
+        }
+    }
+
+    #[test]
+    fn frame_pacing() {
+        let executor = Executor::new();
+        let scheduler = Scheduler::new();
+        let fast = Arc::new(AtomicUsize::new(0));
+        let idle = Arc::new(AtomicUsize::new(0));
+        let idle_waker = FrameWaker::new();
+
+        // Asks for 1ms frames but is held to 50 fps
+        scheduler.spawn(
+            &executor,
+            50,
+            FrameWaker::new(),
+            Counter {
+                frames: fast.clone(),
+                next: Next::After(Duration::from_millis(1)),
+            },
+        );
+        scheduler.spawn(
+            &executor,
+            50,
+            idle_waker.clone(),
+            Counter {
+                frames: idle.clone(),
+                next: Next::Idle,
+            },
+        );
+
+        smol::block_on(executor.run(async {
+            Timer::after(Duration::from_millis(200)).await;
+            let frames = fast.load(Ordering::SeqCst);
+            assert!((5..=12).contains(&frames), "{frames} frames");
+            assert_eq!(idle.load(Ordering::SeqCst), 1);
+
+            idle_waker.wake();
+            Timer::after(Duration::from_millis(10)).await;
+            assert_eq!(idle.load(Ordering::SeqCst), 2);
+
+            // Nothing runs while paused, and a wake does not resume
+            scheduler.set_paused(PauseReason::SessionLocked, true);
+            Timer::after(Duration::from_millis(30)).await;
+            let frames = fast.load(Ordering::SeqCst);
+            idle_waker.wake();
+            Timer::after(Duration::from_millis(100)).await;
+            assert_eq!(fast.load(Ordering::SeqCst), frames);
+            assert_eq!(idle.load(Ordering::SeqCst), 2);
+
+            scheduler.set_paused(PauseReason::SessionLocked, false);
+            Timer::after(Duration::from_millis(30)).await;
+            assert!(fast.load(Ordering::SeqCst) > frames);
+            assert_eq!(idle.load(Ordering::SeqCst), 3);
+        }));
+    }
+
+    #[test]
+    fn slow_frames_leave_the_executor_free() {
+        let executor = Executor::new();
+        let scheduler = Scheduler::new();
+        scheduler.spawn(
+            &executor,
+            60,
+            FrameWaker::new(),
+            Slow(Duration::from_millis(500)),
+        );
+        smol::block_on(executor.run(async {
+            // Let the slow frame start, then check other work still runs
+            Timer::after(Duration::from_millis(20)).await;
+            let start = Instant::now();
+            Timer::after(Duration::from_millis(20)).await;
+            assert!(start.elapsed() < Duration::from_millis(200));
+        }));
+    }
+}
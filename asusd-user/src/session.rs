@@ -0,0 +1,468 @@
+//! Dim or pause the keyboard lighting and `AniMe` while the user session is
+//! locked or idle.
+//!
+//! The session is locked while the logind `LockedHint` of this session is set,
+//! or while a screensaver on the session bus is active. It is idle while the
+//! logind `IdleHint` is set. The actions for the state are applied once its
+//! delay has passed, and undone when the state ends.
+
+use std::time::{Duration, Instant};
+
+use config_traits::{StdConfig, StdConfigLoad};
+use log::{info, warn};
+use logind_zbus::session::SessionProxy;
+use rog_aura::LedBrightness;
+use rog_dbus::zbus_aura::AuraProxyBlocking;
+use serde::{Deserialize, Serialize};
+use smol::channel::{unbounded, Sender};
+use smol::{future, Timer};
+use zbus::export::futures_util::StreamExt;
+use zbus::{proxy, Connection};
+
+use crate::config::ConfigAnime;
+use crate::ctrl_anime::{AnimeRunner, Swapped};
+use crate::error::Error;
+use crate::scheduler::{PauseReason, Scheduler};
+
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    fn get_active(&self) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+/// What to show on the `AniMe` while in a session state
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum SessionAnime {
+    /// Turn the display off
+    Off,
+    /// Name of an anime config file in the user config directory to run in
+    /// place of `active_anime`
+    Config(String),
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionActions {
+    /// How long the session must be in the state before acting
+    pub delay: Duration,
+    /// Pause the per-key effects and `AniMe` sequences, which also stops
+    /// `anime: Some(Config(..))` from showing
+    pub pause: bool,
+    /// The previous brightness is restored after
+    pub keyboard_brightness: Option<LedBrightness>,
+    pub anime: Option<SessionAnime>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub locked: SessionActions,
+    pub idle: SessionActions,
+}
+
+impl SessionConfig {
+    fn actions(&self, state: SessionState) -> Option<&SessionActions> {
+        match state {
+            SessionState::Active => None,
+            SessionState::Idle => Some(&self.idle),
+            SessionState::Locked => Some(&self.locked),
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            locked: SessionActions {
+                pause: true,
+                ..Default::default()
+            },
+            idle: SessionActions::default(),
+        }
+    }
+}
+
+/// The locked state takes priority over idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Active,
+    Idle,
+    Locked,
+}
+
+/// Where a change of session state was seen
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    LockedHint,
+    ScreenSaver,
+    GnomeScreenSaver,
+    IdleHint,
+}
+
+/// Follows the session state from the changes seen by each source, and when
+/// the actions for the state are due
+#[derive(Debug)]
+struct SessionTracker {
+    locked: [bool; 3],
+    idle: bool,
+    state: SessionState,
+    /// Set while waiting for the delay of the state to pass
+    due: Option<Instant>,
+}
+
+impl SessionTracker {
+    fn new() -> Self {
+        Self {
+            locked: [false; 3],
+            idle: false,
+            state: SessionState::Active,
+            due: None,
+        }
+    }
+
+    /// Record a change seen by `source` at `now`. Returns `true` if the
+    /// session state changed, in which case the actions of the previous state
+    /// must be undone straight away. The actions of the new state are due
+    /// after its delay.
+    fn update(&mut self, source: Source, on: bool, now: Instant, config: &SessionConfig) -> bool {
+        match source {
+            Source::LockedHint => self.locked[0] = on,
+            Source::ScreenSaver => self.locked[1] = on,
+            Source::GnomeScreenSaver => self.locked[2] = on,
+            Source::IdleHint => self.idle = on,
+        }
+        let next = if self.locked.contains(&true) {
+            SessionState::Locked
+        } else if self.idle {
+            SessionState::Idle
+        } else {
+            SessionState::Active
+        };
+        if next == self.state {
+            return false;
+        }
+        self.state = next;
+        self.due = config.actions(next).map(|a| now + a.delay);
+        true
+    }
+
+    /// The state to apply the actions of once `due` has passed
+    fn take_due(&mut self) -> SessionState {
+        self.due = None;
+        self.state
+    }
+}
+
+/// State to return to once the session is active again
+enum Restore {
+    Pause(PauseReason),
+    Brightness(LedBrightness),
+    AnimeDisplay(bool),
+    Anime(Swapped),
+}
+
+pub struct SessionReactor {
+    config: SessionConfig,
+    scheduler: Scheduler,
+    aura: Option<AuraProxyBlocking<'static>>,
+    /// Set only if the user anime sequence runner is active
+    anime: Option<AnimeRunner>,
+    restore: Vec<Restore>,
+}
+
+impl SessionReactor {
+    pub fn new(
+        config: SessionConfig,
+        scheduler: Scheduler,
+        aura: Option<AuraProxyBlocking<'static>>,
+        anime: Option<AnimeRunner>,
+    ) -> Self {
+        Self {
+            config,
+            scheduler,
+            aura,
+            anime,
+            restore: Vec::new(),
+        }
+    }
+
+    /// Run `f` off the executor, as the D-Bus proxies it uses block
+    async fn unblocked(mut self, f: impl FnOnce(&mut Self) + Send + 'static) -> Self {
+        smol::unblock(move || {
+            f(&mut self);
+            self
+        })
+        .await
+    }
+
+    fn apply(&mut self, state: SessionState) {
+        let Some(actions) = self.config.actions(state).cloned() else {
+            return;
+        };
+        info!("Session: {state:?}");
+        if actions.pause {
+            let reason = if state == SessionState::Locked {
+                PauseReason::SessionLocked
+            } else {
+                PauseReason::SessionIdle
+            };
+            self.scheduler.set_paused(reason, true);
+            self.restore.push(Restore::Pause(reason));
+        }
+        if let (Some(brightness), Some(aura)) = (actions.keyboard_brightness, self.aura.as_ref()) {
+            if let Ok(previous) = aura.brightness() {
+                self.restore.push(Restore::Brightness(previous));
+            }
+            aura.set_brightness(brightness)
+                .map_err(|e| warn!("Session: set keyboard brightness: {e}"))
+                .ok();
+        }
+        if let (Some(anime), Some(runner)) = (actions.anime, self.anime.as_ref()) {
+            match anime {
+                SessionAnime::Off => {
+                    if let Ok(previous) = runner.client.enable_display() {
+                        self.restore.push(Restore::AnimeDisplay(previous));
+                    }
+                    runner.client.set_enable_display(false).ok();
+                }
+                SessionAnime::Config(name) => {
+                    let config = ConfigAnime::new().set_name(name.clone()).load();
+                    match config.create(runner.anime_type) {
+                        Ok(sequences) => {
+                            if let Some(previous) = runner.swap(sequences) {
+                                self.restore.push(Restore::Anime(previous));
+                            }
+                        }
+                        Err(e) => warn!("Session: could not create anime {name}: {e}"),
+                    }
+                }
+            }
+        }
+    }
+
+    fn restore(&mut self) {
+        for r in self.restore.drain(..).rev() {
+            match r {
+                Restore::Pause(reason) => self.scheduler.set_paused(reason, false),
+                Restore::Brightness(brightness) => {
+                    if let Some(aura) = self.aura.as_ref() {
+                        aura.set_brightness(brightness).ok();
+                    }
+                }
+                Restore::AnimeDisplay(enabled) => {
+                    if let Some(runner) = self.anime.as_ref() {
+                        runner.client.set_enable_display(enabled).ok();
+                    }
+                }
+                Restore::Anime(swapped) => {
+                    // Keep a config the user activated in the meantime
+                    if let Some(runner) = self.anime.as_ref() {
+                        runner.swap_back(swapped);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Watch the session and react to it. Runs until none of the session
+    /// state can be watched.
+    pub async fn run(self) -> Result<(), Error> {
+        let system = Connection::system().await?;
+        let session = Connection::session().await?;
+        let (tx, rx) = unbounded();
+
+        let watch = future::zip(
+            watch_logind(system, tx.clone()),
+            watch_screensavers(session, tx),
+        );
+
+        let react = async move {
+            let mut reactor = self;
+            let mut tracker = SessionTracker::new();
+            loop {
+                let event = match tracker.due {
+                    Some(deadline) => {
+                        future::or(async { Some(rx.recv().await) }, async {
+                            Timer::at(deadline).await;
+                            None
+                        })
+                        .await
+                    }
+                    None => Some(rx.recv().await),
+                };
+                match event {
+                    Some(Ok((source, on))) => {
+                        if tracker.update(source, on, Instant::now(), &reactor.config) {
+                            reactor = reactor.unblocked(Self::restore).await;
+                        }
+                    }
+                    // All watches have stopped
+                    Some(Err(_)) => break,
+                    None => {
+                        let state = tracker.take_due();
+                        reactor = reactor.unblocked(move |r| r.apply(state)).await;
+                    }
+                }
+            }
+            reactor.unblocked(Self::restore).await;
+        };
+
+        future::zip(watch, react).await;
+        Ok(())
+    }
+}
+
+async fn watch_logind(connection: Connection, tx: Sender<(Source, bool)>) {
+    let watch = async {
+        // The session this process belongs to, or the graphical session of the
+        // user if run as a user service
+        let session = SessionProxy::builder(&connection)
+            .path("/org/freedesktop/login1/session/auto")?
+            .build()
+            .await?;
+
+        let locked = async {
+            if let Ok(locked) = session.locked_hint().await {
+                tx.send((Source::LockedHint, locked)).await.ok();
+            }
+            let mut stream = session.receive_locked_hint_changed().await;
+            while let Some(change) = stream.next().await {
+                if let Ok(locked) = change.get().await {
+                    tx.send((Source::LockedHint, locked)).await.ok();
+                }
+            }
+        };
+        let idle = async {
+            if let Ok(idle) = session.idle_hint().await {
+                tx.send((Source::IdleHint, idle)).await.ok();
+            }
+            let mut stream = session.receive_idle_hint_changed().await;
+            while let Some(change) = stream.next().await {
+                if let Ok(idle) = change.get().await {
+                    tx.send((Source::IdleHint, idle)).await.ok();
+                }
+            }
+        };
+        future::zip(locked, idle).await;
+        Ok::<(), Error>(())
+    };
+    watch
+        .await
+        .map_err(|e| warn!("Session: logind watch stopped: {e}"))
+        .ok();
+}
+
+/// A screensaver which is not running sends nothing
+async fn watch_screensavers(connection: Connection, tx: Sender<(Source, bool)>) {
+    let watch = |proxy: zbus::Result<ScreenSaverProxy<'static>>, source| {
+        let tx = tx.clone();
+        async move {
+            let proxy = proxy?;
+            let mut stream = proxy.receive_active_changed().await?;
+            if let Ok(active) = proxy.get_active().await {
+                tx.send((source, active)).await.ok();
+            }
+            while let Some(signal) = stream.next().await {
+                if let Ok(args) = signal.args() {
+                    tx.send((source, args.active)).await.ok();
+                }
+            }
+            Ok::<(), Error>(())
+        }
+    };
+    let freedesktop = ScreenSaverProxy::new(&connection).await;
+    // GNOME has the same interface under its own name
+    let gnome = async {
+        ScreenSaverProxy::builder(&connection)
+            .destination("org.gnome.ScreenSaver")?
+            .path("/org/gnome/ScreenSaver")?
+            .interface("org.gnome.ScreenSaver")?
+            .build()
+            .await
+    }
+    .await;
+    let (freedesktop, gnome) = future::zip(
+        watch(freedesktop, Source::ScreenSaver),
+        watch(gnome, Source::GnomeScreenSaver),
+    )
+    .await;
+    for result in [freedesktop, gnome] {
+        if let Err(e) = result {
+            warn!("Session: screensaver watch stopped: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{SessionActions, SessionConfig, SessionState, SessionTracker, Source};
+
+    fn config() -> SessionConfig {
+        SessionConfig {
+            locked: SessionActions {
+                delay: Duration::from_secs(5),
+                ..Default::default()
+            },
+            idle: SessionActions {
+                delay: Duration::from_secs(60),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn locked_takes_priority_over_idle() {
+        let config = config();
+        let now = Instant::now();
+        let mut tracker = SessionTracker::new();
+
+        assert!(tracker.update(Source::IdleHint, true, now, &config));
+        assert_eq!(tracker.state, SessionState::Idle);
+        assert!(tracker.update(Source::ScreenSaver, true, now, &config));
+        assert_eq!(tracker.state, SessionState::Locked);
+        // Still locked by the screensaver, and no longer idle doesn't matter
+        assert!(!tracker.update(Source::IdleHint, false, now, &config));
+        assert!(!tracker.update(Source::LockedHint, true, now, &config));
+        assert!(!tracker.update(Source::ScreenSaver, false, now, &config));
+        assert_eq!(tracker.state, SessionState::Locked);
+        assert!(tracker.update(Source::LockedHint, false, now, &config));
+        assert_eq!(tracker.state, SessionState::Active);
+    }
+
+    #[test]
+    fn actions_wait_for_the_delay_of_each_state() {
+        let config = config();
+        let now = Instant::now();
+        let mut tracker = SessionTracker::new();
+
+        tracker.update(Source::IdleHint, true, now, &config);
+        assert_eq!(tracker.due, Some(now + Duration::from_secs(60)));
+
+        // Locking before idle is due undoes nothing yet applied and waits for
+        // the locked delay instead
+        let later = now + Duration::from_secs(10);
+        assert!(tracker.update(Source::LockedHint, true, later, &config));
+        assert_eq!(tracker.due, Some(later + Duration::from_secs(5)));
+        assert_eq!(tracker.take_due(), SessionState::Locked);
+        assert_eq!(tracker.due, None);
+
+        // Unlocking while still idle undoes the locked actions, then waits for
+        // the idle delay again
+        let unlock = later + Duration::from_secs(30);
+        assert!(tracker.update(Source::LockedHint, false, unlock, &config));
+        assert_eq!(tracker.state, SessionState::Idle);
+        assert_eq!(tracker.due, Some(unlock + Duration::from_secs(60)));
+
+        // Nothing is due once active
+        assert!(tracker.update(Source::IdleHint, false, unlock, &config));
+        assert_eq!(tracker.due, None);
+    }
+}
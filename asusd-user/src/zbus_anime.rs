@@ -62,6 +62,18 @@ trait Daemon {
         brightness: f64,
     ) -> zbus::Result<String>;
 
+    /// InsertGenerator method
+    fn insert_generator(
+        &self,
+        index: u32,
+        generator: &str,
+        time: &(u32, u64, u64, u64),
+        speed: f64,
+        density: f64,
+        brightness: f64,
+        seed: u64,
+    ) -> zbus::Result<String>;
+
     /// InsertPause method
     fn insert_pause(&self, index: u32, millis: u64) -> zbus::Result<String>;
 
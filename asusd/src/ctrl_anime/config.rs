@@ -4,49 +4,48 @@ use config_traits::{StdConfig, StdConfigLoad};
 use rog_anime::error::AnimeError;
 use rog_anime::usb::Brightness;
 use rog_anime::{
-    ActionData, ActionLoader, AnimTime, Animations, AnimeType, DeviceState, Fade, Vec2,
+    ActionLoader, AnimTime, Animations, AnimeType, ColourOptions, DeviceState, Fade, PlayOrder,
+    Sequences, Vec2,
 };
 use serde::{Deserialize, Serialize};
 
 const CONFIG_FILE: &str = "anime.ron";
 
-#[derive(Deserialize, Serialize, Default)]
+#[derive(Deserialize, Serialize)]
 pub struct AnimeConfigCached {
-    pub system: Vec<ActionData>,
-    pub boot: Vec<ActionData>,
-    pub wake: Vec<ActionData>,
-    pub shutdown: Vec<ActionData>,
+    pub system: Sequences,
+    pub boot: Sequences,
+    pub wake: Sequences,
+    pub shutdown: Sequences,
 }
 
 impl AnimeConfigCached {
+    pub fn new(anime_type: AnimeType) -> Self {
+        Self {
+            system: Sequences::new(anime_type),
+            boot: Sequences::new(anime_type),
+            wake: Sequences::new(anime_type),
+            shutdown: Sequences::new(anime_type),
+        }
+    }
+
     pub fn init_from_config(
         &mut self,
         config: &AnimeConfig,
         anime_type: AnimeType,
     ) -> Result<(), AnimeError> {
-        let mut sys = Vec::with_capacity(config.system.len());
-        for ani in &config.system {
-            sys.push(ActionData::from_anime_action(anime_type, ani)?);
-        }
-        self.system = sys;
-
-        let mut boot = Vec::with_capacity(config.boot.len());
-        for ani in &config.boot {
-            boot.push(ActionData::from_anime_action(anime_type, ani)?);
-        }
-        self.boot = boot;
-
-        let mut wake = Vec::with_capacity(config.wake.len());
-        for ani in &config.wake {
-            wake.push(ActionData::from_anime_action(anime_type, ani)?);
-        }
-        self.wake = wake;
-
-        let mut shutdown = Vec::with_capacity(config.shutdown.len());
-        for ani in &config.shutdown {
-            shutdown.push(ActionData::from_anime_action(anime_type, ani)?);
-        }
-        self.shutdown = shutdown;
+        let load = |actions: &[ActionLoader]| -> Result<Sequences, AnimeError> {
+            let mut seq = Sequences::new(anime_type);
+            for (idx, ani) in actions.iter().enumerate() {
+                seq.insert(idx, ani)?;
+            }
+            Ok(seq)
+        };
+        self.system = load(&config.system)?;
+        self.system.set_order(config.system_order);
+        self.boot = load(&config.boot)?;
+        self.wake = load(&config.wake)?;
+        self.shutdown = load(&config.shutdown)?;
         Ok(())
     }
 }
@@ -55,6 +54,9 @@ impl AnimeConfigCached {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AnimeConfig {
     pub system: Vec<ActionLoader>,
+    /// The order the `system` actions are played in
+    #[serde(default)]
+    pub system_order: PlayOrder,
     pub boot: Vec<ActionLoader>,
     pub wake: Vec<ActionLoader>,
     pub shutdown: Vec<ActionLoader>,
@@ -73,6 +75,7 @@ impl Default for AnimeConfig {
     fn default() -> Self {
         AnimeConfig {
             system: Vec::new(),
+            system_order: PlayOrder::default(),
             boot: Vec::new(),
             wake: Vec::new(),
             shutdown: Vec::new(),
@@ -146,6 +149,7 @@ impl AnimeConfig {
                     Some(Duration::from_secs(2)),
                     Duration::from_secs(2),
                 )),
+                colour: ColourOptions::default(),
             }],
             wake: vec![ActionLoader::ImageAnimation {
                 file: "/usr/share/asusd/anime/custom/sonic-run.gif".into(),
@@ -158,6 +162,7 @@ impl AnimeConfig {
                     Some(Duration::from_secs(2)),
                     Duration::from_secs(2),
                 )),
+                colour: ColourOptions::default(),
             }],
             shutdown: vec![ActionLoader::ImageAnimation {
                 file: "/usr/share/asusd/anime/custom/sonic-wait.gif".into(),
@@ -166,6 +171,7 @@ impl AnimeConfig {
                 translation: Vec2::new(3.0, 2.0),
                 brightness: 1.0,
                 time: AnimTime::Infinite,
+                colour: ColourOptions::default(),
             }],
             ..Default::default()
         }
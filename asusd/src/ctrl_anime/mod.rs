@@ -1,11 +1,16 @@
 pub mod config;
+/// Frames written to the `AniMe` over a socket handed out on D-Bus
+pub mod stream;
 /// Implements `CtrlTask`, Reloadable, `ZbusRun`
 pub mod trait_impls;
 
+use std::cell::RefCell;
 use std::convert::TryFrom;
+use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::sleep;
+use std::time::{Duration, Instant};
 
 use ::zbus::export::futures_util::lock::Mutex;
 use config_traits::{StdConfig, StdConfigLoad};
@@ -15,8 +20,12 @@ use rog_anime::usb::{
     get_maybe_anime_type, pkt_flush, pkt_set_brightness, pkt_set_enable_display,
     pkt_set_enable_powersave_anim, pkts_for_init, Brightness,
 };
-use rog_anime::{ActionData, AnimeDataBuffer, AnimePacketType, AnimeType};
+use rog_anime::{
+    ActionData, AnimeDataBuffer, AnimeFrames, AnimePacketType, AnimeType, PlayContext, PowerState,
+    Sequences, TransitionPlayer, RECHECK_RULES, TRANSITION_STEP,
+};
 use rog_platform::hid_raw::HidRaw;
+use rog_platform::power::AsusPower;
 use rog_platform::usb_raw::USBRaw;
 
 use self::config::{AnimeConfig, AnimeConfigCached};
@@ -59,6 +68,9 @@ pub struct CtrlAnime {
     thread_exit: Arc<AtomicBool>,
     // Set to false when the thread exits
     thread_running: Arc<AtomicBool>,
+    // The socket frames are being streamed from, and its ID
+    stream: Option<(u64, UnixStream)>,
+    stream_count: u64,
 }
 
 impl CtrlAnime {
@@ -99,7 +111,7 @@ impl CtrlAnime {
         let mut config = AnimeConfig::new().load();
 
         info!("Device has an AniMe Matrix display: {anime_type:?}");
-        let mut cache = AnimeConfigCached::default();
+        let mut cache = AnimeConfigCached::new(anime_type);
         if let Err(e) = cache.init_from_config(&config, anime_type) {
             error!("Trying to cache the Anime Config failed, will reset to default config: {e:?}");
             config.rename_file_old();
@@ -114,6 +126,8 @@ impl CtrlAnime {
             config,
             thread_exit: Arc::new(AtomicBool::new(false)),
             thread_running: Arc::new(AtomicBool::new(false)),
+            stream: None,
+            stream_count: 0,
         };
         ctrl.do_initialization()?;
 
@@ -127,7 +141,7 @@ impl CtrlAnime {
     ///
     /// Because this also writes to the usb device, other write tries (display
     /// only) *must* get the mutex lock and set the `thread_exit` atomic.
-    async fn run_thread(inner: Arc<Mutex<CtrlAnime>>, actions: Vec<ActionData>, mut once: bool) {
+    async fn run_thread(inner: Arc<Mutex<CtrlAnime>>, actions: Sequences, mut once: bool) {
         if actions.is_empty() {
             warn!("AniMe system actions was empty");
             return;
@@ -180,57 +194,129 @@ impl CtrlAnime {
                 info!("AniMe no previous system thread running (now)");
                 thread_exit.store(false, Ordering::SeqCst);
                 thread_running.store(true, Ordering::SeqCst);
+                // The last frame written, which a transition starts from, and
+                // the transition in to the current action
+                let last = RefCell::new(AnimeDataBuffer::new(anime_type));
+                let blend: RefCell<Option<TransitionPlayer>> = RefCell::new(None);
+                // Blend a frame with the transition in progress, if any
+                let transition = |frame: AnimeDataBuffer| {
+                    let frame = blend
+                        .borrow()
+                        .as_ref()
+                        .and_then(|blend| blend.frame(&frame, Instant::now()))
+                        .unwrap_or(frame);
+                    *last.borrow_mut() = frame.clone();
+                    frame
+                };
+                // Play an animation, returns true if the thread should exit
+                let play = |frames: &dyn AnimeFrames| {
+                    rog_anime::run_animation(frames, &|frame| {
+                        if thread_exit.load(Ordering::Acquire) {
+                            info!("rog-anime: animation sub-loop was asked to exit");
+                            return Ok(true); // Do safe exit
+                        }
+                        inner
+                            .try_lock()
+                            .map(|lock| {
+                                lock.write_data_buffer(transition(frame))
+                                    .map_err(|err| {
+                                        warn!("rog_anime::run_animation:callback {}", err);
+                                    })
+                                    .ok();
+                                false // Don't exit yet
+                            })
+                            .map_or_else(
+                                || {
+                                    warn!("rog_anime::run_animation:callback failed");
+                                    Err(AnimeError::NoFrames)
+                                },
+                                Ok,
+                            )
+                    });
+                    if thread_exit.load(Ordering::Acquire) {
+                        info!("rog-anime: sub-loop exited and main loop exiting now");
+                        return true;
+                    }
+                    false
+                };
+                // The play rules of the actions can depend on the power state
+                let power = AsusPower::new().ok();
+                let context =
+                    || PlayContext::now(power.as_ref().map(PowerState::from).unwrap_or_default());
                 'main: loop {
-                    for action in &actions {
+                    let round = actions.round(&context());
+                    if round.is_empty() {
+                        if once || !actions.has_remaining() {
+                            break 'main;
+                        }
+                        // Nothing can play right now, check the rules again
+                        // later while watching for an exit
+                        let start = Instant::now();
+                        while start.elapsed() < RECHECK_RULES {
+                            if thread_exit.load(Ordering::SeqCst) {
+                                break 'main;
+                            }
+                            sleep(Duration::from_millis(100));
+                        }
+                        continue;
+                    }
+                    for index in round {
                         if thread_exit.load(Ordering::SeqCst) {
                             break 'main;
                         }
+                        let Some(action) = actions.begin(index, &context()) else {
+                            continue;
+                        };
+                        *blend.borrow_mut() = actions
+                            .transition(index)
+                            .filter(|_| !matches!(action, ActionData::Pause(_)))
+                            .map(|t| {
+                                TransitionPlayer::new(t, last.borrow().clone(), Instant::now())
+                            });
                         match action {
                             ActionData::Animation(frames) => {
-                                rog_anime::run_animation(frames, &|frame| {
-                                    if thread_exit.load(Ordering::Acquire) {
-                                        info!("rog-anime: animation sub-loop was asked to exit");
-                                        return Ok(true); // Do safe exit
-                                    }
-                                    inner
-                                        .try_lock()
-                                        .map(|lock| {
-                                            lock.write_data_buffer(frame)
-                                                .map_err(|err| {
-                                                    warn!(
-                                                        "rog_anime::run_animation:callback {}",
-                                                        err
-                                                    );
-                                                })
-                                                .ok();
-                                            false // Don't exit yet
-                                        })
-                                        .map_or_else(
-                                            || {
-                                                warn!("rog_anime::run_animation:callback failed");
-                                                Err(AnimeError::NoFrames)
-                                            },
-                                            Ok,
-                                        )
-                                });
-                                if thread_exit.load(Ordering::Acquire) {
-                                    info!("rog-anime: sub-loop exited and main loop exiting now");
+                                if play(frames) {
+                                    break 'main;
+                                }
+                            }
+                            ActionData::Stream(frames) => {
+                                if play(frames) {
+                                    break 'main;
+                                }
+                            }
+                            ActionData::Generator(frames) => {
+                                if play(frames) {
                                     break 'main;
                                 }
                             }
                             ActionData::Image(image) => {
                                 once = false;
-                                if let Some(lock) = inner.try_lock() {
-                                    lock.write_data_buffer(image.as_ref().clone())
-                                        .map_err(|e| error!("{}", e))
-                                        .ok();
+                                // Blend in to the image before leaving it on
+                                // the display
+                                loop {
+                                    if thread_exit.load(Ordering::SeqCst) {
+                                        break 'main;
+                                    }
+                                    let done = match &*blend.borrow() {
+                                        Some(blend) => blend.remaining(Instant::now()).is_zero(),
+                                        None => true,
+                                    };
+                                    let frame = transition(image.as_ref().clone());
+                                    if let Some(lock) = inner.try_lock() {
+                                        lock.write_data_buffer(frame)
+                                            .map_err(|e| error!("{}", e))
+                                            .ok();
+                                    }
+                                    if done {
+                                        break;
+                                    }
+                                    sleep(TRANSITION_STEP);
                                 }
                             }
                             ActionData::Pause(duration) => sleep(*duration),
-                            ActionData::AudioEq
-                            | ActionData::SystemInfo
-                            | ActionData::TimeDate
-                            | ActionData::Matrix => {}
+                            ActionData::AudioEq | ActionData::SystemInfo | ActionData::TimeDate => {
+                                // Placeholders, nothing to show yet
+                            }
                         }
                     }
                     if thread_exit.load(Ordering::SeqCst) {
@@ -0,0 +1,219 @@
+//! Frames streamed to the `AniMe` over a socket, for renderers such as games
+//! and visualisers that would otherwise call `Write` over D-Bus for every
+//! frame.
+//!
+//! The client is handed one end of a socket pair and writes the raw LED data
+//! of each frame to it, `AnimeType::data_length()` bytes per frame with no
+//! header. Frames are written to the panel no faster than `FRAME_TIME`, a
+//! client writing faster is held back by the socket. When the client closes
+//! the socket the system animation is started again.
+
+use std::io::Read;
+use std::net::Shutdown;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use ::zbus::export::futures_util::lock::Mutex;
+use futures_lite::future::block_on;
+use log::{info, warn};
+use rog_anime::{AnimeDataBuffer, AnimeType};
+use zbus::fdo::{DBusProxy, Error};
+use zbus::message::Header;
+use zbus::names::BusName;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{proxy, Connection};
+
+use super::CtrlAnime;
+
+/// The shortest time between two streamed frames, about 60 a second
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// The ID, user ID, user name, seat and path of a login session
+type SessionInfo = (String, u32, String, String, OwnedObjectPath);
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn list_sessions(&self) -> zbus::Result<Vec<SessionInfo>>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait LoginSession {
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+}
+
+/// Only root, or a user with the active session on a seat, can stream. Those
+/// are the people in front of the display.
+pub async fn check_access(connection: &Connection, header: &Header<'_>) -> Result<(), Error> {
+    let sender = header
+        .sender()
+        .ok_or_else(|| Error::AccessDenied("The caller has no bus name".into()))?;
+    let uid = DBusProxy::new(connection)
+        .await?
+        .get_connection_unix_user(BusName::from(sender.to_owned()))
+        .await?;
+    if uid == 0 {
+        return Ok(());
+    }
+
+    let manager = LoginManagerProxy::new(connection).await?;
+    for (_, session_uid, _, seat, path) in manager.list_sessions().await? {
+        if session_uid != uid || seat.is_empty() {
+            continue;
+        }
+        let session = LoginSessionProxy::builder(connection)
+            .path(path)?
+            .build()
+            .await?;
+        if session.active().await.unwrap_or_default() {
+            return Ok(());
+        }
+    }
+    Err(Error::AccessDenied(format!(
+        "User {uid} does not have an active session"
+    )))
+}
+
+/// The frames written by a client, each given no sooner than `frame_time`
+/// after the one before. Ends when the client closes the socket, and a
+/// partly written last frame is dropped.
+struct FrameReader<R> {
+    reader: R,
+    anime_type: AnimeType,
+    frame_time: Duration,
+    next: Instant,
+}
+
+impl<R: Read> FrameReader<R> {
+    fn new(reader: R, anime_type: AnimeType, frame_time: Duration) -> Self {
+        Self {
+            reader,
+            anime_type,
+            frame_time,
+            next: Instant::now(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = AnimeDataBuffer;
+
+    fn next(&mut self) -> Option<AnimeDataBuffer> {
+        let mut frame = vec![0u8; self.anime_type.data_length()];
+        self.reader.read_exact(&mut frame).ok()?;
+        sleep(self.next.saturating_duration_since(Instant::now()));
+        self.next = Instant::now() + self.frame_time;
+        // The length is always right, as exactly that much was read
+        AnimeDataBuffer::from_vec(self.anime_type, frame).ok()
+    }
+}
+
+impl CtrlAnime {
+    /// Close the open stream, if any. Its thread exits without starting the
+    /// system animation.
+    pub(super) fn close_stream(&mut self) {
+        if let Some((id, old)) = self.stream.take() {
+            info!("AniMe stream {id} replaced");
+            old.shutdown(Shutdown::Both).ok();
+        }
+    }
+
+    /// Stop the system animation and close any stream, so that something
+    /// else can write to the display
+    pub(super) fn stop_writers(&mut self) {
+        self.thread_exit.store(true, Ordering::SeqCst);
+        self.close_stream();
+    }
+
+    /// Stop the system animation and any stream already open, then start
+    /// reading frames from a new socket. The client end is returned.
+    pub async fn open_stream(inner: Arc<Mutex<CtrlAnime>>) -> std::io::Result<OwnedFd> {
+        let (client, daemon) = UnixStream::pair()?;
+        let reader = daemon.try_clone()?;
+
+        let (id, anime_type) = {
+            let mut lock = inner.lock().await;
+            lock.stop_writers();
+            lock.stream_count += 1;
+            lock.stream = Some((lock.stream_count, daemon));
+            (lock.stream_count, lock.anime_type)
+        };
+
+        std::thread::Builder::new()
+            .name("AniMe stream".into())
+            .spawn(move || {
+                info!("AniMe stream {id} opened");
+                for buffer in FrameReader::new(reader, anime_type, FRAME_TIME) {
+                    let lock = block_on(inner.lock());
+                    if lock.stream.as_ref().map(|(current, _)| *current) != Some(id) {
+                        return;
+                    }
+                    // Frames are dropped while the display is turned off
+                    if !lock.config.display_enabled {
+                        continue;
+                    }
+                    lock.write_data_buffer(buffer)
+                        .map_err(|e| warn!("AniMe stream {id}: {e}"))
+                        .ok();
+                }
+
+                // Fall back to the system animation unless another stream
+                // has taken over
+                let actions = {
+                    let mut lock = block_on(inner.lock());
+                    if lock.stream.as_ref().map(|(current, _)| *current) != Some(id) {
+                        return;
+                    }
+                    lock.stream = None;
+                    lock.cache.system.clone()
+                };
+                info!("AniMe stream {id} closed, starting the system animation");
+                block_on(CtrlAnime::run_thread(inner, actions, false));
+            })?;
+        Ok(client.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    use rog_anime::AnimeType;
+
+    use super::FrameReader;
+
+    #[test]
+    fn frames_are_read_whole_and_paced() {
+        let anime_type = AnimeType::GA402;
+        let len = anime_type.data_length();
+        let (mut client, daemon) = UnixStream::pair().unwrap();
+        for value in 1..=3u8 {
+            client.write_all(&vec![value; len]).unwrap();
+        }
+        // Half a frame, then the client goes away
+        client.write_all(&vec![9; len / 2]).unwrap();
+        drop(client);
+
+        let frame_time = Duration::from_millis(20);
+        let start = Instant::now();
+        let frames: Vec<_> = FrameReader::new(daemon, anime_type, frame_time).collect();
+        assert!(start.elapsed() >= frame_time * 2);
+        assert_eq!(frames.len(), 3);
+        for (frame, value) in frames.iter().zip(1..) {
+            assert!(frame.data().iter().all(|&b| b == value));
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use config_traits::StdConfig;
@@ -10,9 +9,12 @@ use rog_anime::usb::{
 };
 use rog_anime::{Animations, AnimeDataBuffer, DeviceState};
 use zbus::export::futures_util::lock::Mutex;
+use zbus::message::Header;
+use zbus::zvariant::OwnedFd;
 use zbus::{interface, CacheProperties, Connection, SignalContext};
 
 use super::config::AnimeConfig;
+use super::stream::check_access;
 use super::CtrlAnime;
 use crate::error::RogError;
 
@@ -49,11 +51,7 @@ impl CtrlAnimeZbus {
     /// Writes a data stream of length. Will force system thread to exit until
     /// it is restarted
     async fn write(&self, input: AnimeDataBuffer) -> zbus::fdo::Result<()> {
-        self.0
-            .lock()
-            .await
-            .thread_exit
-            .store(true, Ordering::SeqCst);
+        self.0.lock().await.stop_writers();
         self.0
             .lock()
             .await
@@ -140,11 +138,7 @@ impl CtrlAnimeZbus {
         self.0.lock().await.config.builtin_anims_enabled = enabled;
         self.0.lock().await.config.write();
         if enabled {
-            self.0
-                .lock()
-                .await
-                .thread_exit
-                .store(true, Ordering::Release);
+            self.0.lock().await.stop_writers();
         }
     }
 
@@ -267,15 +261,30 @@ impl CtrlAnimeZbus {
         self.0.lock().await.config.write();
     }
 
+    /// Returns a socket to write raw frames to, `AnimeType::data_length()`
+    /// bytes each, at up to 60 per second. The system animation is stopped
+    /// until the socket is closed. Only root or the user with the active
+    /// session may call this.
+    async fn open_stream(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<OwnedFd> {
+        check_access(connection, &header).await?;
+        let fd = CtrlAnime::open_stream(self.0.clone())
+            .await
+            .map_err(|err| {
+                warn!("ctrl_anime::open_stream {}", err);
+                zbus::fdo::Error::IOError(err.to_string())
+            })?;
+        Ok(fd.into())
+    }
+
     /// The main loop is the base system set action if the user isn't running
     /// the user daemon
     async fn run_main_loop(&self, start: bool) {
         if start {
-            self.0
-                .lock()
-                .await
-                .thread_exit
-                .store(true, Ordering::SeqCst);
+            self.0.lock().await.stop_writers();
             CtrlAnime::run_thread(
                 self.0.clone(),
                 self.0.lock().await.cache.system.clone(),
@@ -309,11 +318,7 @@ impl crate::CtrlTask for CtrlAnimeZbus {
                 async move {
                     let config = inner.lock().await.config.clone();
                     if config.display_enabled {
-                        inner
-                            .lock()
-                            .await
-                            .thread_exit
-                            .store(true, Ordering::Release); // ensure clean slate
+                        inner.lock().await.stop_writers(); // ensure clean slate
 
                         inner
                             .lock()
@@ -368,6 +373,7 @@ impl crate::CtrlTask for CtrlAnimeZbus {
                         ..
                     } = inner.lock().await.config;
                     if display_enabled && !builtin_anims_enabled {
+                        inner.lock().await.close_stream();
                         if shutting_down {
                             CtrlAnime::run_thread(
                                 inner.clone(),
@@ -472,7 +478,7 @@ impl crate::CtrlTask for CtrlAnimeZbus {
 
 impl crate::Reloadable for CtrlAnimeZbus {
     async fn reload(&mut self) -> Result<(), RogError> {
-        if let Some(lock) = self.0.try_lock() {
+        if let Some(mut lock) = self.0.try_lock() {
             let anim = &lock.config.builtin_anims;
             // Set builtins
             if lock.config.builtin_anims_enabled {
@@ -513,6 +519,7 @@ impl crate::Reloadable for CtrlAnimeZbus {
                     .write_bytes(&pkt_set_enable_powersave_anim(false))
                     .ok();
 
+                lock.close_stream();
                 let action = lock.cache.boot.clone();
                 CtrlAnime::run_thread(self.0.clone(), action, true).await;
             }
@@ -22,6 +22,10 @@ pub struct AuraConfig {
     pub multizone: Option<BTreeMap<AuraModeNum, Vec<AuraEffect>>>,
     pub multizone_on: bool,
     pub enabled: LaptopAuraPower,
+    /// Time in milliseconds to fade between modes and brightness levels, `0`
+    /// changes instantly
+    #[serde(default)]
+    pub transition_time_ms: u32,
 }
 
 impl StdConfig for AuraConfig {
@@ -63,6 +67,7 @@ impl AuraConfig {
             multizone: None,
             multizone_on: false,
             enabled,
+            transition_time_ms: 0,
         };
 
         for n in &support_data.basic_modes {
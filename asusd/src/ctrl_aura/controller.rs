@@ -1,13 +1,14 @@
 use std::collections::HashSet;
+use std::time::Duration;
 
 use config_traits::{StdConfig, StdConfigLoad};
 use dmi_id::DMIID;
 use inotify::Inotify;
 use log::{debug, info, warn};
 use rog_aura::aura_detection::LedSupportData;
-use rog_aura::keyboard::{LedUsbPackets, UsbPackets};
+use rog_aura::keyboard::{AdvancedAuraType, LedUsbPackets, UsbPackets};
 use rog_aura::usb::{LED_APPLY, LED_SET};
-use rog_aura::{AuraDeviceType, AuraEffect, LedBrightness, LED_MSG_LEN};
+use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, Colour, LedBrightness, LED_MSG_LEN};
 use rog_platform::hid_raw::HidRaw;
 use rog_platform::keyboard_led::KeyboardBacklight;
 use udev::Device;
@@ -99,11 +100,66 @@ impl LEDNode {
     }
 }
 
+/// Time between frames of a per-key colour transition
+const TRANSITION_FRAME_TIME: Duration = Duration::from_millis(33);
+
+/// What a transition changes to
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Transition {
+    /// Step the brightness to a new level
+    Brightness(u8),
+    /// Change mode, fading between the main colours of the old and new modes
+    /// and ending at `brightness`
+    Mode {
+        from: Colour,
+        to: Colour,
+        brightness: u8,
+    },
+}
+
+/// One write of a transition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum TransitionStep {
+    /// Fill every key with one colour using direct addressing
+    Fill(Colour),
+    /// Set the brightness level
+    Brightness(u8),
+    /// Write the new mode
+    Mode,
+}
+
+/// Step the brightness one level at a time from `from` to `to`, taking `time`
+/// for the whole change
+fn brightness_steps(from: u8, to: u8, time: Duration) -> Vec<(TransitionStep, Duration)> {
+    if from == to || time.is_zero() {
+        return vec![(TransitionStep::Brightness(to), Duration::ZERO)];
+    }
+    let step_time = time / u32::from(from.abs_diff(to));
+    let levels: Vec<u8> = if to > from {
+        (from + 1..=to).collect()
+    } else {
+        (to..from).rev().collect()
+    };
+    levels
+        .into_iter()
+        .map(|level| {
+            let wait = if level == to {
+                Duration::ZERO
+            } else {
+                step_time
+            };
+            (TransitionStep::Brightness(level), wait)
+        })
+        .collect()
+}
+
 /// Individual controller for one Aura device
 pub struct CtrlKbdLed {
     pub led_type: AuraDeviceType,
     pub led_node: LEDNode,
     pub supported_data: LedSupportData, // TODO: is storing this really required?
+    /// The support DB files that produced `supported_data`
+    pub supported_data_sources: Vec<String>,
     pub per_key_mode_active: bool,
     pub config: AuraConfig,
     pub dbus_path: OwnedObjectPath,
@@ -218,10 +274,13 @@ impl CtrlKbdLed {
 
                 if dmi.product_family.contains("TUF") {
                     info!("AuraControl found a TUF laptop keyboard");
+                    let (supported_data, supported_data_sources) =
+                        LedSupportData::get_data_with_sources("tuf");
                     let ctrl = CtrlKbdLed {
                         led_type: AuraDeviceType::LaptopTuf,
                         led_node: LEDNode::KbdLed(kbd_backlight),
-                        supported_data: LedSupportData::get_data("tuf"),
+                        supported_data,
+                        supported_data_sources,
                         per_key_mode_active: false,
                         config: Self::load_and_update_config("tuf"),
                         dbus_path: dbus_path_for_tuf(),
@@ -260,11 +319,12 @@ impl CtrlKbdLed {
         // New loads data from the DB also
         // let config = Self::init_config(prod_id, data);
 
-        let data = LedSupportData::get_data(device.prod_id());
+        let (data, sources) = LedSupportData::get_data_with_sources(device.prod_id());
         let ctrl = CtrlKbdLed {
             led_type: prod_id,
             led_node: LEDNode::Rog(rgb_led, device),
             supported_data: data.clone(),
+            supported_data_sources: sources,
             per_key_mode_active: false,
             config: AuraConfig::default(),
             dbus_path,
@@ -345,6 +405,16 @@ impl CtrlKbdLed {
             self.config.write();
         }
 
+        let correction = self.supported_data.colour_correction;
+        let mut corrected;
+        let effect = if correction.is_identity() {
+            effect
+        } else {
+            corrected = effect.clone();
+            correction.apply_packets(&mut corrected);
+            &corrected
+        };
+
         let pkt_type = effect[0][1];
         const PER_KEY_TYPE: u8 = 0xbc;
 
@@ -381,6 +451,7 @@ impl CtrlKbdLed {
 
     /// Write the AuraEffect to the device
     pub fn write_effect_and_apply(&mut self, mode: &AuraEffect) -> Result<(), RogError> {
+        let mode = &self.supported_data.colour_correction.apply_effect(mode);
         if let LEDNode::KbdLed(platform) = &self.led_node {
             let buf = [
                 1,
@@ -404,6 +475,90 @@ impl CtrlKbdLed {
         Ok(())
     }
 
+    fn transition_time(&self) -> Duration {
+        Duration::from_millis(self.config.transition_time_ms as u64)
+    }
+
+    /// Per-key devices can fade colours with direct addressing
+    fn can_fade_colours(&self) -> bool {
+        matches!(self.led_node, LEDNode::Rog(..))
+            && self.supported_data.advanced_type == AdvancedAuraType::PerKey
+    }
+
+    /// The main colour of a mode, from the first zone if multizone is on
+    pub(super) fn mode_colour(&self, mode: AuraModeNum) -> Colour {
+        if self.config.multizone_on {
+            if let Some(effect) = self.config.get_multizone(mode).and_then(|z| z.first()) {
+                return effect.colour1;
+            }
+        }
+        self.config
+            .builtins
+            .get(&mode)
+            .map(|e| e.colour1)
+            .unwrap_or_default()
+    }
+
+    /// Plan the writes of `transition` using the configured transition time,
+    /// each paired with the time to wait after it. Devices that can't read
+    /// back their brightness change brightness instantly.
+    ///
+    /// Per-key devices fade from the old to the new main colour with direct
+    /// addressing before the mode is written. Other devices step the
+    /// brightness down to off, write the mode, then step back up.
+    pub(super) fn transition_steps(
+        &self,
+        transition: Transition,
+    ) -> Vec<(TransitionStep, Duration)> {
+        let time = self.transition_time();
+        let current = self.led_node.get_brightness().ok();
+        let (from, to, brightness) = match transition {
+            Transition::Brightness(level) => {
+                return brightness_steps(current.unwrap_or(level), level, time);
+            }
+            Transition::Mode {
+                from,
+                to,
+                brightness,
+            } => (from, to, brightness),
+        };
+
+        let mut steps = Vec::new();
+        if time.is_zero() || self.can_fade_colours() {
+            if !time.is_zero() {
+                let frames = (time.as_millis() / TRANSITION_FRAME_TIME.as_millis()).max(1) as u32;
+                steps.extend((1..=frames).map(|frame| {
+                    let colour = from.lerp(to, frame as f32 / frames as f32);
+                    (TransitionStep::Fill(colour), TRANSITION_FRAME_TIME)
+                }));
+            }
+            steps.push((TransitionStep::Mode, Duration::ZERO));
+            if self.led_node.has_brightness_control() {
+                steps.push((TransitionStep::Brightness(brightness), Duration::ZERO));
+            }
+        } else {
+            steps.extend(brightness_steps(current.unwrap_or(0), 0, time / 2));
+            steps.push((TransitionStep::Mode, Duration::ZERO));
+            let off = current.map_or(brightness, |_| 0);
+            steps.extend(brightness_steps(off, brightness, time / 2));
+        }
+        steps
+    }
+
+    /// Write a fill or brightness step of a transition. Mode steps are written
+    /// by whoever started the transition.
+    pub(super) fn write_transition_step(&mut self, step: TransitionStep) -> Result<(), RogError> {
+        match step {
+            TransitionStep::Fill(colour) => {
+                let mut packets = LedUsbPackets::new_per_key();
+                packets.fill(colour.r, colour.g, colour.b);
+                self.write_effect_block(&packets.into())
+            }
+            TransitionStep::Brightness(level) => self.led_node.set_brightness(level),
+            TransitionStep::Mode => Ok(()),
+        }
+    }
+
     pub(super) fn write_current_config_mode(&mut self) -> Result<(), RogError> {
         if self.config.multizone_on {
             let mode = self.config.current_mode;
@@ -443,13 +598,15 @@ impl CtrlKbdLed {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use rog_aura::aura_detection::LedSupportData;
-    use rog_aura::{AuraDeviceType, AuraModeNum, AuraZone, PowerZones};
+    use rog_aura::{AuraDeviceType, AuraModeNum, AuraZone, ColourCorrection, PowerZones};
     use rog_platform::hid_raw::HidRaw;
     use rog_platform::keyboard_led::KeyboardBacklight;
     use zbus::zvariant::OwnedObjectPath;
 
-    use super::CtrlKbdLed;
+    use super::{brightness_steps, CtrlKbdLed, TransitionStep};
     use crate::ctrl_aura::config::AuraConfig;
     use crate::ctrl_aura::controller::LEDNode;
 
@@ -466,6 +623,7 @@ mod tests {
             basic_zones: vec![],
             advanced_type: rog_aura::keyboard::AdvancedAuraType::None,
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            colour_correction: ColourCorrection::default(),
         };
         let mut controller = CtrlKbdLed {
             led_type: AuraDeviceType::LaptopPost2021,
@@ -474,6 +632,7 @@ mod tests {
                 HidRaw::new("19b6").unwrap(),
             ),
             supported_data: supported_basic_modes,
+            supported_data_sources: Vec::new(),
             per_key_mode_active: false,
             config,
             dbus_path: OwnedObjectPath::default(),
@@ -516,6 +675,7 @@ mod tests {
             basic_zones: vec![AuraZone::Key1, AuraZone::Key2],
             advanced_type: rog_aura::keyboard::AdvancedAuraType::None,
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            colour_correction: ColourCorrection::default(),
         };
         let mut controller = CtrlKbdLed {
             led_type: AuraDeviceType::LaptopPost2021,
@@ -524,6 +684,7 @@ mod tests {
                 HidRaw::new("19b6").unwrap(),
             ),
             supported_data: supported_basic_modes,
+            supported_data_sources: Vec::new(),
             per_key_mode_active: false,
             config,
             dbus_path: OwnedObjectPath::default(),
@@ -549,4 +710,34 @@ mod tests {
         assert_eq!(e[0].zone, AuraZone::Key1);
         assert_eq!(e[1].zone, AuraZone::Key2);
     }
+
+    #[test]
+    fn brightness_steps_one_level_at_a_time() {
+        let time = Duration::from_millis(300);
+        let step = Duration::from_millis(100);
+        assert_eq!(
+            brightness_steps(0, 3, time),
+            vec![
+                (TransitionStep::Brightness(1), step),
+                (TransitionStep::Brightness(2), step),
+                (TransitionStep::Brightness(3), Duration::ZERO),
+            ]
+        );
+        assert_eq!(
+            brightness_steps(3, 1, time),
+            vec![
+                (TransitionStep::Brightness(2), Duration::from_millis(150)),
+                (TransitionStep::Brightness(1), Duration::ZERO),
+            ]
+        );
+        // Nothing to step through, the level is still written once
+        assert_eq!(
+            brightness_steps(2, 2, time),
+            vec![(TransitionStep::Brightness(2), Duration::ZERO)]
+        );
+        assert_eq!(
+            brightness_steps(0, 3, Duration::ZERO),
+            vec![(TransitionStep::Brightness(3), Duration::ZERO)]
+        );
+    }
 }
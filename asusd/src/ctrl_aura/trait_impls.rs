@@ -1,28 +1,148 @@
 use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
 
 use config_traits::StdConfig;
+use futures_lite::future::block_on;
 use log::{debug, error, info, warn};
 use rog_aura::keyboard::{LaptopAuraPower, UsbPackets};
 use rog_aura::{AuraDeviceType, AuraEffect, AuraModeNum, AuraZone, LedBrightness, PowerZones};
+use tokio::sync::oneshot;
 use zbus::export::futures_util::lock::{Mutex, MutexGuard};
 use zbus::export::futures_util::StreamExt;
 use zbus::fdo::Error as ZbErr;
 use zbus::{interface, SignalContext};
 
-use super::controller::CtrlKbdLed;
+use super::controller::{CtrlKbdLed, Transition, TransitionStep};
 use crate::error::RogError;
 use crate::CtrlTask;
 
 pub const AURA_ZBUS_NAME: &str = "Aura";
 pub const AURA_ZBUS_PATH: &str = "/org/asuslinux";
+/// Longest transition time a client can set
+const MAX_TRANSITION_TIME_MS: u32 = 5000;
+
+/// Writes the new mode of a transition, changing the config to match
+type WriteMode = Box<dyn FnOnce(&mut CtrlKbdLed) -> Result<(), RogError> + Send>;
+
+/// A transition waiting for the transition worker
+struct TransitionJob {
+    transition: Transition,
+    write_mode: Option<WriteMode>,
+    done: oneshot::Sender<Result<TransitionEnd, RogError>>,
+}
+
+/// How a transition that did not fail ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransitionEnd {
+    /// The mode was written, or every brightness step was
+    Applied,
+    /// A newer transition cancelled it before it got that far
+    Superseded,
+}
 
 #[derive(Clone)]
-pub struct CtrlAuraZbus(Arc<Mutex<CtrlKbdLed>>, SignalContext<'static>);
+pub struct CtrlAuraZbus(
+    Arc<Mutex<CtrlKbdLed>>,
+    SignalContext<'static>,
+    Sender<TransitionJob>,
+);
 
 impl CtrlAuraZbus {
     pub fn new(controller: CtrlKbdLed, signal: SignalContext<'static>) -> Self {
-        Self(Arc::new(Mutex::new(controller)), signal)
+        let inner = Arc::new(Mutex::new(controller));
+        let (jobs, rx) = channel();
+        let worker = inner.clone();
+        std::thread::spawn(move || Self::run_transitions(worker, rx));
+        Self(inner, signal, jobs)
+    }
+
+    /// Run `transition` on the transition worker and wait for it to finish.
+    /// `write_mode` is called for the mode step, and the config is written
+    /// only once it succeeds. If it fails the config changes are undone.
+    async fn transition(
+        &self,
+        transition: Transition,
+        write_mode: Option<WriteMode>,
+    ) -> Result<TransitionEnd, RogError> {
+        let (done, result) = oneshot::channel();
+        self.2
+            .send(TransitionJob {
+                transition,
+                write_mode,
+                done,
+            })
+            .map_err(|_| RogError::DoTask("Aura transition worker stopped".into()))?;
+        result
+            .await
+            .map_err(|_| RogError::DoTask("Aura transition was dropped".into()))?
+    }
+
+    /// Run transitions one at a time. A new transition cancels the one in
+    /// progress, which then ends as `Superseded` unless its mode was already
+    /// written. The controller is locked only while each step is written, not
+    /// while waiting between steps.
+    fn run_transitions(inner: Arc<Mutex<CtrlKbdLed>>, jobs: Receiver<TransitionJob>) {
+        let mut next = jobs.recv().ok();
+        while let Some(job) = next.take() {
+            let steps = block_on(inner.lock()).transition_steps(job.transition);
+            let mut write_mode = job.write_mode;
+            let mut applied = false;
+            let mut result = Ok(());
+            for (step, wait) in steps {
+                let mut ctrl = block_on(inner.lock());
+                result = match step {
+                    TransitionStep::Mode => match write_mode.take() {
+                        Some(write_mode) => {
+                            let previous = ctrl.config.clone();
+                            let res = write_mode(&mut ctrl);
+                            if res.is_ok() {
+                                ctrl.config.write();
+                                applied = true;
+                            } else {
+                                ctrl.config = previous;
+                            }
+                            res
+                        }
+                        None => Ok(()),
+                    },
+                    step => ctrl.write_transition_step(step),
+                };
+                drop(ctrl);
+                if result.is_err() {
+                    break;
+                }
+                match jobs.recv_timeout(wait) {
+                    Ok(newer) => {
+                        next = Some(newer);
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if let Err(e) = &result {
+                warn!("Aura transition failed: {e}");
+            }
+            let end = if applied || next.is_none() {
+                TransitionEnd::Applied
+            } else {
+                TransitionEnd::Superseded
+            };
+            job.done.send(result.map(|_| end)).ok();
+            if next.is_none() {
+                next = jobs.recv().ok();
+            }
+        }
+    }
+
+    /// Changing mode turns the keyboard back on if it is off
+    fn brightness_for_mode(ctrl: &CtrlKbdLed) -> LedBrightness {
+        if ctrl.config.brightness == LedBrightness::Off {
+            LedBrightness::Med
+        } else {
+            ctrl.config.brightness
+        }
     }
 
     fn update_config(lock: &mut CtrlKbdLed) -> Result<(), RogError> {
@@ -56,8 +176,11 @@ impl CtrlAuraZbus {
     /// Set the keyboard brightness level (0-3)
     #[zbus(property)]
     async fn set_brightness(&mut self, brightness: LedBrightness) -> Result<(), ZbErr> {
-        let ctrl = self.0.lock().await;
-        Ok(ctrl.led_node.set_brightness(brightness.into())?)
+        // A superseded change is still `Ok`, the brightness property reads the
+        // device so the change signal shows whatever level it was left at
+        self.transition(Transition::Brightness(brightness.into()), None)
+            .await?;
+        Ok(())
     }
 
     /// Total levels of brightness available
@@ -90,6 +213,36 @@ impl CtrlAuraZbus {
         Ok(ctrl.supported_data.power_zones.clone())
     }
 
+    /// Time in milliseconds to fade between modes and brightness levels.
+    /// Per-key devices fade colours, other devices step through brightness
+    /// levels. `0` disables transitions.
+    #[zbus(property)]
+    async fn transition_time(&self) -> u32 {
+        let ctrl = self.0.lock().await;
+        ctrl.config.transition_time_ms
+    }
+
+    #[zbus(property)]
+    async fn set_transition_time(&mut self, time_ms: u32) -> Result<(), ZbErr> {
+        if time_ms > MAX_TRANSITION_TIME_MS {
+            return Err(ZbErr::InvalidArgs(format!(
+                "Transition time must be at most {MAX_TRANSITION_TIME_MS}ms"
+            )));
+        }
+        let mut ctrl = self.0.lock().await;
+        ctrl.config.transition_time_ms = time_ms;
+        ctrl.config.write();
+        Ok(())
+    }
+
+    /// The support DB files that the supported modes and zones came from, in
+    /// the order they were applied
+    #[zbus(property)]
+    async fn supported_data_sources(&self) -> Vec<String> {
+        let ctrl = self.0.lock().await;
+        ctrl.supported_data_sources.clone()
+    }
+
     /// The current mode data
     #[zbus(property)]
     async fn led_mode(&self) -> Result<AuraModeNum, ZbErr> {
@@ -103,19 +256,31 @@ impl CtrlAuraZbus {
     /// the effect is stored and config written to disk.
     #[zbus(property)]
     async fn set_led_mode(&mut self, num: AuraModeNum) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
-        ctrl.config.current_mode = num;
-        ctrl.write_current_config_mode()?;
-        if ctrl.config.brightness == LedBrightness::Off {
-            ctrl.config.brightness = LedBrightness::Med;
-        }
-        if ctrl.led_node.has_brightness_control() {
-            ctrl.led_node
-                .set_brightness(ctrl.config.brightness.into())?;
-        }
-        ctrl.config.write();
+        let ctrl = self.0.lock().await;
+        let from = ctrl.mode_colour(ctrl.config.current_mode);
+        let to = ctrl.mode_colour(num);
+        let brightness = Self::brightness_for_mode(&ctrl);
+        drop(ctrl);
+
+        let transition = Transition::Mode {
+            from,
+            to,
+            brightness: brightness.into(),
+        };
+        let end = self
+            .transition(
+                transition,
+                Some(Box::new(move |ctrl| {
+                    ctrl.config.current_mode = num;
+                    ctrl.config.brightness = brightness;
+                    ctrl.write_current_config_mode()
+                })),
+            )
+            .await?;
 
-        self.led_mode_data_invalidate(&self.1).await.ok();
+        if end == TransitionEnd::Applied {
+            self.led_mode_data_invalidate(&self.1).await.ok();
+        }
         Ok(())
     }
 
@@ -136,7 +301,7 @@ impl CtrlAuraZbus {
     /// the effect is stored and config written to disk.
     #[zbus(property)]
     async fn set_led_mode_data(&mut self, effect: AuraEffect) -> Result<(), ZbErr> {
-        let mut ctrl = self.0.lock().await;
+        let ctrl = self.0.lock().await;
         if !ctrl.supported_data.basic_modes.contains(&effect.mode)
             || effect.zone != AuraZone::None
                 && !ctrl.supported_data.basic_zones.contains(&effect.zone)
@@ -146,18 +311,30 @@ impl CtrlAuraZbus {
             )));
         }
 
-        ctrl.write_effect_and_apply(&effect)?;
-        if ctrl.config.brightness == LedBrightness::Off {
-            ctrl.config.brightness = LedBrightness::Med;
-        }
-        if ctrl.led_node.has_brightness_control() {
-            ctrl.led_node
-                .set_brightness(ctrl.config.brightness.into())?;
-        }
-        ctrl.config.set_builtin(effect);
-        ctrl.config.write();
+        let from = ctrl.mode_colour(ctrl.config.current_mode);
+        let brightness = Self::brightness_for_mode(&ctrl);
+        drop(ctrl);
+
+        let transition = Transition::Mode {
+            from,
+            to: effect.colour1,
+            brightness: brightness.into(),
+        };
+        let end = self
+            .transition(
+                transition,
+                Some(Box::new(move |ctrl| {
+                    ctrl.write_effect_and_apply(&effect)?;
+                    ctrl.config.brightness = brightness;
+                    ctrl.config.set_builtin(effect);
+                    Ok(())
+                })),
+            )
+            .await?;
 
-        self.led_mode_invalidate(&self.1).await.ok();
+        if end == TransitionEnd::Applied {
+            self.led_mode_invalidate(&self.1).await.ok();
+        }
         Ok(())
     }
 
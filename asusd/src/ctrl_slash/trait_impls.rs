@@ -111,9 +111,9 @@ impl CtrlSlashZbus {
     }
 
     #[zbus(property)]
-    async fn slash_mode(&self) -> u8 {
+    async fn slash_mode(&self) -> SlashMode {
         let lock = self.0.lock().await;
-        lock.config.slash_interval
+        lock.config.slash_mode
     }
 
     /// Set interval between slash animations (0-255)
@@ -15,6 +15,7 @@ use asusd::ctrl_slash::CtrlSlash;
 use asusd::{print_board_info, start_tasks, CtrlTask, DBUS_NAME};
 use config_traits::{StdConfig, StdConfigLoad1};
 use log::{error, info};
+use rog_decode::Message;
 use zbus::fdo::ObjectManager;
 
 #[tokio::main]
@@ -48,10 +49,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!(" rog-profiles v{}", rog_profiles::VERSION);
     info!("rog-platform v{}", rog_platform::VERSION);
 
+    if env::var_os("ASUSD_DEBUG_PACKETS").is_some_and(|val| val == "1") {
+        info!("Logging every packet written to USB devices");
+        rog_platform::set_packet_observer(log_packet);
+    }
+
     start_daemon().await?;
     Ok(())
 }
 
+/// Log a packet written through `rog_platform` in decoded form
+fn log_packet(prod_id: &str, message: &[u8]) {
+    info!("{prod_id} <- {}", Message::decode(message, None));
+}
+
 /// The actual main loop for the daemon
 async fn start_daemon() -> Result<(), Box<dyn Error>> {
     // let supported = SupportedFunctions::get_supported();
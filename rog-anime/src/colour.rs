@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::image::{Led, Pixel};
+
+/// How the brightness of each LED is rounded to the levels the display can
+/// show. Dithering spreads the rounding over neighbouring LEDs so that smooth
+/// gradients do not turn in to bands.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Dither {
+    /// Round each LED to the nearest level
+    #[default]
+    None,
+    /// A fixed 4x4 Bayer pattern, which does not crawl between the frames of
+    /// an animation
+    Ordered,
+    /// Floyd-Steinberg error diffusion, the smoothest for still images
+    FloydSteinberg,
+}
+
+/// How colour and transparent images are turned in to the brightness of each
+/// LED
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "UncheckedOptions")]
+pub struct ColourOptions {
+    /// The grey level that transparent parts of the image are blended over,
+    /// `0` leaves them unlit
+    pub background: u8,
+    /// Applied to each LED as `(value / 255)^gamma` to match the brightness
+    /// response of the display. `1.0` leaves the values as they are, higher
+    /// values darken the mid tones.
+    pub gamma: f32,
+    pub dither: Dither,
+    /// How many brightness levels to round to, `256` uses every level
+    pub levels: u16,
+}
+
+impl Default for ColourOptions {
+    fn default() -> Self {
+        Self {
+            background: 0,
+            gamma: 1.0,
+            dither: Dither::None,
+            levels: 256,
+        }
+    }
+}
+
+/// `ColourOptions` as read from a file, before they are checked
+#[derive(Deserialize)]
+#[serde(default)]
+struct UncheckedOptions {
+    background: u8,
+    gamma: f32,
+    dither: Dither,
+    levels: u16,
+}
+
+impl Default for UncheckedOptions {
+    fn default() -> Self {
+        let ColourOptions {
+            background,
+            gamma,
+            dither,
+            levels,
+        } = ColourOptions::default();
+        Self {
+            background,
+            gamma,
+            dither,
+            levels,
+        }
+    }
+}
+
+impl TryFrom<UncheckedOptions> for ColourOptions {
+    type Error = String;
+
+    fn try_from(c: UncheckedOptions) -> Result<Self, Self::Error> {
+        if !(c.gamma.is_finite() && c.gamma > 0.0) {
+            return Err(format!("colour gamma must be above 0, not {}", c.gamma));
+        }
+        Ok(Self {
+            background: c.background,
+            gamma: c.gamma,
+            dither: c.dither,
+            levels: c.levels,
+        })
+    }
+}
+
+/// 4x4 Bayer matrix, the order each LED in a block is lit in
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Linear light from an 8-bit sRGB value
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// 8-bit sRGB value from linear light, unrounded
+fn linear_to_srgb(v: f32) -> f32 {
+    let v = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    v * 255.0
+}
+
+/// Converts RGBA to greyscale `Pixel` with the perceptual (Rec. 709)
+/// luminance. The weights apply to linear light, so the sRGB values are
+/// converted to linear and back.
+pub(crate) struct Luminance([f32; 256]);
+
+impl Luminance {
+    pub fn new() -> Self {
+        let mut table = [0.0; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = srgb_to_linear(i as u8);
+        }
+        Self(table)
+    }
+
+    pub fn grey(&self, r: u8, g: u8, b: u8) -> u8 {
+        if r == g && g == b {
+            return r;
+        }
+        let y =
+            0.2126 * self.0[r as usize] + 0.7152 * self.0[g as usize] + 0.0722 * self.0[b as usize];
+        linear_to_srgb(y).round().clamp(0.0, 255.0) as u8
+    }
+
+    pub fn pixel(&self, r: u8, g: u8, b: u8, a: u8) -> Pixel {
+        Pixel {
+            color: self.grey(r, g, b) as u32,
+            alpha: a as f32 / 255.0,
+        }
+    }
+}
+
+impl ColourOptions {
+    /// The brightness of a pixel that has `alpha` coverage and a colour
+    /// premultiplied by that alpha, after blending it over the background and
+    /// applying the gamma
+    pub(crate) fn blend(&self, premultiplied: f32, alpha: f32) -> f32 {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let value = premultiplied + self.background as f32 * (1.0 - alpha);
+        if self.gamma == 1.0 {
+            value
+        } else {
+            (value / 255.0).max(0.0).powf(self.gamma) * 255.0
+        }
+    }
+
+    /// Round the brightness of each LED to `levels`, dithering as set. `leds`
+    /// and `values` are in the order of
+    /// `AnimeImage::generate_image_positioning()`.
+    pub(crate) fn quantise(&self, leds: &[Option<Led>], values: &mut [f32]) -> Vec<u8> {
+        let step = 255.0 / (self.levels.clamp(2, 256) - 1) as f32;
+        let round = |v: f32| ((v / step).round() * step).clamp(0.0, 255.0);
+
+        match self.dither {
+            Dither::None => {
+                for v in values.iter_mut() {
+                    *v = round(*v);
+                }
+            }
+            Dither::Ordered => {
+                for (led, v) in leds.iter().zip(values.iter_mut()) {
+                    if let Some(led) = led {
+                        let x = (led.x().floor() as i32).rem_euclid(4) as usize;
+                        let y = led.y() as usize % 4;
+                        let threshold = (BAYER[y][x] as f32 + 0.5) / 16.0 - 0.5;
+                        *v = round(*v + threshold * step);
+                    }
+                }
+            }
+            Dither::FloydSteinberg => {
+                // Odd rows are offset by half an LED, so there is no LED
+                // directly below. The error that would go there is shared
+                // between the two below.
+                let index: HashMap<(i32, i32), usize> = leds
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, led)| {
+                        led.map(|led| (((led.x() * 2.0) as i32, led.y() as i32), i))
+                    })
+                    .collect();
+                const SPREAD: [(i32, i32, f32); 3] =
+                    [(2, 0, 7.0 / 16.0), (-1, 1, 5.5 / 16.0), (1, 1, 3.5 / 16.0)];
+                for (i, led) in leds.iter().enumerate() {
+                    let Some(led) = led else { continue };
+                    let old = values[i];
+                    let new = round(old);
+                    values[i] = new;
+                    let (x, y) = ((led.x() * 2.0) as i32, led.y() as i32);
+                    for (dx, dy, weight) in SPREAD {
+                        if let Some(n) = index.get(&(x + dx, y + dy)) {
+                            values[*n] += (old - new) * weight;
+                        }
+                    }
+                }
+            }
+        }
+        values.iter().map(|v| v.clamp(0.0, 255.0) as u8).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colour::*;
+    use crate::{AnimeImage, AnimeType};
+
+    #[test]
+    fn luminance() {
+        let lum = Luminance::new();
+        assert_eq!(lum.grey(0, 0, 0), 0);
+        assert_eq!(lum.grey(255, 255, 255), 255);
+        assert_eq!(lum.grey(128, 128, 128), 128);
+        // Green looks far brighter than blue
+        assert!(lum.grey(0, 255, 0) > 200);
+        assert!(lum.grey(0, 0, 255) < 90);
+        assert!(lum.grey(255, 0, 0) > lum.grey(0, 0, 255));
+    }
+
+    #[test]
+    fn blend_over_background() {
+        let opts = ColourOptions {
+            background: 100,
+            ..Default::default()
+        };
+        assert_eq!(opts.blend(0.0, 0.0), 100.0);
+        assert_eq!(opts.blend(200.0, 1.0), 200.0);
+        assert_eq!(opts.blend(100.0, 0.5), 150.0);
+
+        let opts = ColourOptions {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(opts.blend(255.0, 1.0), 255.0);
+        assert!((opts.blend(127.5, 1.0) - 63.75).abs() < 0.01);
+    }
+
+    #[test]
+    fn dither_keeps_average() {
+        let leds = AnimeImage::generate_image_positioning(AnimeType::GA402);
+        let lit = leds.iter().flatten().count() as f32;
+        for dither in [Dither::None, Dither::Ordered, Dither::FloydSteinberg] {
+            let opts = ColourOptions {
+                dither,
+                levels: 2,
+                ..Default::default()
+            };
+            let mut values = vec![64.0; leds.len()];
+            let out = opts.quantise(&leds, &mut values);
+            assert!(out.iter().all(|v| *v == 0 || *v == 255));
+            let mean = leds
+                .iter()
+                .zip(&out)
+                .filter(|(led, _)| led.is_some())
+                .map(|(_, v)| *v as f32)
+                .sum::<f32>()
+                / lit;
+            if dither == Dither::None {
+                assert_eq!(mean, 0.0);
+            } else {
+                assert!((mean - 64.0).abs() < 8.0, "{dither:?} mean {mean}");
+            }
+        }
+    }
+    #[test]
+    fn gamma_checked_on_load() {
+        let opts: ColourOptions = ron::from_str("(gamma: 2.2)").unwrap();
+        assert_eq!(opts.gamma, 2.2);
+        assert_eq!(opts.levels, 256);
+        assert_eq!(
+            ron::from_str::<ColourOptions>("()").unwrap(),
+            Default::default()
+        );
+        for gamma in ["0.0", "-1.0", "inf", "NaN"] {
+            assert!(ron::from_str::<ColourOptions>(&format!("(gamma: {gamma})")).is_err());
+        }
+    }
+}
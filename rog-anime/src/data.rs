@@ -1,25 +1,28 @@
 use std::convert::TryFrom;
+use std::fmt;
 use std::str::FromStr;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use log::info;
-use serde::{Deserialize, Serialize};
+use log::{info, warn};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use typeshare::typeshare;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::{OwnedValue, Type, Value};
 
 use crate::error::{AnimeError, Result};
+use crate::model::PACKET_PREFIXES;
 use crate::usb::{AnimAwake, AnimBooting, AnimShutdown, AnimSleeping, Brightness};
-use crate::{AnimTime, AnimeGif};
+use crate::{anime_models, AnimTime, AnimeFrames, AnimeModel};
 
 /// The first 7 bytes of a USB packet are accounted for by `USB_PREFIX1` and
 /// `USB_PREFIX2`
-const BLOCK_START: usize = 7;
+pub(crate) const BLOCK_START: usize = 7;
 /// *Not* inclusive, the byte before this is the final for each "pane"
-const BLOCK_END: usize = 634;
+pub(crate) const BLOCK_END: usize = 634;
 /// Individual usable data length of each USB packet
-const PANE_LEN: usize = BLOCK_END - BLOCK_START;
+pub(crate) const PANE_LEN: usize = BLOCK_END - BLOCK_START;
 
 /// First packet is for GA401 + GA402
 pub const USB_PREFIX1: [u8; 7] = [0x5e, 0xc0, 0x02, 0x01, 0x00, 0x73, 0x02];
@@ -55,54 +58,99 @@ pub struct DeviceState {
     pub brightness_on_battery: Brightness,
 }
 
-#[typeshare]
+/// An `AniMe` model, by the name of its entry in `data/anime_models.ron`.
+/// Names that are not in the file are `AnimeType::Unsupported`, which has the
+/// geometry of the GA402.
+#[typeshare(serialized_as = "String")]
 #[cfg_attr(feature = "dbus", derive(Type), zvariant(signature = "s"))]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
-pub enum AnimeType {
-    GA401,
-    GA402,
-    GU604,
-    Unsupported,
-}
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct AnimeType(&'static str);
 
-impl FromStr for AnimeType {
-    type Err = AnimeError;
+impl AnimeType {
+    pub const GA401: Self = Self("GA401");
+    pub const GA402: Self = Self("GA402");
+    pub const GU604: Self = Self("GU604");
+    #[allow(non_upper_case_globals)]
+    pub const Unsupported: Self = Self("Unsupported");
+
+    /// The model with this name, ignoring case
+    pub fn named(name: &str) -> Self {
+        anime_models()
+            .iter()
+            .find(|m| m.name.eq_ignore_ascii_case(name))
+            .map_or(Self::Unsupported, |m| Self(m.name.as_str()))
+    }
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        Ok(match s {
-            "ga401" | "GA401" => Self::GA401,
-            "ga402" | "GA402" => Self::GA402,
-            "gu604" | "GU604" => Self::GU604,
-            _ => Self::Unsupported,
-        })
+    /// The model used on a laptop with this DMI board name
+    pub fn from_board_name(board_name: &str) -> Self {
+        anime_models()
+            .iter()
+            .find(|m| {
+                m.board_names
+                    .iter()
+                    .any(|b| board_name.contains(b.as_str()))
+            })
+            .map_or(Self::Unsupported, |m| Self(m.name.as_str()))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    /// The geometry of the model
+    pub fn model(&self) -> &'static AnimeModel {
+        let models = anime_models();
+        models
+            .iter()
+            .find(|m| m.name == self.0)
+            .or_else(|| models.iter().find(|m| m.name == Self::GA402.0))
+            .expect("data/anime_models.ron has no GA402")
     }
-}
 
-impl AnimeType {
     /// The width of diagonal images
     pub fn width(&self) -> usize {
-        match self {
-            AnimeType::GU604 => 70,
-            _ => 74,
-        }
+        self.model().diagonal_size.0
     }
 
     /// The height of diagonal images
     pub fn height(&self) -> usize {
-        match self {
-            AnimeType::GA401 => 36,
-            AnimeType::GU604 => 43,
-            _ => 39,
-        }
+        self.model().diagonal_size.1
     }
 
     /// The length of usable data for this type
     pub fn data_length(&self) -> usize {
-        match self {
-            AnimeType::GA401 => PANE_LEN * 2,
-            AnimeType::GU604 => PANE_LEN * 3,
-            _ => PANE_LEN * 3,
+        self.model().data_length()
+    }
+}
+
+impl FromStr for AnimeType {
+    type Err = AnimeError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::named(s))
+    }
+}
+
+impl fmt::Debug for AnimeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Serialize for AnimeType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnimeType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let anime_type = Self::named(&name);
+        if anime_type == Self::Unsupported && !name.eq_ignore_ascii_case(Self::Unsupported.0) {
+            return Err(D::Error::custom(format!("unknown AniMe model {name}")));
         }
+        Ok(anime_type)
     }
 }
 
@@ -138,6 +186,12 @@ impl AnimeDataBuffer {
         &mut self.data
     }
 
+    /// The display the data is laid out for
+    #[inline]
+    pub fn anime_type(&self) -> AnimeType {
+        self.anime
+    }
+
     /// Create from a vector of bytes
     ///
     /// # Errors
@@ -163,113 +217,162 @@ impl TryFrom<AnimeDataBuffer> for AnimePacketType {
             return Err(AnimeError::DataBufferLength);
         }
 
-        let mut buffers = match anime.anime {
-            AnimeType::GA401 => vec![[0; 640]; 2],
-            AnimeType::GA402 | AnimeType::GU604 | AnimeType::Unsupported => vec![[0; 640]; 3],
-        };
-
+        let mut buffers = vec![[0; 640]; anime.anime.model().packets];
         for (idx, chunk) in anime.data.as_slice().chunks(PANE_LEN).enumerate() {
+            buffers[idx][..7].copy_from_slice(&PACKET_PREFIXES[idx]);
             buffers[idx][BLOCK_START..BLOCK_END].copy_from_slice(chunk);
         }
-        buffers[0][..7].copy_from_slice(&USB_PREFIX1);
-        buffers[1][..7].copy_from_slice(&USB_PREFIX2);
-
-        if matches!(
-            anime.anime,
-            AnimeType::GA402 | AnimeType::GU604 | AnimeType::Unsupported
-        ) {
-            buffers[2][..7].copy_from_slice(&USB_PREFIX3);
-        }
         Ok(buffers)
     }
 }
 
-/// This runs the animations as a blocking loop by using the `callback` to write
-/// data
-///
-/// If `callback` is `Ok(true)` then `run_animation` will exit the animation
-/// loop early.
-pub fn run_animation(frames: &AnimeGif, callback: &dyn Fn(AnimeDataBuffer) -> Result<bool>) {
-    let mut count = 0;
-    let start = Instant::now();
-
-    let mut timed = false;
-    let mut run_time = frames.total_frame_time();
-    if let AnimTime::Fade(time) = frames.duration() {
-        if let Some(middle) = time.show_for() {
-            run_time = middle + time.total_fade_time();
+/// Steps through the frames of an `AnimeGif` or `AnimeStream`, applying the
+/// fades and run time of its `AnimTime`. The caller decides how to wait
+/// between frames, so this can be driven by a timer instead of a blocking
+/// loop.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    start: Option<Instant>,
+    index: usize,
+    count: u32,
+    finished: bool,
+    timed: bool,
+    run_time: Duration,
+    fade_in: Duration,
+    fade_out: Duration,
+    fade_in_step: f32,
+    fade_in_accum: f32,
+    fade_out_step: f32,
+}
+
+impl AnimationPlayer {
+    pub fn new(frames: &dyn AnimeFrames) -> Self {
+        let mut timed = false;
+        let mut run_time = frames.total_frame_time();
+        if let AnimTime::Fade(time) = frames.duration() {
+            if let Some(middle) = time.show_for() {
+                run_time = middle + time.total_fade_time();
+            }
+            // add a small buffer
+            run_time += Duration::from_millis(250);
+            timed = true;
+        } else if let AnimTime::Time(time) = frames.duration() {
+            run_time = time;
+            timed = true;
+        }
+
+        let mut fade_in = Duration::from_millis(0);
+        let mut fade_out = Duration::from_millis(0);
+        let mut fade_in_step = 0.0;
+        let mut fade_out_step = 0.0;
+        if let AnimTime::Fade(time) = frames.duration() {
+            fade_in = time.fade_in();
+            fade_out = time.fade_out();
+            fade_in_step = 1.0 / fade_in.as_secs_f32();
+            fade_out_step = 1.0 / fade_out.as_secs_f32();
+
+            if time.total_fade_time() > run_time {
+                println!("Total fade in/out time larger than gif run time. Setting fades to half");
+                fade_in = run_time / 2;
+                fade_in_step = 1.0 / (run_time / 2).as_secs_f32();
+
+                fade_out = run_time / 2;
+                fade_out_step = 1.0 / (run_time / 2).as_secs_f32();
+            }
         }
-        // add a small buffer
-        run_time += Duration::from_millis(250);
-        timed = true;
-    } else if let AnimTime::Time(time) = frames.duration() {
-        run_time = time;
-        timed = true;
-    }
 
-    // After setting up all the data
-    let mut fade_in = Duration::from_millis(0);
-    let mut fade_out = Duration::from_millis(0);
-    let mut fade_in_step = 0.0;
-    let mut fade_in_accum = 0.0;
-    let mut fade_out_step = 0.0;
-    let mut fade_out_accum;
-    if let AnimTime::Fade(time) = frames.duration() {
-        fade_in = time.fade_in();
-        fade_out = time.fade_out();
-        fade_in_step = 1.0 / fade_in.as_secs_f32();
-        fade_out_step = 1.0 / fade_out.as_secs_f32();
-
-        if time.total_fade_time() > run_time {
-            println!("Total fade in/out time larger than gif run time. Setting fades to half");
-            fade_in = run_time / 2;
-            fade_in_step = 1.0 / (run_time / 2).as_secs_f32();
-
-            fade_out = run_time / 2;
-            fade_out_step = 1.0 / (run_time / 2).as_secs_f32();
+        Self {
+            start: None,
+            index: 0,
+            count: 0,
+            finished: frames.frame_count() == 0,
+            timed,
+            run_time,
+            fade_in,
+            fade_out,
+            fade_in_step,
+            fade_in_accum: 0.0,
+            fade_out_step,
         }
     }
 
-    'animation: loop {
-        for frame in frames.frames() {
-            let frame_start = Instant::now();
-            let mut output = frame.frame().clone();
-
-            if let AnimTime::Fade(_) = frames.duration() {
-                if frame_start <= start + fade_in {
-                    for pixel in output.data_mut() {
-                        *pixel = (*pixel as f32 * fade_in_accum) as u8;
-                    }
-                    fade_in_accum = fade_in_step * (frame_start - start).as_secs_f32();
-                } else if frame_start > (start + run_time) - fade_out {
-                    if run_time > (frame_start - start) {
-                        fade_out_accum =
-                            fade_out_step * (run_time - (frame_start - start)).as_secs_f32();
-                    } else {
-                        fade_out_accum = 0.0;
-                    }
-                    for pixel in output.data_mut() {
-                        *pixel = (*pixel as f32 * fade_out_accum) as u8;
-                    }
-                }
+    /// The next frame to write at `now` and how long to show it for, or `None`
+    /// once the animation has finished. The animation starts at the time of
+    /// the first call.
+    pub fn next_frame(
+        &mut self,
+        frames: &dyn AnimeFrames,
+        now: Instant,
+    ) -> Option<(AnimeDataBuffer, Duration)> {
+        if self.finished {
+            return None;
+        }
+        let start = *self.start.get_or_insert(now);
+        let frame = match frames.frame(self.index) {
+            Ok(frame) => frame,
+            Err(e) => {
+                warn!("rog-anime: could not get frame {}: {e}", self.index);
+                self.finish(frames);
+                return None;
             }
+        };
+        let mut output = frame.frame().clone();
 
-            // TODO: Log this error
-            if matches!(callback(output), Ok(true)) {
-                info!("rog-anime: animation frame-loop callback asked to exit early");
-                return;
+        if let AnimTime::Fade(_) = frames.duration() {
+            if now <= start + self.fade_in {
+                for pixel in output.data_mut() {
+                    *pixel = (*pixel as f32 * self.fade_in_accum) as u8;
+                }
+                self.fade_in_accum = self.fade_in_step * (now - start).as_secs_f32();
+            } else if now > (start + self.run_time) - self.fade_out {
+                let fade_out_accum = if self.run_time > (now - start) {
+                    self.fade_out_step * (self.run_time - (now - start)).as_secs_f32()
+                } else {
+                    0.0
+                };
+                for pixel in output.data_mut() {
+                    *pixel = (*pixel as f32 * fade_out_accum) as u8;
+                }
             }
+        }
 
-            if timed && Instant::now().duration_since(start) > run_time {
-                break 'animation;
-            }
-            sleep(frame.delay());
+        if self.timed && now.duration_since(start) > self.run_time {
+            self.finish(frames);
+            return Some((output, Duration::ZERO));
         }
-        if let AnimTime::Count(times) = frames.duration() {
-            count += 1;
-            if count >= times {
-                break 'animation;
+
+        self.index += 1;
+        if self.index == frames.frame_count() {
+            self.index = 0;
+            if let AnimTime::Count(times) = frames.duration() {
+                self.count += 1;
+                if self.count >= times {
+                    self.finish(frames);
+                }
             }
         }
+        Some((output, frame.delay()))
+    }
+
+    fn finish(&mut self, frames: &dyn AnimeFrames) {
+        self.finished = true;
+        frames.finished();
+    }
+}
+
+/// This runs the animations as a blocking loop by using the `callback` to write
+/// data
+///
+/// If `callback` is `Ok(true)` then `run_animation` will exit the animation
+/// loop early.
+pub fn run_animation(frames: &dyn AnimeFrames, callback: &dyn Fn(AnimeDataBuffer) -> Result<bool>) {
+    let mut player = AnimationPlayer::new(frames);
+    while let Some((output, delay)) = player.next_frame(frames, Instant::now()) {
+        // TODO: Log this error
+        if matches!(callback(output), Ok(true)) {
+            info!("rog-anime: animation frame-loop callback asked to exit early");
+            return;
+        }
+        sleep(delay);
     }
 }
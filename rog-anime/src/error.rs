@@ -1,8 +1,9 @@
 use std::error::Error;
 use std::fmt;
 
-use gif::DecodingError;
+use gif::{DecodingError, EncodingError};
 use png_pong::decode::Error as PngError;
+use png_pong::encode::Error as PngEncodeError;
 
 pub type Result<T> = std::result::Result<T, AnimeError>;
 
@@ -11,7 +12,10 @@ pub enum AnimeError {
     NoFrames,
     Io(std::io::Error),
     Png(PngError),
+    PngEncode(PngEncodeError),
     Gif(DecodingError),
+    GifEncode(EncodingError),
+    WebP(image_webp::DecodingError),
     Format,
     /// The input was incorrect size, expected size is `IncorrectSize(width,
     /// height)`
@@ -21,6 +25,9 @@ pub enum AnimeError {
     NoDevice,
     UnsupportedDevice,
     InvalidBrightness(f32),
+    InvalidFramerate(f32),
+    InvalidSpeed(f32),
+    InvalidFontSize(f32),
     DataBufferLength,
     PixelGifWidth(usize),
     PixelGifHeight(usize),
@@ -35,7 +42,10 @@ impl fmt::Display for AnimeError {
             AnimeError::NoFrames => write!(f, "No frames in PNG"),
             AnimeError::Io(e) => write!(f, "Could not open: {}", e),
             AnimeError::Png(e) => write!(f, "PNG error: {}", e),
+            AnimeError::PngEncode(e) => write!(f, "PNG encoding error: {}", e),
             AnimeError::Gif(e) => write!(f, "GIF error: {}", e),
+            AnimeError::GifEncode(e) => write!(f, "GIF encoding error: {}", e),
+            AnimeError::WebP(e) => write!(f, "WebP error: {}", e),
             AnimeError::Format => write!(f, "PNG file is not 8bit greyscale"),
             AnimeError::IncorrectSize(width, height) => write!(
                 f,
@@ -55,6 +65,15 @@ impl fmt::Display for AnimeError {
                 "Image brightness must be between 0.0 and 1.0 (inclusive), was {}",
                 bright
             ),
+            AnimeError::InvalidFramerate(fps) => {
+                write!(f, "Frame rate must be greater than 0.0, was {fps}")
+            }
+            AnimeError::InvalidSpeed(speed) => {
+                write!(f, "Speed must be greater than 0.0, was {speed}")
+            }
+            AnimeError::InvalidFontSize(size) => {
+                write!(f, "Font size must be greater than 0.0, was {size}")
+            }
             AnimeError::PixelGifWidth(n) => {
                 write!(f, "The gif used for pixel-perfect gif is is wider than {n}")
             }
@@ -82,6 +101,13 @@ impl From<PngError> for AnimeError {
     }
 }
 
+impl From<PngEncodeError> for AnimeError {
+    #[inline]
+    fn from(err: PngEncodeError) -> Self {
+        AnimeError::PngEncode(err)
+    }
+}
+
 impl From<DecodingError> for AnimeError {
     #[inline]
     fn from(err: DecodingError) -> Self {
@@ -89,6 +115,20 @@ impl From<DecodingError> for AnimeError {
     }
 }
 
+impl From<EncodingError> for AnimeError {
+    #[inline]
+    fn from(err: EncodingError) -> Self {
+        AnimeError::GifEncode(err)
+    }
+}
+
+impl From<image_webp::DecodingError> for AnimeError {
+    #[inline]
+    fn from(err: image_webp::DecodingError) -> Self {
+        AnimeError::WebP(err)
+    }
+}
+
 impl From<AnimeError> for zbus::fdo::Error {
     #[inline]
     fn from(err: AnimeError) -> Self {
@@ -0,0 +1,575 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AnimeError, Result};
+use crate::model::LedGrid;
+use crate::playlist::Rng;
+use crate::{AnimTime, AnimeDataBuffer, AnimeFrame, AnimeFrames, AnimeType};
+
+/// How long each generated frame is shown for
+const FRAME_TIME: Duration = Duration::from_millis(33);
+/// How many frames are one play of a generator for `AnimTime::Count`, which
+/// is about 10 seconds
+const CYCLE_FRAMES: usize = 300;
+/// How many Game of Life generations there are each second at a speed of
+/// `1.0`
+const LIFE_GENERATIONS: f32 = 8.0;
+/// How many past Game of Life generations are checked for a repeat
+const LIFE_HISTORY: usize = 32;
+/// How far in to the display a star is drawn at its furthest, as a fraction
+/// of the distance from the centre to the edge
+const STAR_FOCAL: f32 = 0.25;
+
+/// A procedural source of frames
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Generator {
+    /// Drops falling down each column, leaving a fading trail
+    MatrixRain,
+    /// Conway's Game of Life on the LEDs, started again from a new random
+    /// pattern when it dies out or repeats
+    GameOfLife,
+    /// Stars flying out from the centre of the display
+    Starfield,
+    /// Waves of brightness moving over each other
+    Plasma,
+}
+
+impl FromStr for Generator {
+    type Err = AnimeError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let name: String = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect();
+        match name.to_lowercase().as_str() {
+            "matrixrain" | "matrix" | "rain" => Ok(Self::MatrixRain),
+            "gameoflife" | "life" => Ok(Self::GameOfLife),
+            "starfield" | "stars" => Ok(Self::Starfield),
+            "plasma" => Ok(Self::Plasma),
+            _ => Err(AnimeError::ParseError(s.to_owned())),
+        }
+    }
+}
+
+/// How a `Generator` looks. Any option left out of `anime.ron` is the
+/// default.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GeneratorOptions {
+    /// How fast it moves, where `1.0` is the normal speed
+    pub speed: f32,
+    /// From `0.0` to `1.0`, how many drops, live cells or stars there are, or
+    /// how close together the plasma waves are
+    pub density: f32,
+    /// From `0.0` to `1.0`
+    pub brightness: f32,
+    /// The same seed always makes the same frames
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            density: 0.5,
+            brightness: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+/// An animation made by a `Generator` while it plays. It starts again from
+/// the seed after it finishes, and `AnimTime::Count` counts 10 seconds as one
+/// play.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnimeGenerator {
+    generator: Generator,
+    options: GeneratorOptions,
+    time: AnimTime,
+    anime_type: AnimeType,
+    #[serde(skip)]
+    state: Mutex<Option<GeneratorState>>,
+}
+
+impl Clone for AnimeGenerator {
+    fn clone(&self) -> Self {
+        Self {
+            generator: self.generator,
+            options: self.options,
+            time: self.time,
+            anime_type: self.anime_type,
+            state: Mutex::default(),
+        }
+    }
+}
+
+impl AnimeGenerator {
+    pub fn new(
+        generator: Generator,
+        options: GeneratorOptions,
+        time: AnimTime,
+        anime_type: AnimeType,
+    ) -> Result<Self> {
+        if !(0.0..=1.0).contains(&options.brightness) {
+            return Err(AnimeError::InvalidBrightness(options.brightness));
+        }
+        if !(options.speed > 0.0 && options.speed.is_finite()) {
+            return Err(AnimeError::InvalidSpeed(options.speed));
+        }
+        Ok(Self {
+            generator,
+            options,
+            time,
+            anime_type,
+            state: Mutex::default(),
+        })
+    }
+
+    #[inline]
+    pub fn generator(&self) -> Generator {
+        self.generator
+    }
+
+    #[inline]
+    pub fn options(&self) -> GeneratorOptions {
+        self.options
+    }
+}
+
+impl AnimeFrames for AnimeGenerator {
+    fn duration(&self) -> AnimTime {
+        self.time
+    }
+
+    fn frame_count(&self) -> usize {
+        CYCLE_FRAMES
+    }
+
+    fn total_frame_time(&self) -> Duration {
+        FRAME_TIME * CYCLE_FRAMES as u32
+    }
+
+    fn frame(&self, index: usize) -> Result<AnimeFrame> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = state.get_or_insert_with(|| {
+            GeneratorState::new(self.generator, &self.options, self.anime_type)
+        });
+        // The frames only go forwards, so asking for the same index again gets
+        // the same frame and any other index gets the next one
+        match &state.last {
+            Some((last, frame)) if *last == index => return Ok(frame.clone()),
+            Some(_) => state.step(self.options.speed),
+            None => {}
+        }
+        let frame = state.frame(self.anime_type, self.options.brightness);
+        state.last = Some((index, frame.clone()));
+        Ok(frame)
+    }
+
+    fn finished(&self) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+#[derive(Debug)]
+struct GeneratorState {
+    grid: LedGrid,
+    rng: Rng,
+    /// The brightness of each byte of the data, from `0.0` to `1.0`
+    glow: Vec<f32>,
+    scene: Scene,
+    /// The index and frame last made
+    last: Option<(usize, AnimeFrame)>,
+}
+
+#[derive(Debug)]
+enum Scene {
+    Rain(Vec<Drop>),
+    Life(Life),
+    Stars(Vec<Star>),
+    Plasma {
+        phases: [f32; 4],
+        frequency: f32,
+        time: f32,
+    },
+}
+
+impl GeneratorState {
+    fn new(generator: Generator, options: &GeneratorOptions, anime_type: AnimeType) -> Self {
+        let grid = LedGrid::new(anime_type);
+        let rng = Rng::seeded(options.seed);
+        let density = options.density.clamp(0.0, 1.0);
+        let scene = match generator {
+            Generator::MatrixRain => {
+                let (rows, columns) = grid.size();
+                let count = ((columns as f32 * density).round() as usize).max(1);
+                Scene::Rain(
+                    (0..count)
+                        .map(|_| Drop::new(&rng, rows, columns, true))
+                        .collect(),
+                )
+            }
+            Generator::GameOfLife => Scene::Life(Life::new(&grid, &rng, density)),
+            Generator::Starfield => {
+                let count = ((grid.leds.len() as f32 * density * 0.1).round() as usize).max(1);
+                Scene::Stars((0..count).map(|_| Star::new(&rng, true)).collect())
+            }
+            Generator::Plasma => Scene::Plasma {
+                phases: [(); 4].map(|_| rng.unit() * TAU),
+                frequency: 0.2 + density * 1.6,
+                time: 0.0,
+            },
+        };
+        let mut state = Self {
+            grid,
+            rng,
+            glow: vec![0.0; anime_type.data_length()],
+            scene,
+            last: None,
+        };
+        state.draw(0.0);
+        state
+    }
+
+    /// Move on by one frame
+    fn step(&mut self, speed: f32) {
+        let dt = FRAME_TIME.as_secs_f32() * speed;
+        match &mut self.scene {
+            Scene::Rain(drops) => {
+                let (rows, columns) = self.grid.size();
+                for drop in drops.iter_mut() {
+                    drop.row += drop.rate * dt;
+                    if drop.row >= rows as f32 {
+                        *drop = Drop::new(&self.rng, rows, columns, false);
+                    }
+                }
+            }
+            Scene::Life(life) => {
+                life.wait -= dt;
+                while life.wait <= 0.0 {
+                    life.wait += 1.0 / LIFE_GENERATIONS;
+                    life.evolve(&self.grid, &self.rng);
+                }
+            }
+            Scene::Stars(stars) => {
+                for star in stars.iter_mut() {
+                    star.z -= 0.5 * dt;
+                    if star.z <= 0.05 || star.position(&self.grid).is_none() {
+                        *star = Star::new(&self.rng, false);
+                    }
+                }
+            }
+            Scene::Plasma { time, .. } => *time += dt,
+        }
+        self.draw(speed);
+    }
+
+    /// Fade the last frame by how far it moved and draw the scene over it
+    fn draw(&mut self, speed: f32) {
+        let fade = |glow: &mut [f32], per_frame: f32| {
+            let fade = per_frame.powf(speed);
+            glow.iter_mut().for_each(|g| *g *= fade);
+        };
+        let grid = &self.grid;
+        match &mut self.scene {
+            Scene::Rain(drops) => {
+                fade(&mut self.glow, 0.85);
+                for drop in drops.iter_mut() {
+                    // Light every row passed since the last frame, so that a
+                    // fast drop leaves an unbroken trail
+                    let to = drop.row.floor() as i64;
+                    for row in (drop.drawn + 1).max(0)..=to {
+                        if let Some(i) = grid.at(row as usize, drop.column) {
+                            self.glow[i] = 1.0;
+                        }
+                    }
+                    drop.drawn = drop.drawn.max(to);
+                }
+            }
+            Scene::Life(life) => {
+                fade(&mut self.glow, 0.7);
+                for led in &grid.leds {
+                    if life.cells[led.row * life.columns + led.column] {
+                        self.glow[led.index] = 1.0;
+                    }
+                }
+            }
+            Scene::Stars(stars) => {
+                fade(&mut self.glow, 0.6);
+                for star in stars.iter() {
+                    if let Some(i) = star.position(grid).and_then(|(x, y)| grid.led_at(x, y)) {
+                        self.glow[i] = self.glow[i].max(1.0 - star.z);
+                    }
+                }
+            }
+            Scene::Plasma {
+                phases,
+                frequency,
+                time,
+            } => {
+                let ([p0, p1, p2, p3], f, t) = (*phases, *frequency, *time);
+                let (cx, cy) = grid.centre();
+                for led in &grid.leds {
+                    let (x, y) = (led.x - cx, led.y - cy);
+                    let v = (x * f + t + p0).sin()
+                        + (y * f * 1.7 - t * 1.3 + p1).sin()
+                        + ((x + y) * f * 0.7 + t * 0.7 + p2).sin()
+                        + ((x * x + y * y).sqrt() * f * 1.3 - t * 1.1 + p3).sin();
+                    let v = (v / 4.0 + 1.0) / 2.0;
+                    self.glow[led.index] = v * v;
+                }
+            }
+        }
+    }
+
+    fn frame(&self, anime_type: AnimeType, brightness: f32) -> AnimeFrame {
+        let mut data = AnimeDataBuffer::new(anime_type);
+        let bytes = data.data_mut();
+        for led in &self.grid.leds {
+            bytes[led.index] = (self.glow[led.index] * brightness * 255.0).round() as u8;
+        }
+        AnimeFrame::new(data, FRAME_TIME)
+    }
+}
+
+/// A falling drop of the Matrix rain
+#[derive(Debug)]
+struct Drop {
+    column: usize,
+    row: f32,
+    /// The last row lit
+    drawn: i64,
+    /// Rows per second
+    rate: f32,
+}
+
+impl Drop {
+    /// A new drop somewhere on the display, or above it to fall in
+    fn new(rng: &Rng, rows: usize, columns: usize, anywhere: bool) -> Self {
+        let row = if anywhere {
+            rng.unit() * rows as f32
+        } else {
+            -rng.unit() * rows as f32 / 2.0
+        };
+        Self {
+            column: (rng.next() % columns.max(1) as u64) as usize,
+            row,
+            drawn: row.floor() as i64 - 1,
+            rate: 10.0 + rng.unit() * 20.0,
+        }
+    }
+}
+
+/// The cells of the Game of Life on the rows and columns of the LEDs. Only a
+/// cell with an LED can be alive, and the edges wrap around.
+#[derive(Debug)]
+struct Life {
+    cells: Vec<bool>,
+    rows: usize,
+    columns: usize,
+    density: f32,
+    /// Hashes of the latest generations, to see if it is repeating
+    history: VecDeque<u64>,
+    /// Seconds until the next generation
+    wait: f32,
+}
+
+impl Life {
+    fn new(grid: &LedGrid, rng: &Rng, density: f32) -> Self {
+        let (rows, columns) = grid.size();
+        let mut life = Self {
+            cells: vec![false; rows * columns],
+            rows,
+            columns,
+            density,
+            history: VecDeque::with_capacity(LIFE_HISTORY),
+            wait: 1.0 / LIFE_GENERATIONS,
+        };
+        life.reseed(grid, rng);
+        life
+    }
+
+    /// A new random pattern, with up to half of the cells alive
+    fn reseed(&mut self, grid: &LedGrid, rng: &Rng) {
+        self.cells.fill(false);
+        for led in &grid.leds {
+            self.cells[led.row * self.columns + led.column] = rng.unit() < self.density * 0.5;
+        }
+        self.history.clear();
+    }
+
+    fn evolve(&mut self, grid: &LedGrid, rng: &Rng) {
+        let (rows, columns) = (self.rows, self.columns);
+        let mut next = vec![false; self.cells.len()];
+        for led in &grid.leds {
+            let mut neighbours = 0;
+            for dr in [rows - 1, 0, 1] {
+                for dc in [columns - 1, 0, 1] {
+                    if (dr, dc) == (0, 0) {
+                        continue;
+                    }
+                    let (r, c) = ((led.row + dr) % rows, (led.column + dc) % columns);
+                    neighbours += self.cells[r * columns + c] as u8;
+                }
+            }
+            let i = led.row * columns + led.column;
+            next[i] = matches!((self.cells[i], neighbours), (true, 2) | (_, 3));
+        }
+        self.cells = next;
+
+        let mut hasher = DefaultHasher::new();
+        self.cells.hash(&mut hasher);
+        let hash = hasher.finish();
+        if !self.cells.contains(&true) || self.history.contains(&hash) {
+            self.reseed(grid, rng);
+            return;
+        }
+        if self.history.len() == LIFE_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(hash);
+    }
+}
+
+/// A star of the starfield, at `x` and `y` from `-1.0` to `1.0` across the
+/// view and `z` from `1.0` far away to `0.0` at the display
+#[derive(Debug)]
+struct Star {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl Star {
+    /// A new star at any depth, or far away
+    fn new(rng: &Rng, anywhere: bool) -> Self {
+        Self {
+            x: rng.unit() * 2.0 - 1.0,
+            y: rng.unit() * 2.0 - 1.0,
+            z: if anywhere { rng.unit().max(0.1) } else { 1.0 },
+        }
+    }
+
+    /// Where the star is on the display in cm, if it is on it
+    fn position(&self, grid: &LedGrid) -> Option<(f32, f32)> {
+        let (cx, cy) = grid.centre();
+        let (width, height) = grid.span();
+        let x = cx + self.x * STAR_FOCAL / self.z * width / 2.0;
+        let y = cy + self.y * STAR_FOCAL / self.z * height / 2.0;
+        grid.contains(x, y).then_some((x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActionData, ActionLoader};
+
+    const ALL: [Generator; 4] = [
+        Generator::MatrixRain,
+        Generator::GameOfLife,
+        Generator::Starfield,
+        Generator::Plasma,
+    ];
+
+    fn frames(generator: &AnimeGenerator, count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| generator.frame(i).unwrap().frame().data().to_vec())
+            .collect()
+    }
+
+    fn with(generator: Generator, options: GeneratorOptions) -> AnimeGenerator {
+        AnimeGenerator::new(generator, options, AnimTime::Infinite, AnimeType::GA402).unwrap()
+    }
+
+    #[test]
+    fn seeded_frames() {
+        for generator in ALL {
+            let options = GeneratorOptions {
+                seed: 7,
+                ..Default::default()
+            };
+            let a = frames(&with(generator, options), 40);
+            assert_eq!(a, frames(&with(generator, options), 40), "{generator:?}");
+            let other = GeneratorOptions { seed: 8, ..options };
+            assert_ne!(a, frames(&with(generator, other), 40), "{generator:?}");
+            // It lights some LEDs and moves
+            assert!(a.iter().all(|f| f.iter().any(|&b| b != 0)), "{generator:?}");
+            assert_ne!(a[10], a[11], "{generator:?}");
+        }
+    }
+
+    #[test]
+    fn brightness_and_restart() {
+        for generator in ALL {
+            let dim = with(
+                generator,
+                GeneratorOptions {
+                    brightness: 0.5,
+                    ..Default::default()
+                },
+            );
+            let first = frames(&dim, 20);
+            assert!(first.iter().flatten().all(|&b| b <= 128), "{generator:?}");
+            // The same index gives the same frame, and after finishing it
+            // starts again from the seed
+            assert_eq!(dim.frame(19).unwrap().frame().data(), &first[19][..]);
+            dim.finished();
+            assert_eq!(frames(&dim, 20), first, "{generator:?}");
+        }
+
+        let options = GeneratorOptions {
+            speed: 0.0,
+            ..Default::default()
+        };
+        assert!(AnimeGenerator::new(
+            Generator::Plasma,
+            options,
+            AnimTime::Infinite,
+            AnimeType::GA402
+        )
+        .is_err());
+        let options = GeneratorOptions {
+            brightness: 1.5,
+            ..Default::default()
+        };
+        assert!(AnimeGenerator::new(
+            Generator::Plasma,
+            options,
+            AnimTime::Infinite,
+            AnimeType::GA402
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn from_ron_and_names() {
+        let action: ActionLoader = ron::from_str(
+            "Generator(generator: GameOfLife, options: (density: 0.3, seed: 4), time: Count(2))",
+        )
+        .unwrap();
+        let Ok(ActionData::Generator(generator)) =
+            ActionData::from_anime_action(AnimeType::GU604, &action)
+        else {
+            panic!("not a generator");
+        };
+        assert_eq!(generator.generator(), Generator::GameOfLife);
+        assert_eq!(generator.options().density, 0.3);
+        assert_eq!(generator.options().speed, 1.0);
+
+        assert_eq!(
+            "matrix-rain".parse::<Generator>().unwrap(),
+            Generator::MatrixRain
+        );
+        assert_eq!("Stars".parse::<Generator>().unwrap(), Generator::Starfield);
+        assert!("fire".parse::<Generator>().is_err());
+    }
+}
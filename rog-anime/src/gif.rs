@@ -1,14 +1,19 @@
 use std::convert::TryFrom;
 use std::fs::File;
-use std::path::Path;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use glam::Vec2;
 use log::error;
 use serde::{Deserialize, Serialize};
 
+use crate::colour::Luminance;
 use crate::error::{AnimeError, Result};
-use crate::{AnimeDataBuffer, AnimeDiagonal, AnimeImage, AnimeType, Pixel};
+use crate::{
+    AnimeDataBuffer, AnimeDiagonal, AnimeImage, AnimeText, AnimeType, ColourOptions, Pixel,
+    ScrollDirection,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AnimeFrame {
@@ -20,6 +25,11 @@ pub struct AnimeFrame {
 }
 
 impl AnimeFrame {
+    #[inline]
+    pub(crate) fn new(data: AnimeDataBuffer, delay: Duration) -> Self {
+        Self { data, delay }
+    }
+
     /// Get the inner data buffer of the gif frame
     #[inline]
     pub fn frame(&self) -> &AnimeDataBuffer {
@@ -102,50 +112,8 @@ impl AnimeGif {
         brightness: f32,
         anime_type: AnimeType,
     ) -> Result<Self> {
-        let mut matrix = AnimeDiagonal::new(anime_type, None);
-
-        let mut decoder = gif::DecodeOptions::new();
-        // Configure the decoder such that it will expand the image to RGBA.
-        decoder.set_color_output(gif::ColorOutput::RGBA);
-        // Read the file header
-        let file = File::open(file_name).map_err(|e| {
-            error!("Could not open {file_name:?}: {e:?}");
-            e
-        })?;
-        let mut decoder = decoder.read_info(file)?;
-
-        let mut frames = Vec::default();
-        while let Some(frame) = decoder.read_next_frame()? {
-            let wait = frame.delay * 10;
-            // if matches!(frame.dispose, gif::DisposalMethod::Background) {
-            //     frames = Vec::new();
-            // }
-            for (y, row) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
-                for (x, px) in row.chunks(4).enumerate() {
-                    if px[3] != 255 {
-                        // should be t but not in some gifs? What, ASUS, what?
-                        continue;
-                    }
-                    let tmp = matrix.get_mut();
-                    let y = y + frame.top as usize;
-                    if y >= tmp.len() {
-                        return Err(AnimeError::PixelGifHeight(tmp.len()));
-                    }
-                    let x = x + frame.left as usize;
-                    if x >= tmp[y].len() {
-                        return Err(AnimeError::PixelGifWidth(tmp[y].len()));
-                    }
-
-                    matrix.get_mut()[y][x] = (px[0] as f32 * brightness) as u8;
-                }
-            }
-
-            frames.push(AnimeFrame {
-                data: matrix.into_data_buffer(anime_type)?,
-                delay: Duration::from_millis(wait as u64),
-            });
-        }
-        Ok(Self(frames, duration))
+        let frames = diagonal_gif_frames(file_name, brightness, anime_type)?;
+        Ok(Self(frames.collect::<Result<_>>()?, duration))
     }
 
     /// Create an animation using the 74x36 ASUS gif format from a png
@@ -179,97 +147,127 @@ impl AnimeGif {
 
     /// Create an animation using a gif of any size. This method must precompute
     /// the result.
+    ///
+    /// Each frame is blended over the frames before it, so the transparent
+    /// parts of a frame show what was there. What is still transparent is
+    /// blended over the background in `colour`.
     #[inline]
-    pub fn from_gif(
-        file_name: &Path,
-        scale: f32,
-        angle: f32,
-        translation: Vec2,
+    pub fn from_gif(file_name: &Path, place: Placement, duration: AnimTime) -> Result<Self> {
+        Ok(Self(
+            gif_frames(file_name, place)?.collect::<Result<_>>()?,
+            duration,
+        ))
+    }
+
+    /// Make a static gif out of a png. If no duration is specified then the
+    /// default will be 1 second long. If `AnimTime::Cycles` is specified for
+    /// `duration` then this can be considered how many seconds the image will
+    /// show for.
+    #[inline]
+    pub fn from_png(file_name: &Path, place: Placement, duration: AnimTime) -> Result<Self> {
+        let image = AnimeImage::from_png(
+            file_name,
+            place.scale,
+            place.angle,
+            place.translation,
+            place.brightness,
+            place.colour,
+            place.anime_type,
+        )?;
+        Ok(Self::still(<AnimeDataBuffer>::try_from(&image)?, duration))
+    }
+
+    /// Create an animation from an apng, or a static gif if the png is not
+    /// animated. Each frame is shown for the delay stored in the file.
+    #[inline]
+    pub fn from_apng(file_name: &Path, place: Placement, duration: AnimTime) -> Result<Self> {
+        Self::animated_or_still(apng_frames(file_name, place)?, duration)
+    }
+
+    /// Create an animation from a webp, or a static gif if the webp is not
+    /// animated. Each frame is shown for the delay stored in the file.
+    #[inline]
+    pub fn from_webp(file_name: &Path, place: Placement, duration: AnimTime) -> Result<Self> {
+        Self::animated_or_still(webp_frames(file_name, place)?, duration)
+    }
+
+    /// Create an animation from a directory of png files, such as the frames
+    /// of a video exported with `ffmpeg -i video.mp4 frame%04d.png`. The
+    /// frames are played in the order of the number at the end of each file
+    /// name, at `fps` frames per second.
+    #[inline]
+    pub fn from_png_frames(
+        directory: &Path,
+        fps: f32,
+        place: Placement,
+        duration: AnimTime,
+    ) -> Result<Self> {
+        Self::animated_or_still(png_frames(directory, fps, place)?, duration)
+    }
+
+    /// Create an animation from a gif, apng or webp, chosen by the file
+    /// extension. Anything that is not a png or webp is read as a gif.
+    #[inline]
+    pub fn from_file(file_name: &Path, place: Placement, duration: AnimTime) -> Result<Self> {
+        let load = match ImageFormat::of(file_name) {
+            ImageFormat::Gif => Self::from_gif,
+            ImageFormat::Apng => Self::from_apng,
+            ImageFormat::WebP => Self::from_webp,
+        };
+        load(file_name, place, duration)
+    }
+
+    /// Create an animation of `text` moving across the display in `direction`
+    /// at `speed` LED widths per second. Each frame is rendered directly to
+    /// the LED positions of `anime_type`.
+    ///
+    /// If the text is static then it is treated like `from_png()`, and the
+    /// single frame is repeated for the length of `duration`.
+    #[inline]
+    pub fn from_text(
+        text: &str,
+        font_size: f32,
+        direction: ScrollDirection,
+        speed: f32,
         duration: AnimTime,
         brightness: f32,
         anime_type: AnimeType,
     ) -> Result<Self> {
-        let mut frames = Vec::new();
-        let mut decoder = gif::DecodeOptions::new();
-        // Configure the decoder such that it will expand the image to RGBA.
-        decoder.set_color_output(gif::ColorOutput::RGBA);
-        // Read the file header
-        let file = File::open(file_name).map_err(|e| {
-            error!("Could not open {file_name:?}: {e:?}");
-            e
-        })?;
-        let mut decoder = decoder.read_info(file)?;
-
-        let height = decoder.height();
-        let width = decoder.width();
-        let pixels: Vec<Pixel> =
-            vec![Pixel::default(); (decoder.width() as u32 * decoder.height() as u32) as usize];
-        let mut image = AnimeImage::new(
-            Vec2::new(scale, scale),
-            angle,
-            translation,
-            brightness,
-            pixels,
-            decoder.width() as u32,
-            anime_type,
-        )?;
-
-        while let Some(frame) = decoder.read_next_frame()? {
-            let wait = frame.delay * 10;
-            if matches!(frame.dispose, gif::DisposalMethod::Background) {
-                let pixels: Vec<Pixel> =
-                    vec![Pixel::default(); (width as u32 * height as u32) as usize];
-                image = AnimeImage::new(
-                    Vec2::new(scale, scale),
-                    angle,
-                    translation,
-                    brightness,
-                    pixels,
-                    width as u32,
-                    anime_type,
-                )?;
-            }
-            for (y, row) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
-                for (x, px) in row.chunks(4).enumerate() {
-                    if px[3] != 255 {
-                        // should be t but not in some gifs? What, ASUS, what?
-                        continue;
-                    }
-                    let pos =
-                        (x + frame.left as usize) + ((y + frame.top as usize) * width as usize);
-                    image.get_mut()[pos] = Pixel {
-                        color: ((px[0] as u32 + px[1] as u32 + px[2] as u32) / 3),
-                        alpha: 1.0,
-                    };
+        let text = AnimeText::new(text, font_size, brightness, anime_type)?;
+        // Make frame delay 30ms, the same as static images
+        let delay = Duration::from_millis(30);
+        let positions = text.scroll_positions(direction, speed, delay);
+
+        if positions.len() == 1 {
+            let mut total = Duration::from_millis(1000);
+            if let AnimTime::Fade(fade) = duration {
+                total = fade.total_fade_time();
+                if let Some(middle) = fade.show_for {
+                    total += middle;
                 }
             }
-            image.update();
+            let frame_count = total.as_millis() / 30;
 
+            let single = AnimeFrame {
+                data: text.render(positions[0])?,
+                delay,
+            };
+            return Ok(Self(vec![single; frame_count as usize], duration));
+        }
+
+        let mut frames = Vec::with_capacity(positions.len());
+        for pos in positions {
             frames.push(AnimeFrame {
-                data: <AnimeDataBuffer>::try_from(&image)?,
-                delay: Duration::from_millis(wait as u64),
+                data: text.render(pos)?,
+                delay,
             });
         }
         Ok(Self(frames, duration))
     }
 
-    /// Make a static gif out of a greyscale png. If no duration is specified
-    /// then the default will be 1 second long. If `AnimTime::Cycles` is
-    /// specified for `duration` then this can be considered how many
-    /// seconds the image will show for.
-    #[inline]
-    pub fn from_png(
-        file_name: &Path,
-        scale: f32,
-        angle: f32,
-        translation: Vec2,
-        duration: AnimTime,
-        brightness: f32,
-        anime_type: AnimeType,
-    ) -> Result<Self> {
-        let image =
-            AnimeImage::from_png(file_name, scale, angle, translation, brightness, anime_type)?;
-
+    /// Repeat a single frame for the length of `duration`. If no duration is
+    /// specified then the default will be 1 second long.
+    fn still(data: AnimeDataBuffer, duration: AnimTime) -> Self {
         let mut total = Duration::from_millis(1000);
         if let AnimTime::Fade(fade) = duration {
             total = fade.total_fade_time();
@@ -281,12 +279,21 @@ impl AnimeGif {
         let frame_count = total.as_millis() / 30;
 
         let single = AnimeFrame {
-            data: <AnimeDataBuffer>::try_from(&image)?,
+            data,
             delay: Duration::from_millis(30),
         };
-        let frames = vec![single; frame_count as usize];
+        Self(vec![single; frame_count as usize], duration)
+    }
 
-        Ok(Self(frames, duration))
+    /// Collect the frames of an animation, making a single frame in to a
+    /// static gif
+    fn animated_or_still(frames: FrameIter, duration: AnimTime) -> Result<Self> {
+        let mut frames = frames.collect::<Result<Vec<_>>>()?;
+        match frames.len() {
+            0 => Err(AnimeError::NoFrames),
+            1 => Ok(Self::still(frames.remove(0).data, duration)),
+            _ => Ok(Self(frames, duration)),
+        }
     }
 
     /// Get a slice of the frames this gif has
@@ -313,3 +320,475 @@ impl AnimeGif {
         Duration::from_millis(time as u64)
     }
 }
+
+/// Frames decoded one at a time. The constructors of `AnimeGif` collect all of
+/// them up front, an `AnimeStream` takes each as it is needed.
+pub(crate) type FrameIter = Box<dyn Iterator<Item = Result<AnimeFrame>> + Send>;
+
+/// Where and how an image is shown on the display, shared by the loaders of
+/// `AnimeGif` and `AnimeStream`
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    /// Scale of the image, 1.0 is the size of the source
+    pub scale: f32,
+    /// Rotation in radians
+    pub angle: f32,
+    /// Offset of the image from the centre of the display
+    pub translation: Vec2,
+    /// Multiplier of each LED, from 0.0 to 1.0
+    pub brightness: f32,
+    /// How the colours of the image are turned into LED levels
+    pub colour: ColourOptions,
+    pub anime_type: AnimeType,
+}
+
+impl Placement {
+    /// An empty image to put each frame through, so that they are all
+    /// scaled, rotated and moved alike
+    fn image(&self) -> Result<AnimeImage> {
+        let mut image = AnimeImage::new(
+            Vec2::new(self.scale, self.scale),
+            self.angle,
+            self.translation,
+            self.brightness,
+            Vec::new(),
+            0,
+            self.anime_type,
+        )?;
+        image.colour = self.colour;
+        Ok(image)
+    }
+}
+
+/// The animated formats that `AnimeGif::from_file()` reads, chosen by the
+/// file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImageFormat {
+    Gif,
+    Apng,
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn of(file_name: &Path) -> Self {
+        let ext = file_name
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "png" | "apng" => Self::Apng,
+            "webp" => Self::WebP,
+            _ => Self::Gif,
+        }
+    }
+
+    pub fn frames(self, file_name: &Path, place: Placement) -> Result<FrameIter> {
+        match self {
+            Self::Gif => gif_frames(file_name, place),
+            Self::Apng => apng_frames(file_name, place),
+            Self::WebP => webp_frames(file_name, place),
+        }
+    }
+
+    pub fn timing(self, file_name: &Path) -> Result<Timing> {
+        match self {
+            Self::Gif => gif_timing(file_name),
+            Self::Apng => apng_timing(file_name),
+            Self::WebP => webp_timing(file_name),
+        }
+    }
+}
+
+/// The number of frames in an animation and the time to play them all once,
+/// found without rasterising any of them
+pub(crate) type Timing = (usize, Duration);
+
+fn open(file_name: &Path) -> Result<File> {
+    File::open(file_name).map_err(|e| {
+        error!("Could not open {file_name:?}: {e:?}");
+        e.into()
+    })
+}
+
+fn read(file_name: &Path) -> Result<Vec<u8>> {
+    std::fs::read(file_name).map_err(|e| {
+        error!("Could not open {file_name:?}: {e:?}");
+        e.into()
+    })
+}
+
+/// The frames of a gif in the 74x36 ASUS format, see
+/// `AnimeGif::from_diagonal_gif()`
+pub(crate) fn diagonal_gif_frames(
+    file_name: &Path,
+    brightness: f32,
+    anime_type: AnimeType,
+) -> Result<FrameIter> {
+    let mut matrix = AnimeDiagonal::new(anime_type, None);
+
+    let mut decoder = gif::DecodeOptions::new();
+    // Configure the decoder such that it will expand the image to RGBA.
+    decoder.set_color_output(gif::ColorOutput::RGBA);
+    // Read the file header
+    let mut decoder = decoder.read_info(open(file_name)?)?;
+
+    let mut next = move || -> Result<Option<AnimeFrame>> {
+        let Some(frame) = decoder.read_next_frame()? else {
+            return Ok(None);
+        };
+        let wait = frame.delay * 10;
+        // if matches!(frame.dispose, gif::DisposalMethod::Background) {
+        //     frames = Vec::new();
+        // }
+        for (y, row) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
+            for (x, px) in row.chunks(4).enumerate() {
+                if px[3] != 255 {
+                    // should be t but not in some gifs? What, ASUS, what?
+                    continue;
+                }
+                let tmp = matrix.get_mut();
+                let y = y + frame.top as usize;
+                if y >= tmp.len() {
+                    return Err(AnimeError::PixelGifHeight(tmp.len()));
+                }
+                let x = x + frame.left as usize;
+                if x >= tmp[y].len() {
+                    return Err(AnimeError::PixelGifWidth(tmp[y].len()));
+                }
+
+                matrix.get_mut()[y][x] = (px[0] as f32 * brightness) as u8;
+            }
+        }
+
+        Ok(Some(AnimeFrame {
+            data: matrix.into_data_buffer(anime_type)?,
+            delay: Duration::from_millis(wait as u64),
+        }))
+    };
+    Ok(Box::new(std::iter::from_fn(move || next().transpose())))
+}
+
+/// The frames of a gif of any size, see `AnimeGif::from_gif()`
+pub(crate) fn gif_frames(file_name: &Path, place: Placement) -> Result<FrameIter> {
+    let mut decoder = gif::DecodeOptions::new();
+    // Configure the decoder such that it will expand the image to RGBA.
+    decoder.set_color_output(gif::ColorOutput::RGBA);
+    // Read the file header
+    let mut decoder = decoder.read_info(open(file_name)?)?;
+
+    let width = decoder.width() as usize;
+    let blank = vec![Pixel::default(); width * decoder.height() as usize];
+    let mut image = place.image()?;
+    image.set_pixels(blank.clone(), width as u32);
+    let lum = Luminance::new();
+
+    let mut next = move || -> Result<Option<AnimeFrame>> {
+        let Some(frame) = decoder.read_next_frame()? else {
+            return Ok(None);
+        };
+        let wait = frame.delay * 10;
+        if matches!(frame.dispose, gif::DisposalMethod::Background) {
+            image.set_pixels(blank.clone(), width as u32);
+        }
+        for (y, row) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
+            for (x, px) in row.chunks(4).enumerate() {
+                let pos = (x + frame.left as usize) + ((y + frame.top as usize) * width);
+                if let Some(below) = image.get_mut().get_mut(pos) {
+                    *below = lum.pixel(px[0], px[1], px[2], px[3]).over(*below);
+                }
+            }
+        }
+        image.update();
+
+        Ok(Some(AnimeFrame {
+            data: <AnimeDataBuffer>::try_from(&image)?,
+            delay: Duration::from_millis(wait as u64),
+        }))
+    };
+    Ok(Box::new(std::iter::from_fn(move || next().transpose())))
+}
+
+/// Put each decoded image through the same `AnimeImage`, so that they are all
+/// scaled, rotated and moved alike
+fn raster_frames(
+    pixels: impl Iterator<Item = Result<(Vec<Pixel>, u32, Duration)>> + Send + 'static,
+    place: Placement,
+) -> Result<FrameIter> {
+    let mut image = place.image()?;
+    Ok(Box::new(pixels.map(move |frame| {
+        let (pixels, width, delay) = frame?;
+        image.set_pixels(pixels, width);
+        image.update();
+        Ok(AnimeFrame {
+            data: <AnimeDataBuffer>::try_from(&image)?,
+            delay,
+        })
+    })))
+}
+
+/// The frames of an apng, each shown for the delay stored in the file
+pub(crate) fn apng_frames(file_name: &Path, place: Placement) -> Result<FrameIter> {
+    let data = std::io::Cursor::new(read(file_name)?);
+    let decoder = png_pong::Decoder::new(data)?.into_steps();
+    let lum = Luminance::new();
+    let pixels = decoder.map(move |step| {
+        let step = step?;
+        let (pixels, width) = AnimeImage::pixels_from_raster(&step.raster, &lum)?;
+        Ok((pixels, width, Duration::from_millis(step.delay as u64)))
+    });
+    raster_frames(pixels, place)
+}
+
+/// The frames of a webp, each shown for the delay stored in the file
+pub(crate) fn webp_frames(file_name: &Path, place: Placement) -> Result<FrameIter> {
+    let mut decoder = image_webp::WebPDecoder::new(BufReader::new(open(file_name)?))?;
+    let animated = decoder.is_animated();
+    let count = if animated {
+        // Leave the canvas transparent so that the background in `colour`
+        // is used instead of the one in the file
+        decoder.set_background_color([0; 4])?;
+        decoder.num_frames()
+    } else {
+        1
+    };
+    let width = decoder.dimensions().0;
+    let channels = if decoder.has_alpha() { 4 } else { 3 };
+    let mut buf = vec![0; decoder.output_buffer_size().ok_or(AnimeError::Format)?];
+
+    let lum = Luminance::new();
+    let pixels = (0..count).map(move |_| {
+        let delay = if animated {
+            decoder.read_frame(&mut buf)?
+        } else {
+            decoder.read_image(&mut buf)?;
+            0
+        };
+        let pixels = buf
+            .chunks(channels)
+            .map(|px| lum.pixel(px[0], px[1], px[2], px.get(3).copied().unwrap_or(255)))
+            .collect();
+        Ok((pixels, width, Duration::from_millis(delay as u64)))
+    });
+    raster_frames(pixels, place)
+}
+
+/// The png files in `directory`, in the order of the number at the end of
+/// each file name
+fn frame_files(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory).map_err(|e| {
+        error!("Could not open {directory:?}: {e:?}");
+        e
+    })? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        {
+            files.push(path);
+        }
+    }
+    files.sort_by_cached_key(|path| (frame_number(path), path.clone()));
+    Ok(files)
+}
+
+fn frame_delay(fps: f32) -> Result<Duration> {
+    if !(fps > 0.0 && fps.is_finite()) {
+        return Err(AnimeError::InvalidFramerate(fps));
+    }
+    Ok(Duration::from_secs_f32(1.0 / fps))
+}
+
+/// The frames of a directory of png files, see `AnimeGif::from_png_frames()`
+pub(crate) fn png_frames(directory: &Path, fps: f32, place: Placement) -> Result<FrameIter> {
+    let delay = frame_delay(fps)?;
+    let lum = Luminance::new();
+    let pixels = frame_files(directory)?.into_iter().map(move |path| {
+        let data = std::io::Cursor::new(read(&path)?);
+        let decoder = png_pong::Decoder::new(data)?.into_steps();
+        let step = decoder.last().ok_or(AnimeError::NoFrames)??;
+        let (pixels, width) = AnimeImage::pixels_from_raster(&step.raster, &lum)?;
+        Ok((pixels, width, delay))
+    });
+    raster_frames(pixels, place)
+}
+
+/// Add up the delays of each frame the same way as
+/// `AnimeGif::total_frame_time()`
+fn timing(delays: impl IntoIterator<Item = Duration>) -> Timing {
+    let (mut count, mut total) = (0, 0);
+    for delay in delays {
+        count += 1;
+        total += delay.as_millis() as u64;
+    }
+    (count, Duration::from_millis(total))
+}
+
+pub(crate) fn gif_timing(file_name: &Path) -> Result<Timing> {
+    let mut decoder = gif::DecodeOptions::new().read_info(open(file_name)?)?;
+    let mut delays = Vec::new();
+    // Only the frame headers are read, the image data is skipped over
+    while let Some(frame) = decoder.next_frame_info()? {
+        delays.push(Duration::from_millis(frame.delay as u64 * 10));
+    }
+    Ok(timing(delays))
+}
+
+/// Only the chunk headers and the `fcTL` frame controls are read, a png with
+/// none is a single frame
+pub(crate) fn apng_timing(file_name: &Path) -> Result<Timing> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    let data = read(file_name)?;
+    let mut chunks = data.strip_prefix(SIGNATURE).ok_or(AnimeError::Format)?;
+    let mut delays = Vec::new();
+    while chunks.len() >= 12 {
+        let len = u32::from_be_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+        let kind = &chunks[4..8];
+        let body = chunks.get(8..8 + len).ok_or(AnimeError::Format)?;
+        if kind == b"fcTL" {
+            // Sequence number, width, height, x and y come before the delay
+            let delay = body.get(20..24).ok_or(AnimeError::Format)?;
+            let num = u16::from_be_bytes([delay[0], delay[1]]) as u64;
+            let den = match u16::from_be_bytes([delay[2], delay[3]]) {
+                0 => 100,
+                den => den as u64,
+            };
+            delays.push(Duration::from_millis(num * 1000 / den));
+        } else if kind == b"IEND" {
+            break;
+        }
+        // Skip the body and the CRC after it
+        chunks = chunks.get(8 + len + 4..).ok_or(AnimeError::Format)?;
+    }
+    if delays.is_empty() {
+        return Ok((1, Duration::ZERO));
+    }
+    Ok(timing(delays))
+}
+
+pub(crate) fn webp_timing(file_name: &Path) -> Result<Timing> {
+    let decoder = image_webp::WebPDecoder::new(BufReader::new(open(file_name)?))?;
+    if !decoder.is_animated() {
+        return Ok((1, Duration::ZERO));
+    }
+    Ok((
+        decoder.num_frames() as usize,
+        Duration::from_millis(decoder.loop_duration()),
+    ))
+}
+
+pub(crate) fn png_frames_timing(directory: &Path, fps: f32) -> Result<Timing> {
+    let delay = frame_delay(fps)?;
+    Ok(timing(vec![delay; frame_files(directory)?.len()]))
+}
+
+/// The number at the end of a frame's file name, so that `frame10.png` sorts
+/// after `frame9.png`
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn frame_order() {
+        let mut files: Vec<PathBuf> = ["frame10.png", "frame9.png", "frame0001.png", "cover.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        files.sort_by_cached_key(|path| (frame_number(path), path.clone()));
+        assert_eq!(
+            files,
+            ["cover.png", "frame0001.png", "frame9.png", "frame10.png"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    fn chunk(name: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut out = name.to_vec();
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    /// An animated webp with one lossless frame for each grey level
+    pub(crate) fn animated_webp(size: u32, greys: &[u8], delay: u32) -> Vec<u8> {
+        let u24 = |v: u32| v.to_le_bytes()[..3].to_vec();
+        let mut vp8x = vec![0x12, 0, 0, 0]; // alpha and animation
+        vp8x.extend(u24(size - 1));
+        vp8x.extend(u24(size - 1));
+        let mut body = b"WEBP".to_vec();
+        body.extend(chunk(b"VP8X", &vp8x));
+        body.extend(chunk(b"ANIM", &[0, 0, 0, 0, 0, 0]));
+        for grey in greys {
+            let data = vec![*grey; (size * size) as usize];
+            let mut still = Vec::new();
+            image_webp::WebPEncoder::new(&mut still)
+                .encode(&data, size, size, image_webp::ColorType::L8)
+                .unwrap();
+            // Skip the RIFF header of the still and keep its VP8L chunk
+            let mut anmf = [u24(0), u24(0), u24(size - 1), u24(size - 1), u24(delay)].concat();
+            anmf.push(0);
+            anmf.extend_from_slice(&still[12..]);
+            body.extend(chunk(b"ANMF", &anmf));
+        }
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        file.extend(body);
+        file
+    }
+
+    /// The source size in the middle of a GA402 display
+    pub(crate) fn placement() -> Placement {
+        Placement {
+            scale: 1.0,
+            angle: 0.0,
+            translation: Vec2::default(),
+            brightness: 1.0,
+            colour: ColourOptions::default(),
+            anime_type: AnimeType::GA402,
+        }
+    }
+
+    #[test]
+    fn webp_frames() {
+        let path = std::env::temp_dir().join(format!("rog-anime-{}.webp", std::process::id()));
+        std::fs::write(&path, animated_webp(40, &[255, 0, 128], 50)).unwrap();
+        let gif = AnimeGif::from_webp(&path, placement(), AnimTime::Infinite);
+        std::fs::remove_file(&path).unwrap();
+        let gif = gif.unwrap();
+
+        assert_eq!(gif.frame_count(), 3);
+        assert_eq!(gif.total_frame_time(), Duration::from_millis(150));
+        let lit = |frame: &AnimeFrame| frame.frame().data().iter().filter(|v| **v != 0).count();
+        assert!(lit(&gif.frames()[0]) > 0);
+        assert_eq!(lit(&gif.frames()[1]), 0);
+        assert!(gif.frames()[2].frame().data().contains(&128));
+    }
+
+    #[test]
+    fn apng_frames() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/data/apng-three-greys.png");
+        assert_eq!(apng_timing(&path).unwrap(), (3, Duration::from_millis(300)));
+
+        let gif = AnimeGif::from_apng(&path, placement(), AnimTime::Infinite).unwrap();
+        let delays: Vec<_> = gif.frames().iter().map(|f| f.delay()).collect();
+        assert_eq!(delays, [50, 100, 150].map(Duration::from_millis).to_vec());
+        let max = |frame: &AnimeFrame| frame.frame().data().iter().copied().max().unwrap();
+        assert_eq!(max(&gif.frames()[0]), 255);
+        assert_eq!(max(&gif.frames()[1]), 0);
+        assert_eq!(max(&gif.frames()[2]), 128);
+    }
+}
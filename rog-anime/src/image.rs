@@ -5,9 +5,10 @@ pub use glam::Vec2;
 use glam::{Mat3, Vec3};
 use log::error;
 
+use crate::colour::Luminance;
 use crate::data::AnimeDataBuffer;
 use crate::error::{AnimeError, Result};
-use crate::AnimeType;
+use crate::{AnimeType, ColourOptions};
 
 /// A single greyscale + alpha pixel in the image
 #[derive(Copy, Clone, Debug)]
@@ -26,6 +27,25 @@ impl Default for Pixel {
     }
 }
 
+impl Pixel {
+    /// Blend this pixel over `below`, such as a gif frame over the frames
+    /// before it
+    #[inline]
+    pub fn over(self, below: Pixel) -> Pixel {
+        let alpha = self.alpha + below.alpha * (1.0 - self.alpha);
+        if alpha <= 0.0 {
+            return Pixel::default();
+        }
+        let color = (self.color as f32 * self.alpha
+            + below.color as f32 * below.alpha * (1.0 - self.alpha))
+            / alpha;
+        Pixel {
+            color: color.round() as u32,
+            alpha,
+        }
+    }
+}
+
 /// A single LED position and brightness. The intention of this struct
 /// is to be used to sample an image and set the LED brightness.
 ///
@@ -67,6 +87,8 @@ pub struct AnimeImage {
     pub translation: Vec2,
     /// Brightness of final image, `0.0` = off, `1.0` = full
     pub bright: f32,
+    /// Background, gamma and dithering used by `update()`
+    pub colour: ColourOptions,
     /// Positions of all the LEDs
     led_pos: Vec<Option<Led>>,
     /// THe image data for sampling
@@ -99,6 +121,7 @@ impl AnimeImage {
             angle,
             translation,
             bright,
+            colour: ColourOptions::default(),
             led_pos: Self::generate_image_positioning(anime_type),
             img_pixels: pixels,
             width,
@@ -106,8 +129,6 @@ impl AnimeImage {
         })
     }
 
-    // TODO: Convert functions back to const after todo completed
-
     /// Scale ratio in CM
     ///
     /// This is worked out by measuring the physical width of the display from
@@ -117,12 +138,8 @@ impl AnimeImage {
     /// For GA401 this is `26.8 / (33 + 0.5) = 0.8`
     /// For GA402 this is `27.4 / (35 + 0.5) = 0.77`
     /// For GA402 this is `30.9 / (39 + 0.5) = 0.77`
-    fn scale_x(anime_type: AnimeType) -> f32 {
-        match anime_type {
-            AnimeType::GA401 => 0.8,
-            AnimeType::GU604 => 0.78,
-            _ => 0.77,
-        }
+    pub(crate) fn scale_x(anime_type: AnimeType) -> f32 {
+        anime_type.model().scale.0
     }
 
     /// Scale ratio in CM
@@ -134,12 +151,8 @@ impl AnimeImage {
     /// For GA401 this is `16.5 / (54.0 + 1.0) = 0.3`
     /// For GA402 this is `17.3 / (61.0)       = 0.283`
     /// For GU604 this is `17.7 / (62.0 + 1)   = 0.28`
-    fn scale_y(anime_type: AnimeType) -> f32 {
-        match anime_type {
-            AnimeType::GA401 => 0.3,
-            AnimeType::GU604 => 0.28,
-            _ => 0.283,
-        }
+    pub(crate) fn scale_y(anime_type: AnimeType) -> f32 {
+        anime_type.model().scale.1
     }
 
     /// Get the starting X position for the data we actually require when
@@ -163,31 +176,7 @@ impl AnimeImage {
     ///  first_x
     /// ```
     fn first_x(anime_type: AnimeType, y: u32) -> u32 {
-        match anime_type {
-            AnimeType::GA401 => {
-                if y < 5 {
-                    // first 5 rows for GA401 are always at X = 0
-                    return 0;
-                }
-                (y + 1) / 2 - 3
-            }
-            AnimeType::GU604 => {
-                // first 9 rows start at zero
-                if y <= 9 {
-                    return 0;
-                }
-                // and then their offset grows by one every two rows
-                (y - 9) / 2
-            }
-            _ => {
-                // first 11 rows start at zero
-                if y <= 11 {
-                    return 0;
-                }
-                // and then their offset grows by one every two rows
-                (y + 1) / 2 - 5
-            }
-        }
+        anime_type.model().rows[y as usize].0
     }
 
     /// Width in LED count
@@ -205,74 +194,41 @@ impl AnimeImage {
     ///       \      |
     ///        ------+
     /// ```
-    // TODO: make this return only width, and move calcs to pitch
     fn width(anime_type: AnimeType, y: u32) -> u32 {
-        match anime_type {
-            AnimeType::GA401 => {
-                if y < 5 {
-                    // First 5 rows for GA401 are always 33 physical LEDs long
-                    return 33;
-                }
-                36 - (y + 1) / 2
-            }
-            AnimeType::GU604 => {
-                if y <= 9 {
-                    return 38 + y % 2;
-                }
-                38 - Self::first_x(anime_type, y) + y % 2
-            }
-            _ => {
-                if y <= 11 {
-                    return 34;
-                }
-                39 - y / 2
-            }
-        }
+        anime_type.model().rows[y as usize].1
     }
 
     /// Physical display width by count of LED
     fn phys_width(anime_type: AnimeType) -> f32 {
-        match anime_type {
-            // 33.0 = Longest row LED count (physical) plus half-pixel offset
-            AnimeType::GA401 => (33.0 + 0.5) * Self::scale_x(anime_type),
-
-            AnimeType::GU604 => (38.0 + 0.5) * Self::scale_x(anime_type),
-            _ => (35.0 + 0.5) * Self::scale_x(anime_type),
-        }
+        anime_type.model().size.0 * Self::scale_x(anime_type)
     }
 
     /// Height in LED count of longest column (physical count)
     fn height(anime_type: AnimeType) -> u32 {
-        match anime_type {
-            AnimeType::GA401 => 55,
-            AnimeType::GU604 => 62,
-            _ => 61,
-        }
+        anime_type.model().rows.len() as u32
     }
 
     /// Physical display height
     fn phys_height(anime_type: AnimeType) -> f32 {
-        match anime_type {
-            // 54.0 = End column LED count (physical) plus one dead pixel
-            AnimeType::GA401 => (54.0 + 1.0) * Self::scale_y(anime_type),
-            AnimeType::GU604 => 62.0 * Self::scale_y(anime_type),
-            // GA402 may not have dead pixels and require only the physical LED count
-            _ => 61.0 * Self::scale_y(anime_type),
-        }
+        anime_type.model().size.1 * Self::scale_y(anime_type)
     }
 
     /// Find the actual width of the data including the dead pixels
     fn pitch(anime_type: AnimeType, y: u32) -> u32 {
-        match anime_type {
-            AnimeType::GA401 => match y {
-                0 | 2 | 4 => 33,
-                1 | 3 => 35, // Some rows are padded
-                _ => 36 - y / 2,
-            },
-            AnimeType::GU604 => AnimeImage::width(anime_type, y),
-            // GA402 does not have padding, equivalent to width
-            _ => AnimeImage::width(anime_type, y),
-        }
+        anime_type.model().rows[y as usize].2
+    }
+
+    /// Convert a brightness per LED, in the same order as
+    /// `generate_image_positioning()`, to the data buffer layout
+    pub(crate) fn data_buffer_from_leds(
+        anime_type: AnimeType,
+        mut leds: Vec<u8>,
+    ) -> Result<AnimeDataBuffer> {
+        let mut v = Vec::with_capacity(anime_type.data_length());
+        v.resize(anime_type.model().data_start, 0);
+        v.append(&mut leds);
+        v.append(&mut vec![0u8; anime_type.data_length() - v.len()]);
+        AnimeDataBuffer::from_vec(anime_type, v)
     }
 
     pub(crate) fn get_mut(&mut self) -> &mut [Pixel] {
@@ -313,7 +269,14 @@ impl AnimeImage {
         let du = led_from_px * Vec3::new(-0.5, 0.5, 0.0);
         let dv = led_from_px * Vec3::new(0.5, 0.5, 0.0);
 
-        for led in self.led_pos.iter_mut().flatten() {
+        let mut values: Vec<f32> = Vec::with_capacity(self.led_pos.len());
+        for led in &self.led_pos {
+            let Some(led) = led else {
+                values.push(0.0);
+                continue;
+            };
+            // Samples off the image are transparent, so the edges blend with
+            // the background
             let mut sum = 0.0;
             let mut alpha = 0.0;
             let mut count = 0;
@@ -324,6 +287,7 @@ impl AnimeImage {
             const GROUP: [f32; 4] = [0.0, 0.5, 1.0, 1.5];
             for u in &GROUP {
                 for v in &GROUP {
+                    count += 1;
                     let sample = x0 + *u * du + *v * dv;
 
                     let x = sample.x as i32;
@@ -333,14 +297,20 @@ impl AnimeImage {
                     }
 
                     let p = self.img_pixels[(x + (y * width)) as usize];
-                    sum += p.color as f32;
+                    sum += p.color as f32 * p.alpha;
                     alpha += p.alpha;
-                    count += 1;
                 }
             }
             alpha /= count as f32;
             sum /= count as f32;
-            led.set_bright((sum * self.bright * alpha) as u8);
+            values.push(self.colour.blend(sum, alpha) * self.bright);
+        }
+
+        let bright = self.colour.quantise(&self.led_pos, &mut values);
+        for (led, bright) in self.led_pos.iter_mut().zip(bright) {
+            if let Some(led) = led {
+                led.set_bright(bright);
+            }
         }
     }
 
@@ -411,8 +381,8 @@ impl AnimeImage {
     }
 
     /// Generate the base image from inputs. The result can be displayed as is
-    /// or updated via scale, position, or angle then displayed again after
-    /// `update()`.
+    /// or updated via scale, position, angle, or colour options then displayed
+    /// again after `update()`.
     #[inline]
     pub fn from_png(
         path: &Path,
@@ -420,6 +390,7 @@ impl AnimeImage {
         angle: f32,
         translation: Vec2,
         bright: f32,
+        colour: ColourOptions,
         anime_type: AnimeType,
     ) -> Result<Self> {
         let data = std::fs::read(path).map_err(|e| {
@@ -429,59 +400,66 @@ impl AnimeImage {
         let data = std::io::Cursor::new(data);
         let decoder = png_pong::Decoder::new(data)?.into_steps();
         let png_pong::Step { raster, delay: _ } = decoder.last().ok_or(AnimeError::NoFrames)??;
+        let (pixels, width) = Self::pixels_from_raster(&raster, &Luminance::new())?;
 
-        let width;
-        let pixels = match &raster {
+        let mut matrix = AnimeImage::new(
+            Vec2::new(scale, scale),
+            angle,
+            translation,
+            bright,
+            pixels,
+            width,
+            anime_type,
+        )?;
+        matrix.colour = colour;
+
+        matrix.update();
+        Ok(matrix)
+    }
+
+    /// Replace the image that is sampled, such as with the next frame of an
+    /// animation. `update()` must be called after.
+    pub(crate) fn set_pixels(&mut self, pixels: Vec<Pixel>, width: u32) {
+        self.img_pixels = pixels;
+        self.width = width;
+    }
+
+    /// The greyscale pixels and width of a decoded png, or one frame of an
+    /// apng
+    pub(crate) fn pixels_from_raster(
+        raster: &png_pong::PngRaster,
+        lum: &Luminance,
+    ) -> Result<(Vec<Pixel>, u32)> {
+        Ok(match raster {
             png_pong::PngRaster::Gray8(ras) => {
-                width = ras.width();
-                Self::pixels_from_8bit(ras, true)
+                (Self::pixels_from_8bit(ras, lum, true), ras.width())
             }
             png_pong::PngRaster::Graya8(ras) => {
-                width = ras.width();
-                Self::pixels_from_8bit(ras, true)
+                (Self::pixels_from_8bit(ras, lum, true), ras.width())
             }
             png_pong::PngRaster::Rgb8(ras) => {
-                width = ras.width();
-                Self::pixels_from_8bit(ras, false)
+                (Self::pixels_from_8bit(ras, lum, false), ras.width())
             }
             png_pong::PngRaster::Rgba8(ras) => {
-                width = ras.width();
-                Self::pixels_from_8bit(ras, false)
+                (Self::pixels_from_8bit(ras, lum, false), ras.width())
             }
             png_pong::PngRaster::Gray16(ras) => {
-                width = ras.width();
-                Self::pixels_from_16bit(ras, true)
+                (Self::pixels_from_16bit(ras, lum, true), ras.width())
             }
             png_pong::PngRaster::Rgb16(ras) => {
-                width = ras.width();
-                Self::pixels_from_16bit(ras, false)
+                (Self::pixels_from_16bit(ras, lum, false), ras.width())
             }
             png_pong::PngRaster::Graya16(ras) => {
-                width = ras.width();
-                Self::pixels_from_16bit(ras, true)
+                (Self::pixels_from_16bit(ras, lum, true), ras.width())
             }
             png_pong::PngRaster::Rgba16(ras) => {
-                width = ras.width();
-                Self::pixels_from_16bit(ras, false)
+                (Self::pixels_from_16bit(ras, lum, false), ras.width())
             }
             png_pong::PngRaster::Palette(..) => return Err(AnimeError::Format),
-        };
-
-        let mut matrix = AnimeImage::new(
-            Vec2::new(scale, scale),
-            angle,
-            translation,
-            bright,
-            pixels,
-            width,
-            anime_type,
-        )?;
-
-        matrix.update();
-        Ok(matrix)
+        })
     }
 
-    fn pixels_from_8bit<P>(ras: &pix::Raster<P>, grey: bool) -> Vec<Pixel>
+    fn pixels_from_8bit<P>(ras: &pix::Raster<P>, lum: &Luminance, grey: bool) -> Vec<Pixel>
     where
         P: pix::el::Pixel<Chan = pix::chan::Ch8>,
     {
@@ -491,16 +469,18 @@ impl AnimeImage {
                 color: if grey {
                     <u8>::from(px.one()) as u32
                 } else {
-                    (<u8>::from(px.one()) / 3) as u32
-                        + (<u8>::from(px.two()) / 3) as u32
-                        + (<u8>::from(px.three()) / 3) as u32
+                    lum.grey(
+                        <u8>::from(px.one()),
+                        <u8>::from(px.two()),
+                        <u8>::from(px.three()),
+                    ) as u32
                 },
                 alpha: <f32>::from(px.alpha()),
             })
             .collect()
     }
 
-    fn pixels_from_16bit<P>(ras: &pix::Raster<P>, grey: bool) -> Vec<Pixel>
+    fn pixels_from_16bit<P>(ras: &pix::Raster<P>, lum: &Luminance, grey: bool) -> Vec<Pixel>
     where
         P: pix::el::Pixel<Chan = pix::chan::Ch16>,
     {
@@ -510,9 +490,11 @@ impl AnimeImage {
                 color: if grey {
                     (<u16>::from(px.one()) >> 8) as u32
                 } else {
-                    ((<u16>::from(px.one()) / 3) >> 8) as u32
-                        + ((<u16>::from(px.two()) / 3) >> 8) as u32
-                        + ((<u16>::from(px.three()) / 3) >> 8) as u32
+                    lum.grey(
+                        (<u16>::from(px.one()) >> 8) as u8,
+                        (<u16>::from(px.two()) >> 8) as u8,
+                        (<u16>::from(px.three()) >> 8) as u8,
+                    ) as u32
                 },
                 alpha: <f32>::from(px.alpha()),
             })
@@ -526,18 +508,12 @@ impl TryFrom<&AnimeImage> for AnimeDataBuffer {
     /// Do conversion from the nested Vec in `AnimeDataBuffer` to the two
     /// required packets suitable for sending over USB
     fn try_from(leds: &AnimeImage) -> Result<Self> {
-        let mut l: Vec<u8> = leds
+        let l: Vec<u8> = leds
             .led_pos
             .iter()
             .map(|l| if let Some(l) = l { l.bright() } else { 0 })
             .collect();
-        let mut v = Vec::with_capacity(leds.anime_type.data_length());
-        if leds.anime_type == AnimeType::GA401 {
-            v.push(0);
-        }
-        v.append(&mut l);
-        v.append(&mut vec![0u8; leds.anime_type.data_length() - v.len()]);
-        AnimeDataBuffer::from_vec(leds.anime_type, v)
+        AnimeImage::data_buffer_from_leds(leds.anime_type, l)
     }
 }
 
@@ -547,7 +523,7 @@ mod tests {
     use std::path::PathBuf;
 
     use crate::image::*;
-    use crate::{AnimTime, AnimeGif, AnimePacketType};
+    use crate::{AnimTime, AnimeGif, AnimePacketType, Placement};
 
     #[test]
     fn led_positions() {
@@ -638,6 +614,51 @@ mod tests {
         assert_eq!(AnimeImage::pitch(a, 14), 29);
     }
 
+    #[test]
+    fn blend_transparency() {
+        let top = Pixel {
+            color: 200,
+            alpha: 0.5,
+        };
+        let below = Pixel {
+            color: 100,
+            alpha: 1.0,
+        };
+        let px = top.over(below);
+        assert_eq!((px.color, px.alpha), (150, 1.0));
+        assert_eq!(Pixel::default().over(below).color, 100);
+
+        let pixels = vec![
+            Pixel {
+                color: 200,
+                alpha: 0.0,
+            };
+            100
+        ];
+        let mut image = AnimeImage::new(
+            Vec2::new(1.0, 1.0),
+            0.0,
+            Vec2::default(),
+            1.0,
+            pixels,
+            10,
+            AnimeType::GA402,
+        )
+        .unwrap();
+        image.colour.background = 100;
+        image.update();
+        assert!(image.led_pos.iter().flatten().all(|l| l.bright() == 100));
+
+        image.colour.background = 0;
+        for px in image.get_mut() {
+            px.alpha = 1.0;
+        }
+        image.update();
+        let max = image.led_pos.iter().flatten().map(Led::bright).max();
+        assert_eq!(max, Some(200));
+        assert!(image.led_pos.iter().flatten().any(|l| l.bright() == 0));
+    }
+
     #[test]
     #[ignore = "Just to inspect image packet"]
     fn ga402_image_packet_check() {
@@ -646,12 +667,15 @@ mod tests {
 
         let matrix = AnimeGif::from_gif(
             &path,
-            1.0,
-            0.0,
-            Vec2::default(),
+            Placement {
+                scale: 1.0,
+                angle: 0.0,
+                translation: Vec2::default(),
+                brightness: 1.0,
+                colour: ColourOptions::default(),
+                anime_type: AnimeType::GA402,
+            },
             AnimTime::Infinite,
-            1.0,
-            AnimeType::GA402,
         )
         .unwrap();
         matrix.frames()[0].frame();
@@ -7,10 +7,19 @@ pub use data::*;
 mod grid;
 pub use grid::*;
 
+/// The geometry of each `AniMe` display, read from `data/anime_models.ron`
+mod model;
+pub use model::*;
+
 /// Transform a PNG image for displaying on `AniMe` matrix display
 mod image;
 pub use image::*;
 
+/// Luminance, transparency, gamma and dithering used when turning images in to
+/// LED brightness
+mod colour;
+pub use colour::*;
+
 /// A grid of data that is intended to be read out and displayed on the `AniMe`
 /// as a diagonal
 mod diagonal;
@@ -21,11 +30,39 @@ pub use diagonal::*;
 mod gif;
 pub use crate::gif::*;
 
+/// Animations decoded a few frames ahead while they play, instead of all at
+/// once when they are loaded
+mod stream;
+pub use stream::*;
+
+/// Text rendered with a built-in font, static or scrolling across the display
+mod text;
+pub use text::*;
+
+/// Animations made while they play by procedural generators, such as Matrix
+/// rain and the Game of Life
+mod generator;
+pub use generator::*;
+
 /// A container of images/grids/gifs/pauses which can be iterated over to
 /// generate cool effects
 mod sequencer;
 pub use sequencer::*;
 
+/// Shuffled and weighted play orders, and rules for when an action in a list
+/// is played
+mod playlist;
+pub use playlist::*;
+
+/// Crossfades, wipes, slides and dissolves from one action to the next
+mod transition;
+pub use transition::*;
+
+/// Draw the display as it looks to a PNG or GIF, for previews without the
+/// hardware
+mod render;
+pub use render::*;
+
 /// Base errors that are possible
 pub mod error;
 
@@ -0,0 +1,349 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{BLOCK_START, PANE_LEN};
+use crate::usb::PACKET_SIZE;
+use crate::{AnimeType, USB_PREFIX1, USB_PREFIX2, USB_PREFIX3};
+
+/// The models in `data/anime_models.ron`
+const MODELS: &str = include_str!("../data/anime_models.ron");
+
+/// The packets a frame is split over, in order. Byte 3 of each is the packet
+/// index used by `PacketRow`.
+pub(crate) const PACKET_PREFIXES: [[u8; 7]; 3] = [USB_PREFIX1, USB_PREFIX2, USB_PREFIX3];
+
+/// A row of LEDs, counting from the top of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LedRow(
+    /// The x position of the first LED, on a square grid where the bottom
+    /// left corner of the display has been cut off diagonally
+    pub u32,
+    /// How many LEDs are physically in the row
+    pub u32,
+    /// How many bytes of data the row takes, which is more than the LED count
+    /// if the row is padded
+    pub u32,
+);
+
+/// A row of LEDs as read from a diagonal image, such as the ASUS gifs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DiagonalRow(
+    /// The index in the data that the row starts at
+    pub usize,
+    /// The x position in the image of the first LED
+    pub usize,
+    /// The y position of the first LED, counting up from the bottom of the
+    /// image. Each following LED is one up and one to the right.
+    pub usize,
+    /// How many LEDs to read
+    pub usize,
+);
+
+/// A row of LEDs as the simulator reads them from a USB packet. A row that is
+/// split across two packets is given as two rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PacketRow(
+    /// The USB packet index, this is mapped to the 4th byte (idx = 3) and is
+    /// one of (in order of packets): 1. `0x01`
+    /// 2. `0x74`
+    /// 3. `0xe7`
+    pub u8,
+    /// Starting index in that packet
+    pub usize,
+    /// The length to read inclusive
+    pub usize,
+    /// Offset to the right by how many LEDs
+    pub i32,
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LedShape {
+    /// Vertical offset from center for the top/bottom points
+    pub vertical: i32,
+    /// Horizontal offset from center for the top/bottom points
+    pub horizontal: i32,
+}
+
+/// The geometry of an `AniMe` display, used for placing images, converting
+/// diagonal images, and by the simulators.
+///
+/// Adding a model means adding an entry to `data/anime_models.ron`. The tests
+/// check every entry with `AnimeModel::check()`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AnimeModel {
+    /// The name used for the `AnimeType`, such as `GA402`
+    pub name: String,
+    /// A laptop has this display if its DMI board name contains one of these
+    pub board_names: Vec<String>,
+    /// How many USB packets the data of a frame is split over, at most 3
+    pub packets: usize,
+    /// The distance in cm between LED centres across a row, and between rows.
+    /// This is the physical width (or height) from LED centre to centre
+    /// divided by the LED count of the longest row (or column) plus any
+    /// offset.
+    pub scale: (f32, f32),
+    /// The width and height of the display in LED spacings. The width is the
+    /// longest row plus half an LED for the offset of odd rows, the height
+    /// includes dead pixels.
+    pub size: (f32, f32),
+    /// Bytes at the start of the data that are not an LED
+    pub data_start: usize,
+    /// Each row of LEDs from the top, as laid out in the data
+    pub rows: Vec<LedRow>,
+    /// The width and height of a diagonal image
+    pub diagonal_size: (usize, usize),
+    /// Where each row of the data is read from in a diagonal image
+    pub diagonal_rows: Vec<DiagonalRow>,
+    /// The shape of an LED in the simulator
+    pub led_shape: LedShape,
+    /// Where each row of LEDs is in the USB packets, for the simulator
+    pub packet_rows: Vec<PacketRow>,
+}
+
+impl AnimeModel {
+    /// The length of usable data
+    pub fn data_length(&self) -> usize {
+        self.packets * PANE_LEN
+    }
+
+    /// Find mistakes in the model that would write outside of the data or
+    /// read outside of an image
+    pub fn check(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if self.packets == 0 || self.packets > PACKET_PREFIXES.len() {
+            issues.push(format!("{} packets, must be 1 to 3", self.packets));
+            return issues;
+        }
+        let len = self.data_length();
+
+        let mut end = self.data_start;
+        for (y, LedRow(_, width, pitch)) in self.rows.iter().enumerate() {
+            if width > pitch {
+                issues.push(format!("row {y} has {width} LEDs but a pitch of {pitch}"));
+            }
+            end += *pitch as usize;
+        }
+        if end > len {
+            issues.push(format!("the rows end at {end}, the data is {len} long"));
+        }
+
+        let (width, height) = self.diagonal_size;
+        for (i, DiagonalRow(start, x, y, count)) in self.diagonal_rows.iter().enumerate() {
+            if start + count > len {
+                issues.push(format!("diagonal row {i} ends past the data"));
+            }
+            if x + count > width || y + count > height {
+                issues.push(format!("diagonal row {i} ends outside the image"));
+            }
+        }
+
+        let indexes: Vec<u8> = PACKET_PREFIXES[..self.packets]
+            .iter()
+            .map(|p| p[3])
+            .collect();
+        for (i, PacketRow(packet, start, count, _)) in self.packet_rows.iter().enumerate() {
+            if !indexes.contains(packet) {
+                issues.push(format!("packet row {i} is in packet {packet:#04x}"));
+            }
+            if *start < BLOCK_START || start + count >= PACKET_SIZE {
+                issues.push(format!("packet row {i} is outside the packet"));
+            }
+        }
+        issues
+    }
+}
+
+/// Every known model
+pub fn anime_models() -> &'static [AnimeModel] {
+    static LOADED: OnceLock<Vec<AnimeModel>> = OnceLock::new();
+    LOADED.get_or_init(|| {
+        // The file is built in and parsed by the tests, so this can't fail
+        ron::from_str(MODELS).expect("data/anime_models.ron is invalid")
+    })
+}
+
+/// Where an LED is on the display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LedPos {
+    /// The index of the LED in the data
+    pub index: usize,
+    /// The row, counting from the top
+    pub row: usize,
+    /// The x position on the square grid of `LedRow`
+    pub column: usize,
+    /// The position of the LED centre in cm, where odd rows sit half an LED
+    /// to the left
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Where each LED of a display is, for drawing by physical position
+#[derive(Debug, Clone)]
+pub(crate) struct LedGrid {
+    rows: &'static [LedRow],
+    pub scale: (f32, f32),
+    /// The data index of the first LED of each row
+    row_starts: Vec<usize>,
+    pub leds: Vec<LedPos>,
+    /// The furthest LED centres, as `(min_x, min_y, max_x, max_y)`
+    pub bounds: (f32, f32, f32, f32),
+}
+
+impl LedGrid {
+    pub fn new(anime_type: AnimeType) -> Self {
+        let model = anime_type.model();
+        let (sx, sy) = model.scale;
+        let mut row_starts = Vec::with_capacity(model.rows.len());
+        let mut leds = Vec::new();
+        let mut start = model.data_start;
+        for (row, LedRow(first_x, width, pitch)) in model.rows.iter().enumerate() {
+            row_starts.push(start);
+            // Odd rows sit half an LED to the left, as in
+            // `AnimeImage::generate_image_positioning()`
+            let offset = 0.5 * (row % 2) as f32;
+            for l in 0..*width {
+                let column = (first_x + l) as usize;
+                leds.push(LedPos {
+                    index: start + l as usize,
+                    row,
+                    column,
+                    x: (column as f32 - offset) * sx,
+                    y: row as f32 * sy,
+                });
+            }
+            start += *pitch as usize;
+        }
+        let bounds = leds.iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(x0, y0, x1, y1), led| (x0.min(led.x), y0.min(led.y), x1.max(led.x), y1.max(led.y)),
+        );
+        Self {
+            rows: &model.rows,
+            scale: model.scale,
+            row_starts,
+            leds,
+            bounds,
+        }
+    }
+
+    /// The number of rows, and the number of columns to the end of the
+    /// longest row
+    pub fn size(&self) -> (usize, usize) {
+        let columns = self
+            .rows
+            .iter()
+            .map(|LedRow(first_x, width, _)| (first_x + width) as usize)
+            .max()
+            .unwrap_or(0);
+        (self.rows.len(), columns)
+    }
+
+    /// The distance a frame moves to be fully off the display, which is one
+    /// LED further than the furthest LEDs are apart
+    pub fn span(&self) -> (f32, f32) {
+        let (x0, y0, x1, y1) = self.bounds;
+        (x1 - x0 + self.scale.0, y1 - y0 + self.scale.1)
+    }
+
+    /// The middle of the furthest LED centres
+    pub fn centre(&self) -> (f32, f32) {
+        let (x0, y0, x1, y1) = self.bounds;
+        ((x0 + x1) / 2.0, (y0 + y1) / 2.0)
+    }
+
+    /// If a point is on the display, including the corner with no LEDs
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let (x0, y0, x1, y1) = self.bounds;
+        let (hx, hy) = (self.scale.0 / 2.0, self.scale.1 / 2.0);
+        (x0 - hx..x1 + hx).contains(&x) && (y0 - hy..y1 + hy).contains(&y)
+    }
+
+    /// The data index of the LED at a row and column, if there is one
+    pub fn at(&self, row: usize, column: usize) -> Option<usize> {
+        let LedRow(first_x, width, _) = *self.rows.get(row)?;
+        let l = column.checked_sub(first_x as usize)?;
+        (l < width as usize).then(|| self.row_starts[row] + l)
+    }
+
+    /// The data index of the LED at a point, if there is one
+    pub fn led_at(&self, x: f32, y: f32) -> Option<usize> {
+        let row = (y / self.scale.1).round();
+        if row < 0.0 || row as usize >= self.rows.len() {
+            return None;
+        }
+        let row = row as usize;
+        let LedRow(first_x, width, _) = self.rows[row];
+        let l = x / self.scale.0 + 0.5 * (row % 2) as f32 - first_x as f32;
+        // A point half an LED past the end of a row is on that row's end
+        // LED, as the rows above and below are offset by half an LED
+        if l < -0.5 || l > width as f32 - 0.5 {
+            return None;
+        }
+        Some(self.row_starts[row] + (l.round() as usize).min(width as usize - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_are_valid() {
+        let models = anime_models();
+        assert!(!models.is_empty());
+        for (i, model) in models.iter().enumerate() {
+            assert_eq!(model.check(), Vec::<String>::new(), "{}", model.name);
+            assert!(
+                models[i + 1..].iter().all(|m| m.name != model.name),
+                "{} is listed twice",
+                model.name
+            );
+            assert_eq!(AnimeType::named(&model.name).model(), model);
+        }
+    }
+
+    #[test]
+    fn anime_type_names() {
+        assert_eq!("gu604".parse::<AnimeType>().unwrap(), AnimeType::GU604);
+        assert_eq!(AnimeType::named("GX999"), AnimeType::Unsupported);
+        assert_eq!(AnimeType::from_board_name("GA402XV"), AnimeType::GA402);
+        assert_eq!(AnimeType::from_board_name("G634JY"), AnimeType::Unsupported);
+        // Unsupported has the GA402 geometry
+        assert_eq!(AnimeType::Unsupported.model(), AnimeType::GA402.model());
+
+        let ron = ron::to_string(&AnimeType::GA401).unwrap();
+        assert_eq!(ron, "\"GA401\"");
+        assert_eq!(ron::from_str::<AnimeType>(&ron).unwrap(), AnimeType::GA401);
+        assert!(ron::from_str::<AnimeType>("\"GX999\"").is_err());
+    }
+
+    #[test]
+    fn check_finds_mistakes() {
+        let mut model = AnimeType::GA402.model().clone();
+        model.rows[3].1 = model.rows[3].2 + 1;
+        model.diagonal_rows[0].3 = model.diagonal_size.0 + 1;
+        model.packet_rows[0].0 = 0x02;
+        assert_eq!(model.check().len(), 3);
+
+        model.packets = 4;
+        assert_eq!(model.check().len(), 1);
+    }
+
+    #[test]
+    fn led_grid_positions() {
+        for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+            let grid = LedGrid::new(anime_type);
+            let (rows, columns) = grid.size();
+            assert_eq!(rows, anime_type.model().rows.len());
+            // Every LED can be found by its position and by its row and column
+            for led in &grid.leds {
+                assert_eq!(grid.led_at(led.x, led.y), Some(led.index));
+                assert_eq!(grid.at(led.row, led.column), Some(led.index));
+                assert!(led.column < columns);
+            }
+            assert_eq!(grid.at(rows, 0), None);
+            assert_eq!(grid.at(0, columns), None);
+        }
+    }
+}
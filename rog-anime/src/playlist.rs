@@ -0,0 +1,347 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// How long to wait before checking the `PlayRules` again when none of the
+/// actions can be played, or when only one can and others may be later
+pub const RECHECK_RULES: Duration = Duration::from_secs(10);
+
+/// The order the actions of a list are played in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PlayOrder {
+    /// Top to bottom, then start again
+    #[default]
+    InOrder,
+    /// Every action once in a random order, then again in a new order
+    Shuffle,
+    /// Actions picked at random, with an action that has twice the `weight`
+    /// of another picked twice as often
+    Weighted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    /// From the number of days since Sunday, as in `libc::tm`
+    fn from_days_since_sunday(days: i64) -> Self {
+        match days.rem_euclid(7) {
+            0 => Self::Sun,
+            1 => Self::Mon,
+            2 => Self::Tue,
+            3 => Self::Wed,
+            4 => Self::Thu,
+            5 => Self::Fri,
+            _ => Self::Sat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// The power state as read by the daemon playing the list. A rule on
+/// something that could not be read never matches.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState {
+    pub source: Option<PowerSource>,
+    /// Battery charge as a percentage
+    pub battery: Option<u8>,
+}
+
+#[cfg(feature = "detect")]
+impl From<&rog_platform::power::AsusPower> for PowerState {
+    /// Read the mains and battery, anything that can't be read is left unknown
+    fn from(power: &rog_platform::power::AsusPower) -> Self {
+        Self {
+            source: power.get_online().ok().map(|online| {
+                if online == 1 {
+                    PowerSource::Ac
+                } else {
+                    PowerSource::Battery
+                }
+            }),
+            battery: power.get_capacity().ok(),
+        }
+    }
+}
+
+/// What the `PlayRules` of an action are checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayContext {
+    /// The hour of the day in local time, 0 to 23
+    pub hour: u8,
+    pub day: Weekday,
+    pub power: PowerState,
+}
+
+impl PlayContext {
+    /// The context at the current local time
+    pub fn now(power: PowerState) -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()) as libc::time_t;
+        // SAFETY: `tm` is plain data, and both pointers are valid for the call
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        let (hour, day) = if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+            // Fall back to UTC, the epoch was a Thursday
+            let secs = secs as i64;
+            ((secs / 3600 % 24) as u8, secs / 86400 + 4)
+        } else {
+            (tm.tm_hour as u8, tm.tm_wday as i64)
+        };
+        Self {
+            hour,
+            day: Weekday::from_days_since_sunday(day),
+            power,
+        }
+    }
+}
+
+/// When and how often an action of a list is played. The default plays it
+/// every time round.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PlayRules {
+    /// How often the action is picked by `PlayOrder::Weighted` compared to
+    /// the others. `0.0` is never.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Hours the action is played in, as `(from, to)` in 24 hour local time
+    /// where `to` is not included, so `(22, 6)` is 10pm until 6am. Empty is
+    /// any hour.
+    #[serde(default)]
+    pub hours: Vec<(u8, u8)>,
+    /// Days the action is played on. Empty is any day.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Play only on AC power or only on battery
+    #[serde(default)]
+    pub power: Option<PowerSource>,
+    /// Play only while the battery charge is within `(min, max)` percent,
+    /// inclusive
+    #[serde(default)]
+    pub battery: Option<(u8, u8)>,
+    /// Play the action once, then drop it from the list until the list is
+    /// loaded again
+    #[serde(default)]
+    pub once: bool,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl Default for PlayRules {
+    fn default() -> Self {
+        Self {
+            weight: default_weight(),
+            hours: Vec::new(),
+            days: Vec::new(),
+            power: None,
+            battery: None,
+            once: false,
+        }
+    }
+}
+
+impl PlayRules {
+    /// If the action can be played in this context
+    pub fn allows(&self, context: &PlayContext) -> bool {
+        let hour = context.hour;
+        let in_hours = self.hours.is_empty()
+            || self.hours.iter().any(|&(from, to)| {
+                if from <= to {
+                    from == to || (from..to).contains(&hour)
+                } else {
+                    hour >= from || hour < to
+                }
+            });
+        let on_day = self.days.is_empty() || self.days.contains(&context.day);
+        let on_power = self.power.is_none() || self.power == context.power.source;
+        let in_battery = match self.battery {
+            Some((min, max)) => context
+                .power
+                .battery
+                .is_some_and(|level| (min..=max).contains(&level)),
+            None => true,
+        };
+        in_hours && on_day && on_power && in_battery
+    }
+}
+
+/// One step of splitmix64, which spreads numbers that are close together far
+/// apart
+pub(crate) fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A small xorshift generator for picking actions, seeded from the clock. It is
+/// atomic so that a shared `Sequences` can pick a round.
+#[derive(Debug)]
+pub(crate) struct Rng(AtomicU64);
+
+impl Default for Rng {
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self(AtomicU64::new(nanos | 1))
+    }
+}
+
+impl Clone for Rng {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl Rng {
+    /// A generator that always gives the same numbers for the same seed
+    pub(crate) fn seeded(seed: u64) -> Self {
+        // Mixed so that close seeds start far apart
+        Self(AtomicU64::new(splitmix64(seed) | 1))
+    }
+
+    pub(crate) fn next(&self) -> u64 {
+        let mut x = self.0.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0.store(x, Ordering::Relaxed);
+        x
+    }
+
+    /// A number in `0.0..1.0`
+    pub(crate) fn unit(&self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// The order to play `candidates` in for one time round a list.
+/// `weights` are indexed the same as the list.
+pub(crate) fn play_round(
+    order: PlayOrder,
+    candidates: Vec<usize>,
+    weights: &[f32],
+    rng: &Rng,
+) -> Vec<usize> {
+    match order {
+        PlayOrder::InOrder => candidates,
+        PlayOrder::Shuffle => {
+            let mut round = candidates;
+            for i in (1..round.len()).rev() {
+                let j = (rng.next() % (i as u64 + 1)) as usize;
+                round.swap(i, j);
+            }
+            round
+        }
+        PlayOrder::Weighted => {
+            let total: f32 = candidates.iter().map(|&i| weights[i].max(0.0)).sum();
+            if total <= 0.0 {
+                return Vec::new();
+            }
+            (0..candidates.len())
+                .filter_map(|_| {
+                    let mut pick = rng.unit() * total;
+                    candidates.iter().copied().find(|&i| {
+                        pick -= weights[i].max(0.0);
+                        pick < 0.0
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u8, day: Weekday, source: Option<PowerSource>, battery: Option<u8>) -> PlayContext {
+        PlayContext {
+            hour,
+            day,
+            power: PowerState { source, battery },
+        }
+    }
+
+    #[test]
+    fn rules_allow() {
+        let any = at(12, Weekday::Mon, None, None);
+        assert!(PlayRules::default().allows(&any));
+
+        let night = PlayRules {
+            hours: vec![(22, 6)],
+            ..Default::default()
+        };
+        assert!(night.allows(&at(23, Weekday::Mon, None, None)));
+        assert!(night.allows(&at(5, Weekday::Mon, None, None)));
+        assert!(!night.allows(&at(6, Weekday::Mon, None, None)));
+        assert!(!night.allows(&any));
+
+        let weekend = PlayRules {
+            days: vec![Weekday::Sat, Weekday::Sun],
+            ..Default::default()
+        };
+        assert!(weekend.allows(&at(12, Weekday::Sun, None, None)));
+        assert!(!weekend.allows(&any));
+
+        let low_battery = PlayRules {
+            power: Some(PowerSource::Battery),
+            battery: Some((0, 20)),
+            ..Default::default()
+        };
+        assert!(low_battery.allows(&at(12, Weekday::Mon, Some(PowerSource::Battery), Some(20))));
+        assert!(!low_battery.allows(&at(12, Weekday::Mon, Some(PowerSource::Battery), Some(21))));
+        assert!(!low_battery.allows(&at(12, Weekday::Mon, Some(PowerSource::Ac), Some(10))));
+        // Nothing is known about the power, so the rule can't match
+        assert!(!low_battery.allows(&any));
+    }
+
+    #[test]
+    fn rounds() {
+        let rng = Rng(AtomicU64::new(0x2545_f491_4f6c_dd1d));
+        let weights = [1.0, 3.0, 0.0, 1.0];
+        assert_eq!(
+            play_round(PlayOrder::InOrder, vec![0, 1, 3], &weights, &rng),
+            vec![0, 1, 3]
+        );
+
+        let mut shuffled = play_round(PlayOrder::Shuffle, vec![0, 1, 2, 3], &weights, &rng);
+        shuffled.sort_unstable();
+        assert_eq!(shuffled, vec![0, 1, 2, 3]);
+
+        let mut counts = [0; 4];
+        for _ in 0..1000 {
+            for i in play_round(PlayOrder::Weighted, vec![0, 1, 2, 3], &weights, &rng) {
+                counts[i] += 1;
+            }
+        }
+        assert_eq!(counts.iter().sum::<i32>(), 4000);
+        assert_eq!(counts[2], 0);
+        // Three times the weight is picked about three times as often
+        assert!((2.5..3.5).contains(&(counts[1] as f32 / counts[0] as f32)));
+        assert!(play_round(PlayOrder::Weighted, vec![2], &weights, &rng).is_empty());
+    }
+
+    #[test]
+    fn local_time() {
+        let context = PlayContext::now(PowerState::default());
+        assert!(context.hour < 24);
+    }
+}
@@ -0,0 +1,369 @@
+use std::borrow::Cow;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::data::AnimeDataBuffer;
+use crate::error::{AnimeError, Result};
+use crate::image::AnimeImage;
+use crate::sequencer::{SequencePlayer, Sequences, Step};
+use crate::AnimeType;
+
+/// The least time a step of a sequence is shown for in a GIF. This keeps
+/// images with no delay from being stepped over forever.
+const MIN_STEP: Duration = Duration::from_millis(20);
+
+/// How the LEDs are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /// Size of the output, the physical display is up to 31cm wide
+    pub pixels_per_cm: f32,
+    /// Size of each LED relative to the spacing between them, `1.0` has the
+    /// LEDs touching
+    pub led_size: f32,
+    /// Brightness of LEDs that are off, so that the shape of the display
+    /// shows. `0` draws them the same as the background.
+    pub unlit: u8,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            pixels_per_cm: 20.0,
+            led_size: 0.8,
+            unlit: 16,
+        }
+    }
+}
+
+/// An 8bit greyscale image of the display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedFrame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RenderedFrame {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Rows of pixels, top to bottom
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Encode as a greyscale PNG
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let raster = pix::Raster::<pix::gray::SGray8>::with_u8_buffer(
+            self.width,
+            self.height,
+            self.pixels.clone(),
+        );
+        let step = png_pong::Step {
+            raster: png_pong::PngRaster::Gray8(raster),
+            delay: 0,
+        };
+        let mut out = Vec::new();
+        png_pong::Encoder::new(&mut out)
+            .into_step_enc()
+            .encode(&step)?;
+        Ok(out)
+    }
+}
+
+/// Draws `AnimeDataBuffer` as the display looks, without any hardware.
+///
+/// Each LED is placed at its physical position using the same geometry as
+/// `AnimeImage`, so the rows are offset by half an LED and the left edge is
+/// slanted as it is on the laptop lid.
+#[derive(Debug, Clone)]
+pub struct AnimeRender {
+    anime_type: AnimeType,
+    options: RenderOptions,
+    /// Index in to the data buffer, and centre in pixels
+    leds: Vec<(usize, f32, f32)>,
+    width: u32,
+    height: u32,
+}
+
+impl AnimeRender {
+    pub fn new(anime_type: AnimeType, options: RenderOptions) -> Self {
+        let step_x = AnimeImage::scale_x(anime_type) * options.pixels_per_cm;
+        let step_y = AnimeImage::scale_y(anime_type) * options.pixels_per_cm;
+        // Some models' data starts with bytes that are not an LED
+        let offset = anime_type.model().data_start;
+
+        // One LED spacing of border on each side, the odd rows start half an
+        // LED to the left of the even rows
+        let leds: Vec<(usize, f32, f32)> = AnimeImage::generate_image_positioning(anime_type)
+            .iter()
+            .enumerate()
+            .filter_map(|(i, led)| {
+                led.map(|led| {
+                    (
+                        i + offset,
+                        (led.x() + 1.5) * step_x,
+                        (led.y() + 1.0) * step_y,
+                    )
+                })
+            })
+            .collect();
+
+        let (max_x, max_y) = leds.iter().fold((0.0f32, 0.0f32), |(mx, my), (_, x, y)| {
+            (mx.max(*x), my.max(*y))
+        });
+
+        Self {
+            anime_type,
+            options,
+            leds,
+            width: (max_x + step_x).ceil() as u32,
+            height: (max_y + step_y).ceil() as u32,
+        }
+    }
+
+    pub fn anime_type(&self) -> AnimeType {
+        self.anime_type
+    }
+
+    /// Size of the rendered frames as `(width, height)`
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Draw each LED as a diamond so that the neighbouring rows fit together
+    /// the way the physical LEDs do.
+    pub fn render(&self, buffer: &AnimeDataBuffer) -> Result<RenderedFrame> {
+        let data = buffer.data();
+        if data.len() != self.anime_type.data_length() {
+            return Err(AnimeError::DataBufferLength);
+        }
+
+        let half_w = 0.5
+            * AnimeImage::scale_x(self.anime_type)
+            * self.options.pixels_per_cm
+            * self.options.led_size;
+        let half_h = AnimeImage::scale_y(self.anime_type)
+            * self.options.pixels_per_cm
+            * self.options.led_size;
+
+        let mut pixels = vec![0u8; (self.width * self.height) as usize];
+        for (index, cx, cy) in &self.leds {
+            let bright = data.get(*index).copied().unwrap_or_default();
+            let bright = bright.max(self.options.unlit);
+            if bright == 0 {
+                continue;
+            }
+
+            let x0 = (cx - half_w).floor().max(0.0) as u32;
+            let x1 = ((cx + half_w).ceil() as u32).min(self.width);
+            let y0 = (cy - half_h).floor().max(0.0) as u32;
+            let y1 = ((cy + half_h).ceil() as u32).min(self.height);
+            for y in y0..y1 {
+                let dy = ((y as f32 + 0.5) - cy).abs() / half_h;
+                for x in x0..x1 {
+                    let dx = ((x as f32 + 0.5) - cx).abs() / half_w;
+                    if dx + dy <= 1.0 {
+                        pixels[(y * self.width + x) as usize] = bright;
+                    }
+                }
+            }
+        }
+
+        Ok(RenderedFrame {
+            width: self.width,
+            height: self.height,
+            pixels,
+        })
+    }
+
+    /// Render a single frame as a PNG
+    pub fn png(&self, buffer: &AnimeDataBuffer) -> Result<Vec<u8>> {
+        self.render(buffer)?.to_png()
+    }
+
+    /// Write the frames as a looping GIF, each shown for its duration.
+    /// Consecutive frames that look the same are joined in to one.
+    pub fn write_gif<W, I>(&self, writer: W, frames: I) -> Result<()>
+    where
+        W: Write,
+        I: IntoIterator<Item = (AnimeDataBuffer, Duration)>,
+    {
+        let palette: Vec<u8> = (0..=255u8).flat_map(|v| [v, v, v]).collect();
+        let mut encoder =
+            gif::Encoder::new(writer, self.width as u16, self.height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        let mut write = |frame: RenderedFrame, delay: Duration| {
+            // GIF delays are in 1/100 of a second, and most viewers treat
+            // anything less than two as a default delay
+            let delay = (delay.as_millis() / 10).clamp(2, u16::MAX as u128) as u16;
+            encoder.write_frame(&gif::Frame {
+                width: frame.width as u16,
+                height: frame.height as u16,
+                delay,
+                buffer: Cow::Owned(frame.pixels),
+                ..Default::default()
+            })
+        };
+
+        let mut pending: Option<(RenderedFrame, Duration)> = None;
+        for (buffer, delay) in frames {
+            let frame = self.render(&buffer)?;
+            match pending.as_mut() {
+                Some((last, total)) if *last == frame => *total += delay,
+                _ => {
+                    if let Some((last, total)) = pending.replace((frame, delay)) {
+                        write(last, total)?;
+                    }
+                }
+            }
+        }
+        let (last, total) = pending.ok_or(AnimeError::NoFrames)?;
+        write(last, total)?;
+        Ok(())
+    }
+
+    /// Play `sequences` for `duration` and write it as a looping GIF. Time is
+    /// simulated, so this returns as soon as the frames are written.
+    pub fn write_sequences_gif<W: Write>(
+        &self,
+        writer: W,
+        sequences: &Sequences,
+        duration: Duration,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let mut elapsed = Duration::ZERO;
+        let mut player = SequencePlayer::new();
+        let mut frames: Vec<(AnimeDataBuffer, Duration)> = Vec::new();
+
+        while elapsed < duration {
+            let remaining = duration - elapsed;
+            match player.next_step(sequences, start + elapsed) {
+                Some(Step::Frame(buffer, Some(delay))) => {
+                    let delay = delay.max(MIN_STEP).min(remaining);
+                    frames.push((buffer, delay));
+                    elapsed += delay;
+                }
+                // Shown until the sequences change
+                Some(Step::Frame(buffer, None)) => {
+                    frames.push((buffer, remaining));
+                    break;
+                }
+                Some(Step::Wait(wait)) => {
+                    let wait = wait.max(MIN_STEP).min(remaining);
+                    match frames.last_mut() {
+                        Some((_, delay)) => *delay += wait,
+                        None => frames.push((AnimeDataBuffer::new(self.anime_type), wait)),
+                    }
+                    elapsed += wait;
+                }
+                None => break,
+            }
+        }
+        self.write_gif(writer, frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        ActionLoader, AnimTime, AnimeDataBuffer, AnimeRender, AnimeType, RenderOptions,
+        ScrollDirection, Sequences,
+    };
+
+    #[test]
+    fn render_geometry() {
+        for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+            let options = RenderOptions {
+                unlit: 0,
+                ..Default::default()
+            };
+            let render = AnimeRender::new(anime_type, options);
+            let (width, height) = render.size();
+            // Around 27-31cm by 16-18cm
+            assert!((500..700).contains(&width), "{anime_type:?} {width}");
+            assert!((300..400).contains(&height), "{anime_type:?} {height}");
+
+            let blank = render.render(&AnimeDataBuffer::new(anime_type)).unwrap();
+            assert_eq!(blank.pixels().len(), (width * height) as usize);
+            assert!(blank.pixels().iter().all(|p| *p == 0));
+
+            let mut lit = AnimeDataBuffer::new(anime_type);
+            lit.data_mut().fill(255);
+            let lit = render.render(&lit).unwrap();
+            // The top right corner always has an LED, the bottom left never
+            // does because of the slant
+            let (w, h) = (width as usize, height as usize);
+            let row = |y: usize| &lit.pixels()[y * w..(y + 1) * w];
+            let top = h / 20;
+            let bottom = h - h / 20;
+            assert!(row(top)[w * 3 / 4..].contains(&255));
+            assert!(!row(bottom)[..w / 4].contains(&255));
+
+            let wrong = AnimeDataBuffer::new(AnimeType::GA401);
+            if anime_type != AnimeType::GA401 {
+                assert!(render.render(&wrong).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn unlit_leds_show() {
+        let anime_type = AnimeType::GA402;
+        let render = AnimeRender::new(anime_type, RenderOptions::default());
+        let frame = render.render(&AnimeDataBuffer::new(anime_type)).unwrap();
+        assert!(frame.pixels().contains(&RenderOptions::default().unlit));
+        assert!(frame.pixels().contains(&0));
+    }
+
+    #[test]
+    fn sequences_to_gif() {
+        let anime_type = AnimeType::GA402;
+        let mut sequences = Sequences::new(anime_type);
+        sequences
+            .insert(0, &ActionLoader::Pause(Duration::from_millis(500)))
+            .unwrap();
+        sequences
+            .insert(
+                1,
+                &ActionLoader::Text {
+                    text: "Hi".into(),
+                    font_size: 1.0,
+                    direction: ScrollDirection::Left,
+                    speed: 20.0,
+                    time: AnimTime::Infinite,
+                    brightness: 1.0,
+                },
+            )
+            .unwrap();
+        let render = AnimeRender::new(anime_type, RenderOptions::default());
+        let mut out = Vec::new();
+        render
+            .write_sequences_gif(&mut out, &sequences, Duration::from_secs(2))
+            .unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(out.as_slice()).unwrap();
+        assert_eq!(decoder.width() as u32, render.size().0);
+        assert_eq!(decoder.height() as u32, render.size().1);
+
+        let mut count = 0;
+        let mut total = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            count += 1;
+            total += frame.delay as u32;
+        }
+        assert!(count > 2);
+        // 2 seconds in 1/100ths, allowing for the rounding of each frame
+        assert!((190..=200).contains(&total), "{total}");
+    }
+}
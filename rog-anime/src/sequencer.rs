@@ -1,12 +1,20 @@
 use std::convert::TryFrom;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use glam::Vec2;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
-use crate::{AnimTime, AnimeDataBuffer, AnimeDiagonal, AnimeGif, AnimeImage, AnimeType};
+use crate::playlist::{play_round, Rng};
+use crate::{
+    AnimTime, AnimationPlayer, AnimeDataBuffer, AnimeDiagonal, AnimeFrames, AnimeGenerator,
+    AnimeGif, AnimeImage, AnimeStream, AnimeType, ColourOptions, Generator, GeneratorOptions,
+    Placement, PlayContext, PlayOrder, PlayRules, PowerState, ScrollDirection, Transition,
+    TransitionPlayer, RECHECK_RULES, TRANSITION_STEP,
+};
 
 /// All the possible `AniMe` actions that can be used. This enum is intended to
 /// be a helper for loading up `ActionData`.
@@ -24,8 +32,8 @@ pub enum ActionLoader {
         time: AnimTime,
         brightness: f32,
     },
-    /// Animated gif. If the file is a png a static gif is created using the
-    /// `time` properties
+    /// Animated gif, apng or webp. If the file is not animated a static gif is
+    /// created using the `time` properties
     ImageAnimation {
         file: PathBuf,
         scale: f32,
@@ -33,6 +41,23 @@ pub enum ActionLoader {
         translation: Vec2,
         time: AnimTime,
         brightness: f32,
+        /// Background, gamma and dithering for colour or transparent files
+        #[serde(default)]
+        colour: ColourOptions,
+    },
+    /// A directory of numbered png files, such as the frames of a video,
+    /// played at `fps` frames per second
+    FrameSequence {
+        directory: PathBuf,
+        fps: f32,
+        scale: f32,
+        angle: f32,
+        translation: Vec2,
+        time: AnimTime,
+        brightness: f32,
+        /// Background, gamma and dithering for colour or transparent files
+        #[serde(default)]
+        colour: ColourOptions,
     },
     Image {
         file: PathBuf,
@@ -41,9 +66,57 @@ pub enum ActionLoader {
         translation: Vec2,
         time: AnimTime,
         brightness: f32,
+        /// Background, gamma and dithering for colour or transparent files
+        #[serde(default)]
+        colour: ColourOptions,
+    },
+    /// Text drawn with the built-in font. `font_size` of `1.0` makes each
+    /// font pixel one LED wide, and `speed` is in LED widths per second
+    Text {
+        text: String,
+        font_size: f32,
+        direction: ScrollDirection,
+        speed: f32,
+        time: AnimTime,
+        brightness: f32,
+    },
+    /// Frames made while it plays by one of the built-in generators
+    Generator {
+        generator: Generator,
+        #[serde(default)]
+        options: GeneratorOptions,
+        time: AnimTime,
     },
     /// A pause to be used between sequences
     Pause(Duration),
+    /// Another action with rules for when and how often it is played in a
+    /// list, and how it replaces the action before it
+    Entry {
+        action: Box<ActionLoader>,
+        #[serde(default)]
+        rules: PlayRules,
+        #[serde(default)]
+        transition: Option<Transition>,
+    },
+}
+
+impl ActionLoader {
+    /// The rules of an `Entry`, any other action is played every time round
+    pub fn rules(&self) -> PlayRules {
+        match self {
+            ActionLoader::Entry { rules, .. } => rules.clone(),
+            _ => PlayRules::default(),
+        }
+    }
+
+    /// The transition of an `Entry`, any other action replaces the one before
+    /// it straight away
+    pub fn transition(&self) -> Option<Transition> {
+        match self {
+            ActionLoader::Entry { transition, .. } => *transition,
+            _ => None,
+        }
+    }
 }
 
 /// All the possible `AniMe` actions that can be used. The enum is intended to
@@ -52,6 +125,8 @@ pub enum ActionLoader {
 pub enum ActionData {
     /// Full gif sequence. Immutable.
     Animation(AnimeGif),
+    /// An animation decoded while it plays
+    Stream(AnimeStream),
     /// Basic image, can have properties changed and image updated via those
     /// properties
     Image(Box<AnimeDataBuffer>),
@@ -63,11 +138,34 @@ pub enum ActionData {
     SystemInfo,
     /// Placeholder
     TimeDate,
-    /// Placeholder
-    Matrix,
+    /// Frames made while it plays
+    Generator(AnimeGenerator),
 }
 
 impl ActionData {
+    /// The same as `from_anime_action()`, but animations with more than one
+    /// frame are decoded while they play instead of all at once here
+    pub fn from_anime_action_streamed(
+        anime_type: AnimeType,
+        action: &ActionLoader,
+    ) -> Result<ActionData> {
+        if let ActionLoader::Entry { action, .. } = action {
+            return Self::from_anime_action_streamed(anime_type, action);
+        }
+        if matches!(
+            action,
+            ActionLoader::AsusAnimation { .. }
+                | ActionLoader::ImageAnimation { .. }
+                | ActionLoader::FrameSequence { .. }
+        ) {
+            let stream = AnimeStream::new(action.clone(), anime_type)?;
+            if stream.frame_count() > 1 {
+                return Ok(ActionData::Stream(stream));
+            }
+        }
+        Self::from_anime_action(anime_type, action)
+    }
+
     pub fn from_anime_action(anime_type: AnimeType, action: &ActionLoader) -> Result<ActionData> {
         let a = match action {
             ActionLoader::AsusAnimation {
@@ -104,30 +202,41 @@ impl ActionData {
                 translation,
                 time,
                 brightness,
-            } => {
-                if let Some(ext) = file.extension() {
-                    if ext.to_string_lossy().to_lowercase() == "png" {
-                        return Ok(ActionData::Animation(AnimeGif::from_png(
-                            file,
-                            *scale,
-                            *angle,
-                            *translation,
-                            *time,
-                            *brightness,
-                            anime_type,
-                        )?));
-                    }
-                }
-                ActionData::Animation(AnimeGif::from_gif(
-                    file,
-                    *scale,
-                    *angle,
-                    *translation,
-                    *time,
-                    *brightness,
+                colour,
+            } => ActionData::Animation(AnimeGif::from_file(
+                file,
+                Placement {
+                    scale: *scale,
+                    angle: *angle,
+                    translation: *translation,
+                    brightness: *brightness,
+                    colour: *colour,
                     anime_type,
-                )?)
-            }
+                },
+                *time,
+            )?),
+            ActionLoader::FrameSequence {
+                directory,
+                fps,
+                scale,
+                angle,
+                translation,
+                time,
+                brightness,
+                colour,
+            } => ActionData::Animation(AnimeGif::from_png_frames(
+                directory,
+                *fps,
+                Placement {
+                    scale: *scale,
+                    angle: *angle,
+                    translation: *translation,
+                    brightness: *brightness,
+                    colour: *colour,
+                    anime_type,
+                },
+                *time,
+            )?),
             ActionLoader::Image {
                 file,
                 scale,
@@ -135,6 +244,7 @@ impl ActionData {
                 translation,
                 brightness,
                 time,
+                colour,
             } => {
                 match time {
                     AnimTime::Infinite => {
@@ -145,6 +255,7 @@ impl ActionData {
                             *angle,
                             *translation,
                             *brightness,
+                            *colour,
                             anime_type,
                         )?;
                         let data = <AnimeDataBuffer>::try_from(&image)?;
@@ -152,37 +263,109 @@ impl ActionData {
                     }
                     _ => ActionData::Animation(AnimeGif::from_png(
                         file,
-                        *scale,
-                        *angle,
-                        *translation,
+                        Placement {
+                            scale: *scale,
+                            angle: *angle,
+                            translation: *translation,
+                            brightness: *brightness,
+                            colour: *colour,
+                            anime_type,
+                        },
                         *time,
-                        *brightness,
-                        anime_type,
                     )?),
                 }
             }
+            ActionLoader::Text {
+                text,
+                font_size,
+                direction,
+                speed,
+                time,
+                brightness,
+            } => ActionData::Animation(AnimeGif::from_text(
+                text,
+                *font_size,
+                *direction,
+                *speed,
+                *time,
+                *brightness,
+                anime_type,
+            )?),
+            ActionLoader::Generator {
+                generator,
+                options,
+                time,
+            } => ActionData::Generator(AnimeGenerator::new(
+                *generator, *options, *time, anime_type,
+            )?),
             ActionLoader::Pause(duration) => ActionData::Pause(*duration),
+            ActionLoader::Entry { action, .. } => Self::from_anime_action(anime_type, action)?,
         };
         Ok(a)
     }
 }
 
+/// An action in `Sequences` and the rules it is played by
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Entry {
+    action: ActionData,
+    rules: PlayRules,
+    transition: Option<Transition>,
+    /// Set once an action with `PlayRules::once` has been played. Clones of
+    /// the sequences share it.
+    #[serde(skip)]
+    played: Arc<AtomicBool>,
+}
+
+impl Entry {
+    fn playable(&self, context: &PlayContext) -> bool {
+        !(self.rules.once && self.played.load(Ordering::Relaxed)) && self.rules.allows(context)
+    }
+}
+
 /// An optimised precomputed set of actions that the user can cycle through
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Sequences(Vec<ActionData>, AnimeType);
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Sequences {
+    entries: Vec<Entry>,
+    anime_type: AnimeType,
+    #[serde(default)]
+    order: PlayOrder,
+    #[serde(skip)]
+    rng: Rng,
+}
 
 impl Sequences {
     #[inline]
     pub fn new(anime_type: AnimeType) -> Self {
-        Self(Vec::new(), anime_type)
+        Self {
+            entries: Vec::new(),
+            anime_type,
+            order: PlayOrder::default(),
+            rng: Rng::default(),
+        }
+    }
+
+    /// Set the order that `round()` plays the actions in
+    #[inline]
+    pub fn set_order(&mut self, order: PlayOrder) {
+        self.order = order;
     }
 
-    /// Use a base `AnimeAction` to generate the precomputed data and insert in
-    /// to the run buffer
+    /// Use a base `AnimeAction` to generate the data and insert in to the run
+    /// buffer. Animations are streamed, see
+    /// `ActionData::from_anime_action_streamed()`. The rules and transition
+    /// of an `ActionLoader::Entry` are kept with it.
     #[inline]
     pub fn insert(&mut self, index: usize, action: &ActionLoader) -> Result<()> {
-        self.0
-            .insert(index, ActionData::from_anime_action(self.1, action)?);
+        self.entries.insert(
+            index,
+            Entry {
+                action: ActionData::from_anime_action_streamed(self.anime_type, action)?,
+                rules: action.rules(),
+                transition: action.transition(),
+                played: Arc::default(),
+            },
+        );
         Ok(())
     }
 
@@ -191,8 +374,8 @@ impl Sequences {
     /// `ActionData` at that location is yeeted and returned.
     #[inline]
     pub fn remove_item(&mut self, index: usize) -> Option<ActionData> {
-        if index < self.0.len() {
-            return Some(self.0.remove(index));
+        if index < self.entries.len() {
+            return Some(self.entries.remove(index).action);
         }
         None
     }
@@ -203,6 +386,64 @@ impl Sequences {
             next_idx: 0,
         }
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&ActionData> {
+        self.entries.get(index).map(|e| &e.action)
+    }
+
+    /// The indexes of the actions to play for one time round the list, in
+    /// the set order. Only actions that the rules allow in `context` are
+    /// included. This can be empty even if the list is not.
+    pub fn round(&self, context: &PlayContext) -> Vec<usize> {
+        let candidates = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.playable(context))
+            .map(|(i, _)| i)
+            .collect();
+        let weights: Vec<f32> = self.entries.iter().map(|e| e.rules.weight).collect();
+        play_round(self.order, candidates, &weights, &self.rng)
+    }
+
+    /// The transition in to the action at `index`
+    #[inline]
+    pub fn transition(&self, index: usize) -> Option<Transition> {
+        self.entries.get(index).and_then(|e| e.transition)
+    }
+
+    /// Start playing the action at `index`. Returns `None` if the rules no
+    /// longer allow it, such as when the hour changed since the round was
+    /// made or it is a `once` action that has been played.
+    pub fn begin(&self, index: usize, context: &PlayContext) -> Option<&ActionData> {
+        let entry = self.entries.get(index)?;
+        if !entry.playable(context) {
+            return None;
+        }
+        if entry.rules.once {
+            entry.played.store(true, Ordering::Relaxed);
+        }
+        Some(&entry.action)
+    }
+
+    /// If any action could still be played, now or at another time. This is
+    /// false once every action is a `once` action that has been played.
+    pub fn has_remaining(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|e| !(e.rules.once && e.played.load(Ordering::Relaxed)))
+    }
 }
 
 /// Iteractor helper for iterating over all the actions in `Sequences`
@@ -216,7 +457,7 @@ impl<'a> Iterator for ActionIterator<'a> {
 
     #[inline]
     fn next(&mut self) -> Option<&'a ActionData> {
-        if self.next_idx == self.actions.0.len() {
+        if self.next_idx == self.actions.entries.len() {
             self.next_idx = 0;
             return None;
         }
@@ -224,6 +465,436 @@ impl<'a> Iterator for ActionIterator<'a> {
         let current = self.next_idx;
         self.next_idx += 1;
 
-        Some(&self.actions.0[current])
+        Some(&self.actions.entries[current].action)
+    }
+}
+
+/// What a `SequencePlayer` wants shown next
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Write this frame and show it for the duration. `None` shows it until
+    /// the sequences change, such as for a single image.
+    Frame(AnimeDataBuffer, Option<Duration>),
+    /// Leave the display as it is for the duration
+    Wait(Duration),
+}
+
+/// A transition in progress, and the frame of the new action it is blending
+/// to until that frame is replaced
+#[derive(Debug)]
+struct Blend {
+    player: TransitionPlayer,
+    target: Option<AnimeDataBuffer>,
+    /// When the target is due to be replaced, `None` is never
+    until: Option<Instant>,
+}
+
+/// Steps through `Sequences` one frame at a time, a round at a time in the
+/// order set on them. The caller writes each frame and waits for the given
+/// time, which lets a timer drive the display instead of a thread.
+#[derive(Debug, Default)]
+pub struct SequencePlayer {
+    /// The indexes of the actions in this time round the sequences
+    round: Vec<usize>,
+    position: usize,
+    animation: Option<AnimationPlayer>,
+    power: PowerState,
+    /// The frame last shown, which a transition starts from
+    last: Option<AnimeDataBuffer>,
+    blend: Option<Blend>,
+}
+
+impl SequencePlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Go back to the first action, this must be called after the sequences
+    /// are changed
+    pub fn restart(&mut self) {
+        self.round.clear();
+        self.position = 0;
+        self.animation = None;
+        self.blend = None;
+    }
+
+    /// Set the power state that the `PlayRules` of the actions are checked
+    /// against
+    pub fn set_power(&mut self, power: PowerState) {
+        self.power = power;
+    }
+
+    /// The next step of `sequences` at `now`. Returns `None` if none of the
+    /// actions have anything to show.
+    pub fn next_step(&mut self, sequences: &Sequences, now: Instant) -> Option<Step> {
+        // Keep blending while the frame being blended to is still due
+        if let Some(blend) = &self.blend {
+            if blend.target.is_some() && blend.until.map_or(true, |until| now < until) {
+                return self.blend_step(now);
+            }
+        }
+        let step = self.advance(sequences, now);
+        if let Some(blend) = &mut self.blend {
+            match &step {
+                Some(Step::Frame(frame, delay)) => {
+                    blend.target = Some(frame.clone());
+                    blend.until = delay.map(|delay| now + delay);
+                    return self.blend_step(now);
+                }
+                // The display is left as it is during a wait, so the
+                // transition carries on
+                Some(Step::Wait(wait)) if blend.target.is_some() => {
+                    blend.until = Some(now + *wait);
+                    return self.blend_step(now);
+                }
+                _ => {}
+            }
+        }
+        if let Some(Step::Frame(frame, _)) = &step {
+            self.last = Some(frame.clone());
+        }
+        step
+    }
+
+    /// The current transition frame, shown until the next blend or until the
+    /// frame being blended to is replaced. The target frame is returned
+    /// as it is once the transition is over.
+    fn blend_step(&mut self, now: Instant) -> Option<Step> {
+        let blend = self.blend.as_ref()?;
+        let target = blend.target.as_ref()?;
+        let until = blend
+            .until
+            .map(|until| until.saturating_duration_since(now));
+        let step = match blend.player.frame(target, now) {
+            Some(frame) => {
+                let delay = TRANSITION_STEP.min(blend.player.remaining(now));
+                Step::Frame(frame, Some(until.map_or(delay, |until| delay.min(until))))
+            }
+            None => {
+                let target = target.clone();
+                self.blend = None;
+                Step::Frame(target, until)
+            }
+        };
+        if let Step::Frame(frame, _) = &step {
+            self.last = Some(frame.clone());
+        }
+        Some(step)
+    }
+
+    /// The next step of the actions themselves, without transitions
+    fn advance(&mut self, sequences: &Sequences, now: Instant) -> Option<Step> {
+        let len = sequences.len();
+        // Set if an action was passed over because of its rules, which may
+        // allow it later
+        let mut held_back = false;
+        // Every action may be passed over once before giving up
+        for _ in 0..=len {
+            if self.position >= self.round.len() {
+                self.position = 0;
+                self.animation = None;
+                self.round = sequences.round(&PlayContext::now(self.power));
+                if self.round.is_empty() {
+                    return sequences
+                        .has_remaining()
+                        .then_some(Step::Wait(RECHECK_RULES));
+                }
+            }
+            let index = self.round[self.position];
+            let action = if self.animation.is_some() {
+                sequences.get(index)
+            } else {
+                sequences.begin(index, &PlayContext::now(self.power))
+            };
+            let Some(action) = action else {
+                held_back = true;
+                self.position += 1;
+                continue;
+            };
+            let shows_frames = matches!(
+                action,
+                ActionData::Animation(_)
+                    | ActionData::Stream(_)
+                    | ActionData::Generator(_)
+                    | ActionData::Image(_)
+            );
+            if self.animation.is_none() && shows_frames {
+                if let Some(transition) = sequences.transition(index) {
+                    let from = self
+                        .last
+                        .clone()
+                        .unwrap_or_else(|| AnimeDataBuffer::new(sequences.anime_type));
+                    self.blend = Some(Blend {
+                        player: TransitionPlayer::new(transition, from, now),
+                        target: None,
+                        until: None,
+                    });
+                }
+            }
+            match action {
+                ActionData::Animation(frames) => {
+                    if let Some(step) = self.animate(frames, now) {
+                        return Some(step);
+                    }
+                }
+                ActionData::Stream(frames) => {
+                    if let Some(step) = self.animate(frames, now) {
+                        return Some(step);
+                    }
+                }
+                ActionData::Generator(frames) => {
+                    if let Some(step) = self.animate(frames, now) {
+                        return Some(step);
+                    }
+                }
+                ActionData::Image(image) => {
+                    self.position += 1;
+                    // Move straight on to the next action unless there is
+                    // none. If the rules are holding the others back, check
+                    // them again later.
+                    let duration = if self.round.len() > 1 {
+                        Some(Duration::ZERO)
+                    } else if len > 1 {
+                        Some(RECHECK_RULES)
+                    } else {
+                        None
+                    };
+                    return Some(Step::Frame(image.as_ref().clone(), duration));
+                }
+                ActionData::Pause(duration) => {
+                    self.position += 1;
+                    return Some(Step::Wait(*duration));
+                }
+                ActionData::AudioEq | ActionData::SystemInfo | ActionData::TimeDate => {
+                    self.position += 1
+                }
+            }
+        }
+        held_back.then_some(Step::Wait(RECHECK_RULES))
+    }
+
+    /// The next frame of the current animation, or `None` once it has
+    /// finished and the next action should be shown
+    fn animate(&mut self, frames: &dyn AnimeFrames, now: Instant) -> Option<Step> {
+        let player = self
+            .animation
+            .get_or_insert_with(|| AnimationPlayer::new(frames));
+        if let Some((frame, delay)) = player.next_frame(frames, now) {
+            return Some(Step::Frame(frame, Some(delay)));
+        }
+        self.animation = None;
+        self.position += 1;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{ActionData, Entry, SequencePlayer, Sequences, Step};
+    use crate::{
+        AnimTime, AnimeDataBuffer, AnimeGif, AnimeType, PlayContext, PlayOrder, PlayRules,
+        PowerSource, PowerState, ScrollDirection, Transition, TransitionStyle, RECHECK_RULES,
+    };
+
+    fn sequences(anime_type: AnimeType, actions: Vec<(ActionData, PlayRules)>) -> Sequences {
+        let mut sequences = Sequences::new(anime_type);
+        sequences.entries = actions
+            .into_iter()
+            .map(|(action, rules)| Entry {
+                action,
+                rules,
+                transition: None,
+                played: Default::default(),
+            })
+            .collect();
+        sequences
+    }
+
+    fn plain(anime_type: AnimeType, actions: Vec<ActionData>) -> Sequences {
+        sequences(
+            anime_type,
+            actions
+                .into_iter()
+                .map(|a| (a, PlayRules::default()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn step_through_sequences() {
+        let anime_type = AnimeType::GA402;
+        let text = AnimeGif::from_text(
+            "Hi",
+            1.0,
+            ScrollDirection::Static,
+            1.0,
+            AnimTime::Count(2),
+            1.0,
+            anime_type,
+        )
+        .unwrap();
+        let frame_count = text.frame_count();
+        let image = Box::new(AnimeDataBuffer::new(anime_type));
+        let sequences = plain(
+            anime_type,
+            vec![
+                ActionData::Animation(text),
+                ActionData::AudioEq,
+                ActionData::Pause(Duration::from_secs(1)),
+                ActionData::Image(image.clone()),
+            ],
+        );
+
+        let mut player = SequencePlayer::new();
+        let now = Instant::now();
+        for _ in 0..2 {
+            for _ in 0..frame_count * 2 {
+                let step = player.next_step(&sequences, now);
+                assert!(matches!(step, Some(Step::Frame(_, Some(d))) if d.as_millis() == 30));
+            }
+            let step = player.next_step(&sequences, now);
+            assert!(matches!(step, Some(Step::Wait(d)) if d.as_secs() == 1));
+            let step = player.next_step(&sequences, now);
+            assert!(matches!(step, Some(Step::Frame(_, Some(Duration::ZERO)))));
+        }
+
+        // A single image is left on the display
+        let single = plain(anime_type, vec![ActionData::Image(image)]);
+        player.restart();
+        let step = player.next_step(&single, now);
+        assert!(matches!(step, Some(Step::Frame(_, None))));
+
+        let nothing = plain(anime_type, vec![ActionData::AudioEq]);
+        player.restart();
+        assert!(player.next_step(&nothing, now).is_none());
+        assert!(player.next_step(&Sequences::new(anime_type), now).is_none());
+    }
+
+    #[test]
+    fn rules_and_order() {
+        let anime_type = AnimeType::GA402;
+        let pause = |secs| ActionData::Pause(Duration::from_secs(secs));
+        let on_battery = PlayRules {
+            power: Some(PowerSource::Battery),
+            ..Default::default()
+        };
+        let mut list = sequences(
+            anime_type,
+            vec![
+                (pause(1), PlayRules::default()),
+                (pause(2), on_battery.clone()),
+                (pause(3), once_rules()),
+            ],
+        );
+        let ac = PlayContext::now(PowerState {
+            source: Some(PowerSource::Ac),
+            battery: Some(80),
+        });
+        assert_eq!(list.round(&ac), vec![0, 2]);
+        assert!(list.begin(1, &ac).is_none());
+        assert!(list.begin(2, &ac).is_some());
+        // A once action is dropped after it is played, clones included
+        let copy = list.clone();
+        assert!(copy.begin(2, &ac).is_none());
+        assert_eq!(list.round(&ac), vec![0]);
+
+        list.set_order(PlayOrder::Shuffle);
+        let battery = PlayContext::now(PowerState {
+            source: Some(PowerSource::Battery),
+            battery: Some(50),
+        });
+        let mut round = list.round(&battery);
+        round.sort_unstable();
+        assert_eq!(round, vec![0, 1]);
+
+        // The player follows the power state it is given
+        list.set_order(PlayOrder::InOrder);
+        let mut player = SequencePlayer::new();
+        let now = Instant::now();
+        let mut waits = || match player.next_step(&list, now) {
+            Some(Step::Wait(d)) => d.as_secs(),
+            _ => 0,
+        };
+        assert_eq!([waits(), waits(), waits()], [1, 1, 1]);
+        player.set_power(battery.power);
+        player.restart();
+        let mut waits = || match player.next_step(&list, now) {
+            Some(Step::Wait(d)) => d.as_secs(),
+            _ => 0,
+        };
+        assert_eq!([waits(), waits(), waits()], [1, 2, 1]);
+
+        // Nothing allowed right now, but something may be later
+        let later = sequences(anime_type, vec![(pause(1), on_battery)]);
+        player.set_power(PowerState::default());
+        player.restart();
+        let step = player.next_step(&later, now);
+        assert!(matches!(step, Some(Step::Wait(d)) if d == RECHECK_RULES));
+
+        // Once every once action has played there is nothing left
+        let done = sequences(anime_type, vec![(pause(1), once_rules())]);
+        player.restart();
+        assert!(matches!(player.next_step(&done, now), Some(Step::Wait(_))));
+        assert!(player.next_step(&done, now).is_none());
+    }
+
+    #[test]
+    fn transition_between_images() {
+        let anime_type = AnimeType::GA402;
+        let image = |bright| {
+            let mut buffer = AnimeDataBuffer::new(anime_type);
+            buffer.data_mut().fill(bright);
+            ActionData::Image(Box::new(buffer))
+        };
+        let second = Duration::from_secs(1);
+        let mut list = plain(
+            anime_type,
+            vec![
+                image(0),
+                ActionData::Pause(second),
+                image(200),
+                ActionData::Pause(second),
+            ],
+        );
+        list.entries[2].transition = Some(Transition {
+            style: TransitionStyle::Crossfade,
+            duration: second,
+        });
+
+        let mut player = SequencePlayer::new();
+        let start = Instant::now();
+        let led = |step: Option<Step>| match step {
+            Some(Step::Frame(frame, delay)) => (frame.data()[100], delay),
+            _ => panic!("expected a frame"),
+        };
+        assert_eq!(led(player.next_step(&list, start)).0, 0);
+        assert!(matches!(
+            player.next_step(&list, start),
+            Some(Step::Wait(_))
+        ));
+
+        // The crossfade carries on through the pause after the image
+        let at = start + second;
+        assert_eq!(led(player.next_step(&list, at)), (0, Some(Duration::ZERO)));
+        let (bright, delay) = led(player.next_step(&list, at));
+        assert_eq!(bright, 0);
+        assert!(delay.unwrap() < second);
+        let (bright, _) = led(player.next_step(&list, at + second / 2));
+        assert_eq!(bright, 100);
+        let (bright, _) = led(player.next_step(&list, at + second * 9 / 10));
+        assert_eq!(bright, 180);
+
+        // Then the first image, which has no transition
+        let (bright, delay) = led(player.next_step(&list, at + second));
+        assert_eq!((bright, delay), (0, Some(Duration::ZERO)));
+    }
+
+    fn once_rules() -> PlayRules {
+        PlayRules {
+            once: true,
+            ..Default::default()
+        }
     }
 }
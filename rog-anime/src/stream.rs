@@ -0,0 +1,374 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AnimeError, Result};
+use crate::gif::{
+    diagonal_gif_frames, png_frames, png_frames_timing, FrameIter, ImageFormat, Placement, Timing,
+};
+use crate::{ActionLoader, AnimTime, AnimeFrame, AnimeGif, AnimeType};
+
+/// How many frames are decoded ahead of the one being shown
+const LOOK_AHEAD: usize = 4;
+/// How many of the most recently shown frames are kept. An animation with no
+/// more frames than this is only decoded once.
+const CACHE_FRAMES: usize = 256;
+
+/// The frames of an animation as `AnimationPlayer` steps through them, either
+/// all computed up front or decoded while it plays
+pub trait AnimeFrames {
+    /// The time/count to play the animation for
+    fn duration(&self) -> AnimTime;
+
+    fn frame_count(&self) -> usize;
+
+    /// The time to play every frame once
+    fn total_frame_time(&self) -> Duration;
+
+    /// The frame at `index`, which is less than `frame_count()`
+    fn frame(&self, index: usize) -> Result<AnimeFrame>;
+
+    /// Called when the animation stops playing, so that anything kept only
+    /// for playing it can be let go
+    fn finished(&self) {}
+}
+
+impl AnimeFrames for AnimeGif {
+    fn duration(&self) -> AnimTime {
+        AnimeGif::duration(self)
+    }
+
+    fn frame_count(&self) -> usize {
+        AnimeGif::frame_count(self)
+    }
+
+    fn total_frame_time(&self) -> Duration {
+        AnimeGif::total_frame_time(self)
+    }
+
+    fn frame(&self, index: usize) -> Result<AnimeFrame> {
+        self.frames()
+            .get(index)
+            .cloned()
+            .ok_or(AnimeError::NoFrames)
+    }
+}
+
+/// The most recently used frames of a stream
+#[derive(Debug, Default)]
+struct FrameCache(VecDeque<(usize, AnimeFrame)>);
+
+impl FrameCache {
+    fn get(&mut self, index: usize) -> Option<AnimeFrame> {
+        let pos = self.0.iter().position(|(i, _)| *i == index)?;
+        let entry = self.0.remove(pos)?;
+        let frame = entry.1.clone();
+        self.0.push_back(entry);
+        Some(frame)
+    }
+
+    fn insert(&mut self, index: usize, frame: AnimeFrame) {
+        self.0.retain(|(i, _)| *i != index);
+        if self.0.len() >= CACHE_FRAMES {
+            self.0.pop_front();
+        }
+        self.0.push_back((index, frame));
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// A thread decoding the frames of a stream in order, looping back to the
+/// first after the last. It stops when the receiver is dropped.
+#[derive(Debug)]
+struct Decoder {
+    frames: Receiver<Result<AnimeFrame>>,
+    /// The index of the next frame to be received
+    next: usize,
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    decoder: Option<Decoder>,
+    cache: FrameCache,
+}
+
+/// An animation that is decoded and rasterised a few frames ahead of where it
+/// is playing, instead of all at once when it is loaded. This keeps long
+/// animations from costing startup time and memory.
+///
+/// The frame count and play time are read from the file when it is created,
+/// so `AnimTime` is handled the same as for an `AnimeGif`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnimeStream {
+    action: ActionLoader,
+    anime_type: AnimeType,
+    timing: Timing,
+    #[serde(skip)]
+    state: Mutex<StreamState>,
+}
+
+impl Clone for AnimeStream {
+    fn clone(&self) -> Self {
+        Self {
+            action: self.action.clone(),
+            anime_type: self.anime_type,
+            timing: self.timing,
+            state: Mutex::default(),
+        }
+    }
+}
+
+impl AnimeStream {
+    /// Stream the frames of an `AsusAnimation`, `ImageAnimation` or
+    /// `FrameSequence`. Any other action is an `AnimeError::Format`.
+    pub fn new(action: ActionLoader, anime_type: AnimeType) -> Result<Self> {
+        let timing = match &action {
+            ActionLoader::AsusAnimation { file, .. } => ImageFormat::Gif.timing(file)?,
+            ActionLoader::ImageAnimation { file, .. } => ImageFormat::of(file).timing(file)?,
+            ActionLoader::FrameSequence { directory, fps, .. } => {
+                png_frames_timing(directory, *fps)?
+            }
+            _ => return Err(AnimeError::Format),
+        };
+        Ok(Self {
+            action,
+            anime_type,
+            timing,
+            state: Mutex::default(),
+        })
+    }
+
+    /// Start decoding from the first frame
+    fn open(&self) -> Result<FrameIter> {
+        match &self.action {
+            ActionLoader::AsusAnimation {
+                file, brightness, ..
+            } => diagonal_gif_frames(file, *brightness, self.anime_type),
+            ActionLoader::ImageAnimation {
+                file,
+                scale,
+                angle,
+                translation,
+                brightness,
+                colour,
+                ..
+            } => ImageFormat::of(file).frames(
+                file,
+                Placement {
+                    scale: *scale,
+                    angle: *angle,
+                    translation: *translation,
+                    brightness: *brightness,
+                    colour: *colour,
+                    anime_type: self.anime_type,
+                },
+            ),
+            ActionLoader::FrameSequence {
+                directory,
+                fps,
+                scale,
+                angle,
+                translation,
+                brightness,
+                colour,
+                ..
+            } => png_frames(
+                directory,
+                *fps,
+                Placement {
+                    scale: *scale,
+                    angle: *angle,
+                    translation: *translation,
+                    brightness: *brightness,
+                    colour: *colour,
+                    anime_type: self.anime_type,
+                },
+            ),
+            _ => Err(AnimeError::Format),
+        }
+    }
+
+    fn start(&self) -> Result<Decoder> {
+        let (send, frames) = sync_channel(LOOK_AHEAD);
+        let stream = self.clone();
+        std::thread::Builder::new()
+            .name("AniMe frame decoder".into())
+            .spawn(move || loop {
+                let frames = match stream.open() {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        send.send(Err(e)).ok();
+                        return;
+                    }
+                };
+                let mut any = false;
+                for frame in frames {
+                    any = true;
+                    if send.send(frame).is_err() {
+                        return;
+                    }
+                }
+                if !any {
+                    return;
+                }
+            })?;
+        Ok(Decoder { frames, next: 0 })
+    }
+}
+
+impl AnimeFrames for AnimeStream {
+    fn duration(&self) -> AnimTime {
+        match &self.action {
+            ActionLoader::AsusAnimation { time, .. }
+            | ActionLoader::ImageAnimation { time, .. }
+            | ActionLoader::FrameSequence { time, .. } => *time,
+            _ => AnimTime::Infinite,
+        }
+    }
+
+    fn frame_count(&self) -> usize {
+        self.timing.0
+    }
+
+    fn total_frame_time(&self) -> Duration {
+        self.timing.1
+    }
+
+    fn frame(&self, index: usize) -> Result<AnimeFrame> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(frame) = state.cache.get(index) {
+            return Ok(frame);
+        }
+        // The decoder only goes forwards, so start again for an earlier frame
+        if state.decoder.as_ref().map(|d| d.next) != Some(index) {
+            state.decoder = None;
+        }
+        if state.decoder.is_none() {
+            state.decoder = Some(self.start()?);
+        }
+
+        let StreamState { decoder, cache } = &mut *state;
+        while let Some(dec) = decoder.as_mut() {
+            let Ok(frame) = dec.frames.recv() else {
+                break;
+            };
+            let i = dec.next;
+            dec.next = (i + 1) % self.frame_count().max(1);
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    *decoder = None;
+                    return Err(e);
+                }
+            };
+            cache.insert(i, frame.clone());
+            if i == index {
+                if cache.len() >= self.frame_count() {
+                    // Everything is cached, there is nothing left to decode
+                    *decoder = None;
+                }
+                return Ok(frame);
+            }
+        }
+        *decoder = None;
+        warn!("AnimeStream: the file ended before frame {index}");
+        Err(AnimeError::NoFrames)
+    }
+
+    fn finished(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.decoder = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use glam::Vec2;
+
+    use super::*;
+    use crate::gif::tests::{animated_webp, placement};
+    use crate::{AnimationPlayer, ColourOptions};
+
+    #[test]
+    fn frame_cache_order() {
+        let frame = AnimeGif::from_text(
+            "A",
+            1.0,
+            crate::ScrollDirection::Static,
+            1.0,
+            AnimTime::Infinite,
+            1.0,
+            AnimeType::GA402,
+        )
+        .unwrap()
+        .frames()[0]
+            .clone();
+        let mut cache = FrameCache::default();
+        for i in 0..CACHE_FRAMES {
+            cache.insert(i, frame.clone());
+        }
+        // Using the first frame makes the second the least recently used
+        assert!(cache.get(0).is_some());
+        cache.insert(CACHE_FRAMES, frame);
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(0).is_some());
+        assert_eq!(cache.len(), CACHE_FRAMES);
+    }
+
+    #[test]
+    fn stream_matches_precomputed() {
+        let path =
+            std::env::temp_dir().join(format!("rog-anime-stream-{}.webp", std::process::id()));
+        std::fs::write(&path, animated_webp(40, &[255, 0, 128, 64, 32], 40)).unwrap();
+        let action = ActionLoader::ImageAnimation {
+            file: path.clone(),
+            scale: 1.0,
+            angle: 0.0,
+            translation: Vec2::default(),
+            time: AnimTime::Count(2),
+            brightness: 1.0,
+            colour: ColourOptions::default(),
+        };
+        let gif = AnimeGif::from_file(&path, placement(), AnimTime::Count(2)).unwrap();
+        let stream = AnimeStream::new(action, AnimeType::GA402).unwrap();
+
+        assert_eq!(stream.frame_count(), 5);
+        assert_eq!(stream.total_frame_time(), gif.total_frame_time());
+
+        // Both play the same frames for the same number of loops
+        let now = Instant::now();
+        let mut a = AnimationPlayer::new(&gif);
+        let mut b = AnimationPlayer::new(&stream);
+        let mut played = 0;
+        loop {
+            match (a.next_frame(&gif, now), b.next_frame(&stream, now)) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.0.data(), b.0.data());
+                    assert_eq!(a.1, b.1);
+                    played += 1;
+                }
+                (None, None) => break,
+                _ => panic!("the stream and the gif ended at different frames"),
+            }
+        }
+        assert_eq!(played, 10);
+
+        // Going back to the start is served from the cache once the file is
+        // gone
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            stream.frame(0).unwrap().frame().data(),
+            gif.frames()[0].frame().data()
+        );
+        assert!(stream.state.lock().unwrap().decoder.is_none());
+    }
+}
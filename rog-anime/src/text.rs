@@ -0,0 +1,385 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AnimeError, Result};
+use crate::{AnimeDataBuffer, AnimeImage, AnimeType};
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+/// Blank columns between each glyph
+const GLYPH_SPACING: usize = 1;
+
+/// Classic 5x7 font covering printable ASCII (`0x20..=0x7e`). Each glyph is
+/// five columns, and each column is a bitmask with bit 0 as the top row.
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x14, 0x08, 0x3e, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// Get the glyph for a char. Anything not printable ASCII is shown as `?`
+fn glyph(c: char) -> &'static [u8; GLYPH_WIDTH] {
+    let idx = if (' '..='~').contains(&c) {
+        c as usize - ' ' as usize
+    } else {
+        '?' as usize - ' ' as usize
+    };
+    &FONT[idx]
+}
+
+/// The direction text moves across the display
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ScrollDirection {
+    /// Text enters on the right and exits on the left
+    #[default]
+    Left,
+    /// Text enters on the left and exits on the right
+    Right,
+    /// Text enters at the bottom and exits at the top
+    Up,
+    /// Text enters at the top and exits at the bottom
+    Down,
+    /// Text is centered and does not move
+    Static,
+}
+
+impl FromStr for ScrollDirection {
+    type Err = AnimeError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "up" => Ok(Self::Up),
+            "down" => Ok(Self::Down),
+            "static" | "none" => Ok(Self::Static),
+            _ => Err(AnimeError::ParseError(s.to_owned())),
+        }
+    }
+}
+
+/// A line of text rasterised with the built-in 5x7 font, ready to be rendered
+/// at any position on the display.
+///
+/// Positions are in centimeters using the physical LED spacing of the display
+/// so that the text keeps its shape regardless of the slanted LED layout or
+/// the half-LED offset of odd rows.
+pub struct AnimeText {
+    /// One entry per pixel of the rasterised text, `true` if lit
+    pixels: Vec<bool>,
+    /// Width of the rasterised text in pixels
+    width: usize,
+    /// Size of a single font pixel in cm
+    pixel_size: f32,
+    /// Brightness of final image, `0.0` = off, `1.0` = full
+    bright: f32,
+    /// Physical position in cm of each LED, in the same order as
+    /// `AnimeImage::generate_image_positioning()`
+    led_pos: Vec<Option<Vec2>>,
+    /// The top-left and bottom-right extents of all LEDs in cm
+    bounds: (Vec2, Vec2),
+    anime_type: AnimeType,
+}
+
+impl AnimeText {
+    /// Rasterise `text`. A `font_size` of `1.0` makes one font pixel the width
+    /// of one LED, which gives a glyph height of roughly a third of the
+    /// display.
+    pub fn new(text: &str, font_size: f32, bright: f32, anime_type: AnimeType) -> Result<Self> {
+        if !(0.0..=1.0).contains(&bright) {
+            return Err(AnimeError::InvalidBrightness(bright));
+        }
+        if !(font_size > 0.0 && font_size.is_finite()) {
+            return Err(AnimeError::InvalidFontSize(font_size));
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let width = (chars.len() * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+        let mut pixels = vec![false; width * GLYPH_HEIGHT];
+        for (i, c) in chars.iter().enumerate() {
+            let x0 = i * (GLYPH_WIDTH + GLYPH_SPACING);
+            for (x, col) in glyph(*c).iter().enumerate() {
+                for y in 0..GLYPH_HEIGHT {
+                    if col & (1 << y) != 0 {
+                        pixels[x0 + x + y * width] = true;
+                    }
+                }
+            }
+        }
+
+        let scale_x = AnimeImage::scale_x(anime_type);
+        let scale_y = AnimeImage::scale_y(anime_type);
+        let led_pos: Vec<Option<Vec2>> = AnimeImage::generate_image_positioning(anime_type)
+            .iter()
+            .map(|l| l.map(|l| Vec2::new(l.x() * scale_x, l.y() * scale_y)))
+            .collect();
+        let (min, max) = led_pos.iter().flatten().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+        // Pad by half an LED so the bounds cover the full LED footprint
+        let pad = Vec2::new(scale_x, scale_y) * 0.5;
+        let bounds = (min - pad, max + pad);
+
+        Ok(Self {
+            pixels,
+            width,
+            pixel_size: font_size * scale_x,
+            bright,
+            led_pos,
+            bounds,
+            anime_type,
+        })
+    }
+
+    /// Size of the rendered text in cm
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(
+            self.width as f32 * self.pixel_size,
+            GLYPH_HEIGHT as f32 * self.pixel_size,
+        )
+    }
+
+    fn pixel(&self, pos: Vec2) -> bool {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return false;
+        }
+        let x = pos.x as usize;
+        let y = pos.y as usize;
+        x < self.width && y < GLYPH_HEIGHT && self.pixels[x + y * self.width]
+    }
+
+    /// Render the text with its top-left corner at `origin` (in cm). Each LED
+    /// samples four points across its own footprint so that partially covered
+    /// LEDs are dimmed instead of flickering as the text moves.
+    pub fn render(&self, origin: Vec2) -> Result<AnimeDataBuffer> {
+        let du = AnimeImage::scale_x(self.anime_type) * 0.25;
+        let dv = AnimeImage::scale_y(self.anime_type) * 0.25;
+        let samples = [
+            Vec2::new(-du, -dv),
+            Vec2::new(du, -dv),
+            Vec2::new(-du, dv),
+            Vec2::new(du, dv),
+        ];
+
+        let leds = self
+            .led_pos
+            .iter()
+            .map(|pos| {
+                if let Some(pos) = pos {
+                    let lit = samples
+                        .iter()
+                        .filter(|s| self.pixel((*pos + **s - origin) / self.pixel_size))
+                        .count();
+                    (lit as f32 / samples.len() as f32 * 255.0 * self.bright) as u8
+                } else {
+                    0
+                }
+            })
+            .collect();
+
+        AnimeImage::data_buffer_from_leds(self.anime_type, leds)
+    }
+
+    /// The top-left origin of each frame required to move the text across the
+    /// display in `direction`. `speed` is in LED widths per second, and
+    /// `delay` is the time each frame is shown for.
+    ///
+    /// A `speed` of zero or less, or `ScrollDirection::Static`, gives a single
+    /// centered position.
+    pub fn scroll_positions(
+        &self,
+        direction: ScrollDirection,
+        speed: f32,
+        delay: Duration,
+    ) -> Vec<Vec2> {
+        let (min, max) = self.bounds;
+        let size = self.size();
+        let center = (min + max - size) * 0.5;
+
+        let (start, end) = match direction {
+            ScrollDirection::Left => (
+                Vec2::new(max.x, center.y),
+                Vec2::new(min.x - size.x, center.y),
+            ),
+            ScrollDirection::Right => (
+                Vec2::new(min.x - size.x, center.y),
+                Vec2::new(max.x, center.y),
+            ),
+            ScrollDirection::Up => (
+                Vec2::new(center.x, max.y),
+                Vec2::new(center.x, min.y - size.y),
+            ),
+            ScrollDirection::Down => (
+                Vec2::new(center.x, min.y - size.y),
+                Vec2::new(center.x, max.y),
+            ),
+            ScrollDirection::Static => return vec![center],
+        };
+
+        let step = speed * AnimeImage::scale_x(self.anime_type) * delay.as_secs_f32();
+        if step <= 0.0 {
+            return vec![center];
+        }
+        let steps = ((end - start).length() / step).ceil() as usize;
+        (0..=steps)
+            .map(|i| start + (end - start) * (i as f32 / steps.max(1) as f32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::text::*;
+
+    #[test]
+    fn glyph_fallback() {
+        assert_eq!(glyph('A'), &FONT[33]);
+        assert_eq!(glyph('~'), &FONT[94]);
+        assert_eq!(glyph('é'), glyph('?'));
+    }
+
+    #[test]
+    fn render_static() {
+        for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+            let text = AnimeText::new("Hi", 1.0, 1.0, anime_type).unwrap();
+            let pos = text.scroll_positions(ScrollDirection::Static, 1.0, Duration::ZERO);
+            assert_eq!(pos.len(), 1);
+
+            let data = text.render(pos[0]).unwrap();
+            assert_eq!(data.data().len(), anime_type.data_length());
+            assert!(data.data().contains(&255));
+        }
+    }
+
+    #[test]
+    fn scroll_starts_and_ends_blank() {
+        for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+            let text = AnimeText::new("Build passed", 1.0, 1.0, anime_type).unwrap();
+            for direction in [
+                ScrollDirection::Left,
+                ScrollDirection::Right,
+                ScrollDirection::Up,
+                ScrollDirection::Down,
+            ] {
+                let pos = text.scroll_positions(direction, 20.0, Duration::from_millis(30));
+                assert!(pos.len() > 2);
+                let first = text.render(pos[0]).unwrap();
+                let last = text.render(*pos.last().unwrap()).unwrap();
+                assert!(first.data().iter().all(|b| *b == 0));
+                assert!(last.data().iter().all(|b| *b == 0));
+                let middle = text.render(pos[pos.len() / 2]).unwrap();
+                assert!(middle.data().iter().any(|b| *b != 0));
+            }
+        }
+    }
+
+    #[test]
+    fn bad_brightness() {
+        assert!(AnimeText::new("x", 1.0, 1.5, AnimeType::GA402).is_err());
+    }
+
+    #[test]
+    fn bad_font_size() {
+        for size in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(AnimeText::new("x", size, 1.0, AnimeType::GA402).is_err());
+        }
+    }
+}
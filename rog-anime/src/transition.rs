@@ -0,0 +1,286 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::LedGrid;
+use crate::playlist::splitmix64;
+use crate::AnimeDataBuffer;
+
+/// How long each blended frame is shown for when the frame being blended to
+/// is shown for longer, such as an image
+pub const TRANSITION_STEP: Duration = Duration::from_millis(33);
+
+/// The direction a wipe or slide moves in
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransitionDirection {
+    /// Starts at the right edge and moves to the left
+    #[default]
+    Left,
+    /// Starts at the left edge and moves to the right
+    Right,
+    /// Starts at the bottom and moves to the top
+    Up,
+    /// Starts at the top and moves to the bottom
+    Down,
+}
+
+/// The way an action replaces the frame shown before it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransitionStyle {
+    /// The brightness of each LED moves from the old frame to the new
+    Crossfade,
+    /// An edge moves across the display uncovering the new frame behind it
+    Wipe(TransitionDirection),
+    /// The new frame pushes the old one off the display
+    Slide(TransitionDirection),
+    /// The LEDs change to the new frame one at a time in a scattered order
+    Dissolve,
+}
+
+/// A transition in to an action from the frame shown before it. Wipes and
+/// slides move by the physical position of the LEDs, so that an edge is
+/// straight on the display even though the rows are offset and the corner is
+/// cut off.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Transition {
+    pub style: TransitionStyle,
+    pub duration: Duration,
+}
+
+impl Transition {
+    /// The frame `progress` of the way from `from` to `to`, where `0.0` is all
+    /// `from` and `1.0` is all `to`. If the two are for different displays
+    /// `to` is returned.
+    pub fn blend(
+        &self,
+        from: &AnimeDataBuffer,
+        to: &AnimeDataBuffer,
+        progress: f32,
+    ) -> AnimeDataBuffer {
+        if from.data().len() != to.data().len() || progress >= 1.0 {
+            return to.clone();
+        }
+        if progress <= 0.0 {
+            return from.clone();
+        }
+        let (old, new) = (from.data(), to.data());
+        let mut out = to.clone();
+        let data = out.data_mut();
+        match self.style {
+            TransitionStyle::Crossfade => {
+                for (led, (&a, &b)) in data.iter_mut().zip(old.iter().zip(new)) {
+                    *led = (a as f32 + (b as f32 - a as f32) * progress).round() as u8;
+                }
+            }
+            TransitionStyle::Dissolve => {
+                let grid = LedGrid::new(to.anime_type());
+                for led in &grid.leds {
+                    if scatter(led.index) >= progress {
+                        data[led.index] = old[led.index];
+                    }
+                }
+            }
+            TransitionStyle::Wipe(direction) => {
+                let grid = LedGrid::new(to.anime_type());
+                for led in &grid.leds {
+                    if along(&grid, direction, led.x, led.y) >= progress {
+                        data[led.index] = old[led.index];
+                    }
+                }
+            }
+            TransitionStyle::Slide(direction) => {
+                let grid = LedGrid::new(to.anime_type());
+                let (width, height) = grid.span();
+                let (dx, dy) = match direction {
+                    TransitionDirection::Left => (width, 0.0),
+                    TransitionDirection::Right => (-width, 0.0),
+                    TransitionDirection::Up => (0.0, height),
+                    TransitionDirection::Down => (0.0, -height),
+                };
+                for led in &grid.leds {
+                    // Where this LED is on the old and new frames as they
+                    // move across
+                    let (x, y) = (led.x + dx * progress, led.y + dy * progress);
+                    data[led.index] = if grid.contains(x, y) {
+                        grid.led_at(x, y).map_or(0, |j| old[j])
+                    } else {
+                        grid.led_at(x - dx, y - dy).map_or(0, |j| new[j])
+                    };
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Blends the frames of an action with the frame shown before it, for the
+/// duration of the transition in to the action
+#[derive(Debug, Clone)]
+pub struct TransitionPlayer {
+    transition: Transition,
+    from: AnimeDataBuffer,
+    start: Instant,
+}
+
+impl TransitionPlayer {
+    pub fn new(transition: Transition, from: AnimeDataBuffer, start: Instant) -> Self {
+        Self {
+            transition,
+            from,
+            start,
+        }
+    }
+
+    /// `to` blended with the old frame at `now`, or `None` once the
+    /// transition is over
+    pub fn frame(&self, to: &AnimeDataBuffer, now: Instant) -> Option<AnimeDataBuffer> {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= self.transition.duration {
+            return None;
+        }
+        let progress = elapsed.as_secs_f32() / self.transition.duration.as_secs_f32();
+        Some(self.transition.blend(&self.from, to, progress))
+    }
+
+    /// How long until the transition is over
+    pub fn remaining(&self, now: Instant) -> Duration {
+        (self.start + self.transition.duration).saturating_duration_since(now)
+    }
+}
+
+/// How far along the display a point is in `direction`, from `0.0` at
+/// the edge a wipe starts at to `1.0` at the far edge
+fn along(grid: &LedGrid, direction: TransitionDirection, x: f32, y: f32) -> f32 {
+    let (x0, y0, x1, y1) = grid.bounds;
+    let (along, span) = match direction {
+        TransitionDirection::Left => (x1 - x, x1 - x0),
+        TransitionDirection::Right => (x - x0, x1 - x0),
+        TransitionDirection::Up => (y1 - y, y1 - y0),
+        TransitionDirection::Down => (y - y0, y1 - y0),
+    };
+    along / span
+}
+
+/// A fixed scattered value in `0.0..1.0` for each LED, the order a dissolve
+/// changes them in
+fn scatter(index: usize) -> f32 {
+    (splitmix64(index as u64) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::AnimeType;
+
+    fn buffers(anime_type: AnimeType) -> (AnimeDataBuffer, AnimeDataBuffer, LedGrid) {
+        let from = AnimeDataBuffer::new(anime_type);
+        let mut to = AnimeDataBuffer::new(anime_type);
+        let grid = LedGrid::new(anime_type);
+        for led in &grid.leds {
+            to.data_mut()[led.index] = 200;
+        }
+        (from, to, grid)
+    }
+
+    fn lit(buffer: &AnimeDataBuffer) -> usize {
+        buffer.data().iter().filter(|&&b| b != 0).count()
+    }
+
+    #[test]
+    fn ends_and_crossfade() {
+        let (from, to, grid) = buffers(AnimeType::GA402);
+        for style in [
+            TransitionStyle::Crossfade,
+            TransitionStyle::Dissolve,
+            TransitionStyle::Wipe(TransitionDirection::Up),
+            TransitionStyle::Slide(TransitionDirection::Right),
+        ] {
+            let transition = Transition {
+                style,
+                duration: Duration::from_secs(1),
+            };
+            assert_eq!(transition.blend(&from, &to, 0.0).data(), from.data());
+            assert_eq!(transition.blend(&from, &to, 1.0).data(), to.data());
+            // Partway, some LEDs have changed, or all part of the way
+            let middle = transition.blend(&from, &to, 0.5);
+            assert_ne!(middle.data(), from.data(), "{style:?}");
+            assert_ne!(middle.data(), to.data(), "{style:?}");
+        }
+
+        let crossfade = Transition {
+            style: TransitionStyle::Crossfade,
+            duration: Duration::from_secs(1),
+        };
+        let middle = crossfade.blend(&from, &to, 0.25);
+        assert!(grid.leds.iter().all(|led| middle.data()[led.index] == 50));
+    }
+
+    #[test]
+    fn wipe_and_slide_follow_the_leds() {
+        for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+            let (from, to, grid) = buffers(anime_type);
+            let led_count = grid.leds.len();
+
+            // A wipe left from the right edge lights the right side first
+            let wipe = Transition {
+                style: TransitionStyle::Wipe(TransitionDirection::Left),
+                duration: Duration::from_secs(1),
+            };
+            let quarter = wipe.blend(&from, &to, 0.25);
+            let (x0, _, x1, _) = grid.bounds;
+            let middle = (x0 + x1) / 2.0;
+            assert!(grid
+                .leds
+                .iter()
+                .all(|led| led.x > middle || quarter.data()[led.index] == 0));
+            assert!(lit(&quarter) > 0);
+            assert!(lit(&wipe.blend(&from, &to, 0.75)) > lit(&quarter));
+
+            // Sliding down, the old frame moves down whole and the new frame
+            // comes in above it
+            let slide = Transition {
+                style: TransitionStyle::Slide(TransitionDirection::Down),
+                duration: Duration::from_secs(1),
+            };
+            let quarter = slide.blend(&to, &from, 0.25);
+            let (_, y0, ..) = grid.bounds;
+            let edge = y0 + grid.span().1 / 4.0;
+            for led in &grid.leds {
+                if led.y > edge + grid.scale.1 {
+                    assert_ne!(quarter.data()[led.index], 0);
+                } else if led.y < edge - grid.scale.1 {
+                    assert_eq!(quarter.data()[led.index], 0);
+                }
+            }
+            assert!(lit(&quarter) < led_count);
+        }
+    }
+
+    #[test]
+    fn player_runs_for_the_duration() {
+        let (from, to, _) = buffers(AnimeType::GA402);
+        let start = Instant::now();
+        let player = TransitionPlayer::new(
+            Transition {
+                style: TransitionStyle::Dissolve,
+                duration: Duration::from_secs(2),
+            },
+            from,
+            start,
+        );
+        let early = player
+            .frame(&to, start + Duration::from_millis(200))
+            .unwrap();
+        let late = player
+            .frame(&to, start + Duration::from_millis(1800))
+            .unwrap();
+        assert!(lit(&early) < lit(&late));
+        assert_eq!(
+            player.remaining(start + Duration::from_secs(1)).as_secs(),
+            1
+        );
+        assert!(player.frame(&to, start + Duration::from_secs(2)).is_none());
+    }
+}
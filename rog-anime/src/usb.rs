@@ -8,6 +8,7 @@
 //!
 //! Step 1 need to applied only on fresh system boot.
 
+use std::fmt;
 use std::str::FromStr;
 
 use dmi_id::DMIID;
@@ -16,10 +17,11 @@ use typeshare::typeshare;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::{OwnedValue, Type, Value};
 
+use crate::data::{BLOCK_END, BLOCK_START};
 use crate::error::AnimeError;
-use crate::AnimeType;
+use crate::{AnimeType, USB_PREFIX1, USB_PREFIX2, USB_PREFIX3};
 
-const PACKET_SIZE: usize = 640;
+pub(crate) const PACKET_SIZE: usize = 640;
 const DEV_PAGE: u8 = 0x5e;
 pub const VENDOR_ID: u16 = 0x0b05;
 pub const PROD_ID: u16 = 0x193b;
@@ -249,17 +251,11 @@ impl From<AnimShutdown> for i32 {
 #[inline]
 pub fn get_maybe_anime_type() -> Result<AnimeType, AnimeError> {
     let dmi = DMIID::new().map_err(|_| AnimeError::NoDevice)?; // TODO: better error
-    let board_name = dmi.board_name;
-
-    if board_name.contains("GA401I") || board_name.contains("GA401Q") {
-        return Ok(AnimeType::GA401);
-    } else if board_name.contains("GA402R") || board_name.contains("GA402X") {
-        return Ok(AnimeType::GA402);
-    } else if board_name.contains("GU604V") {
-        return Ok(AnimeType::GU604);
+    let anime_type = AnimeType::from_board_name(&dmi.board_name);
+    if anime_type == AnimeType::Unsupported {
+        log::warn!("AniMe Matrix device found but could be a slash");
     }
-    log::warn!("AniMe Matrix device found but could be a slash");
-    Ok(AnimeType::Unsupported)
+    Ok(anime_type)
 }
 
 /// Get the two device initialization packets. These are required for device
@@ -343,3 +339,145 @@ pub const fn pkt_set_builtin_animations(
         | ((boot as u8) << 0x03);
     pkt
 }
+
+/// A packet written to the `AniMe`, parsed back from the bytes made by the
+/// `pkt_*` functions and `AnimePacketType`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnimePacket {
+    /// Either of the packets from `pkts_for_init()`
+    Init,
+    /// Show the panes written since the last flush
+    Flush,
+    Brightness(Brightness),
+    EnableDisplay(bool),
+    EnablePowersaveAnim(bool),
+    BuiltinAnimations {
+        boot: AnimBooting,
+        awake: AnimAwake,
+        sleep: AnimSleeping,
+        shutdown: AnimShutdown,
+    },
+    /// The data of one pane of a frame, where `0` is the first pane
+    Pane(usize, Vec<u8>),
+}
+
+impl AnimePacket {
+    /// Parse a packet. Returns `None` if it is not one that is known.
+    pub fn parse(pkt: &[u8]) -> Option<Self> {
+        if pkt.first() != Some(&DEV_PAGE) {
+            return None;
+        }
+        for (pane, prefix) in [USB_PREFIX1, USB_PREFIX2, USB_PREFIX3].iter().enumerate() {
+            if pkt.starts_with(prefix) {
+                let end = pkt.len().min(BLOCK_END);
+                return Some(Self::Pane(pane, pkt[BLOCK_START..end].to_vec()));
+            }
+        }
+
+        let byte = |i: usize| pkt.get(i).copied().unwrap_or_default();
+        Some(match (byte(1), byte(2)) {
+            _ if pkt[1..].starts_with(b"ASUS Tech.Inc.") => Self::Init,
+            (0xc2, _) => Self::Init,
+            (0xc0, 0x03) => Self::Flush,
+            (0xc0, 0x04) => Self::Brightness(byte(3).into()),
+            (0xc3, 0x01) => Self::EnableDisplay(byte(3) == 0x00),
+            (0xc4, 0x01) => Self::EnablePowersaveAnim(byte(3) == 0x00),
+            (0xc5, bits) => Self::BuiltinAnimations {
+                boot: i32::from((bits >> 0x03) & 1).into(),
+                awake: i32::from(bits & 1).into(),
+                sleep: i32::from((bits >> 0x01) & 1).into(),
+                shutdown: i32::from((bits >> 0x02) & 1).into(),
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for AnimePacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Init => write!(f, "AniMe init"),
+            Self::Flush => write!(f, "AniMe flush, show the written panes"),
+            Self::Brightness(brightness) => write!(f, "AniMe set brightness {brightness:?}"),
+            Self::EnableDisplay(enabled) => write!(f, "AniMe set display enabled {enabled}"),
+            Self::EnablePowersaveAnim(enabled) => {
+                write!(f, "AniMe set builtin animations enabled {enabled}")
+            }
+            Self::BuiltinAnimations {
+                boot,
+                awake,
+                sleep,
+                shutdown,
+            } => write!(
+                f,
+                "AniMe set builtin animations boot {boot:?}, awake {awake:?}, sleep {sleep:?}, \
+                 shutdown {shutdown:?}"
+            ),
+            Self::Pane(pane, data) => write!(
+                f,
+                "AniMe pane {} data, {} of {} LEDs lit",
+                pane + 1,
+                data.iter().filter(|b| **b != 0).count(),
+                data.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::usb::{
+        pkt_flush, pkt_set_brightness, pkt_set_builtin_animations, pkt_set_enable_display,
+        pkt_set_enable_powersave_anim, pkts_for_init, AnimAwake, AnimBooting, AnimShutdown,
+        AnimSleeping, AnimePacket, Brightness,
+    };
+    use crate::{AnimeDataBuffer, AnimePacketType, AnimeType};
+
+    #[test]
+    fn parse_packets() {
+        for pkt in pkts_for_init() {
+            assert_eq!(AnimePacket::parse(&pkt), Some(AnimePacket::Init));
+        }
+        assert_eq!(AnimePacket::parse(&pkt_flush()), Some(AnimePacket::Flush));
+        assert_eq!(
+            AnimePacket::parse(&pkt_set_brightness(Brightness::High)),
+            Some(AnimePacket::Brightness(Brightness::High))
+        );
+        assert_eq!(
+            AnimePacket::parse(&pkt_set_enable_display(false)),
+            Some(AnimePacket::EnableDisplay(false))
+        );
+        assert_eq!(
+            AnimePacket::parse(&pkt_set_enable_powersave_anim(true)),
+            Some(AnimePacket::EnablePowersaveAnim(true))
+        );
+        assert_eq!(
+            AnimePacket::parse(&pkt_set_builtin_animations(
+                AnimBooting::StaticEmergence,
+                AnimAwake::BinaryBannerScroll,
+                AnimSleeping::Starfield,
+                AnimShutdown::GlitchOut,
+            )),
+            Some(AnimePacket::BuiltinAnimations {
+                boot: AnimBooting::StaticEmergence,
+                awake: AnimAwake::BinaryBannerScroll,
+                sleep: AnimSleeping::Starfield,
+                shutdown: AnimShutdown::GlitchOut,
+            })
+        );
+        assert_eq!(AnimePacket::parse(&[0x5d, 0xb3]), None);
+
+        let mut buffer = AnimeDataBuffer::new(AnimeType::GA402);
+        buffer.data_mut()[700] = 42;
+        let packets = AnimePacketType::try_from(buffer.clone()).unwrap();
+        let mut data = Vec::new();
+        for (i, pkt) in packets.iter().enumerate() {
+            let Some(AnimePacket::Pane(pane, pane_data)) = AnimePacket::parse(pkt) else {
+                panic!("not a pane");
+            };
+            assert_eq!(pane, i);
+            data.extend(pane_data);
+        }
+        assert_eq!(data, buffer.data());
+    }
+}
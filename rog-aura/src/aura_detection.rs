@@ -1,16 +1,19 @@
-use std::env;
+use std::path::Path;
+use std::{env, fmt};
 
 use dmi_id::DMIID;
 use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::keyboard::AdvancedAuraType;
-use crate::{AuraModeNum, AuraZone, PowerZones};
+use crate::error::Error;
+use crate::keyboard::{AdvancedAuraType, LedCode};
+use crate::{AuraModeNum, AuraZone, ColourCorrection, PowerZones};
 
 pub const ASUS_LED_MODE_CONF: &str = "/usr/share/asusd/aura_support.ron";
 pub const ASUS_LED_MODE_USER_CONF: &str = "/etc/asusd/asusd_user_ledmodes.ron";
+pub const ASUS_LED_MODE_DROPIN_DIR: &str = "/etc/asusd/aura_support.d";
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct LedSupportData {
     /// This can be many different types of name:
     /// - `/sys/class/dmi/id/board_name` (must use for laptops)
@@ -55,6 +58,10 @@ pub struct LedSupportData {
     pub advanced_type: AdvancedAuraType,
     /// If empty will default to `Keyboard` power zone
     pub power_zones: Vec<PowerZones>,
+    /// Gamma and white balance for the LEDs, applied to every colour written
+    /// to the device
+    #[serde(default, skip_serializing_if = "ColourCorrection::is_identity")]
+    pub colour_correction: ColourCorrection,
 }
 
 impl LedSupportData {
@@ -62,6 +69,13 @@ impl LedSupportData {
     /// matches against laptops first, then will proceed with matching the
     /// `device_name` if there are no DMI matches.
     pub fn get_data(product_id: &str) -> Self {
+        Self::get_data_with_sources(product_id).0
+    }
+
+    /// The same as `get_data()`, but also returns the files that produced the
+    /// data in the order they were applied. The list is empty if a default was
+    /// used.
+    pub fn get_data_with_sources(product_id: &str) -> (Self, Vec<String>) {
         let mut dmi = DMIID::new().unwrap_or_default();
         if let Ok(board_name) = env::var("BOARD_NAME") {
             dmi.board_name = board_name;
@@ -69,17 +83,213 @@ impl LedSupportData {
         // let prod_family = dmi.product_family().expect("Could not get
         // product_family");
 
-        if let Some(data) = LedSupportFile::load_from_supoprt_db() {
-            return data.match_device(&dmi.board_name, product_id);
+        if let Some(db) = LedSupportDb::load() {
+            let (data, sources) = db.match_device(&dmi.board_name, product_id);
+            if !sources.is_empty() {
+                info!(
+                    "LED support data for {} from {}",
+                    data.device_name,
+                    sources.join(", ")
+                );
+            }
+            return (data, sources);
         }
         info!("Using generic LED control for keyboard brightness only. No aura_support file found");
         let mut data = LedSupportData::default();
         data.power_zones.push(PowerZones::Keyboard);
-        data
+        (data, Vec::new())
+    }
+}
+
+/// Changes to the fields of an existing `LedSupportData` entry, so that a
+/// whole entry doesn't need to be copied to change one part of it
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LedSupportPatch {
+    /// The `device_name` of the entry to change, this must match exactly
+    pub device_name: String,
+    /// If set, only the entry with this `product_id` is changed
+    pub product_id: Option<String>,
+    pub layout_name: Option<String>,
+    pub add_basic_modes: Vec<AuraModeNum>,
+    pub remove_basic_modes: Vec<AuraModeNum>,
+    pub add_basic_zones: Vec<AuraZone>,
+    pub remove_basic_zones: Vec<AuraZone>,
+    pub advanced_type: Option<AdvancedAuraType>,
+    pub add_power_zones: Vec<PowerZones>,
+    pub remove_power_zones: Vec<PowerZones>,
+    pub colour_correction: Option<ColourCorrection>,
+}
+
+/// Add the items in `add` that are not already in `list` and then remove the
+/// items in `remove`
+fn patch_list<T: PartialEq + Clone>(list: &mut Vec<T>, add: &[T], remove: &[T]) {
+    for item in add {
+        if !list.contains(item) {
+            list.push(item.clone());
+        }
+    }
+    list.retain(|item| !remove.contains(item));
+}
+
+impl LedSupportPatch {
+    pub fn matches(&self, data: &LedSupportData) -> bool {
+        self.device_name == data.device_name
+            && match self.product_id.as_ref() {
+                Some(id) => *id == data.product_id,
+                None => true,
+            }
+    }
+
+    pub fn apply(&self, data: &mut LedSupportData) {
+        if let Some(layout_name) = self.layout_name.as_ref() {
+            data.layout_name.clone_from(layout_name);
+        }
+        patch_list(
+            &mut data.basic_modes,
+            &self.add_basic_modes,
+            &self.remove_basic_modes,
+        );
+        patch_list(
+            &mut data.basic_zones,
+            &self.add_basic_zones,
+            &self.remove_basic_zones,
+        );
+        if let Some(advanced_type) = self.advanced_type.as_ref() {
+            data.advanced_type = advanced_type.clone();
+        }
+        patch_list(
+            &mut data.power_zones,
+            &self.add_power_zones,
+            &self.remove_power_zones,
+        );
+        if let Some(correction) = self.colour_correction {
+            data.colour_correction = correction;
+        }
+    }
+}
+
+/// A file in `/etc/asusd/aura_support.d/`. `entries` are added to the
+/// support DB, replacing any with the same `device_name` and `product_id`,
+/// then `patches` are applied.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LedSupportDropIn {
+    pub entries: Vec<LedSupportData>,
+    pub patches: Vec<LedSupportPatch>,
+}
+
+/// A problem found in a `LedSupportFile` by `LedSupportFile::check()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupportIssue {
+    /// More than one entry has the same `device_name` and `product_id`, only
+    /// the last is ever matched
+    Duplicate {
+        device_name: String,
+        product_id: String,
+    },
+    /// The entry can never be matched because every board name that contains
+    /// its `device_name` also contains the `device_name` of an entry that is
+    /// checked first
+    Shadowed {
+        device_name: String,
+        product_id: String,
+        by: String,
+    },
+    /// There is no `<layout_name>_US.ron` in the layouts dir
+    MissingLayout {
+        device_name: String,
+        layout_name: String,
+    },
+    /// `basic_zones` and `advanced_type` do not agree
+    ZoneMismatch { device_name: String, reason: String },
+}
+
+impl fmt::Display for SupportIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |device_name: &str, product_id: &str| {
+            if product_id.is_empty() {
+                format!("\"{device_name}\"")
+            } else {
+                format!("\"{device_name}\" ({product_id})")
+            }
+        };
+        match self {
+            SupportIssue::Duplicate {
+                device_name,
+                product_id,
+            } => write!(f, "{} is duplicated", name(device_name, product_id)),
+            SupportIssue::Shadowed {
+                device_name,
+                product_id,
+                by,
+            } => write!(
+                f,
+                "{} is never matched, \"{by}\" is checked first",
+                name(device_name, product_id)
+            ),
+            SupportIssue::MissingLayout {
+                device_name,
+                layout_name,
+            } => write!(
+                f,
+                "\"{device_name}\" uses layout \"{layout_name}\" which has no {layout_name}_US.ron"
+            ),
+            SupportIssue::ZoneMismatch {
+                device_name,
+                reason,
+            } => write!(f, "\"{device_name}\" {reason}"),
+        }
+    }
+}
+
+impl LedSupportData {
+    /// Find reasons why `basic_zones` and `advanced_type` don't describe the
+    /// same hardware
+    fn zone_mismatches(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.basic_zones.contains(&AuraZone::None) {
+            reasons.push("has basic zone None, leave basic_zones empty instead".to_owned());
+        }
+        let kb_zones = [
+            AuraZone::Key1,
+            AuraZone::Key2,
+            AuraZone::Key3,
+            AuraZone::Key4,
+        ]
+        .iter()
+        .filter(|z| self.basic_zones.contains(z))
+        .count();
+        if kb_zones != 0 && kb_zones != 4 {
+            reasons.push(format!("has {kb_zones} of the 4 keyboard basic zones"));
+        }
+
+        if let AdvancedAuraType::Zoned(codes) = &self.advanced_type {
+            if codes.is_empty() {
+                reasons.push("has a Zoned advanced_type with no zones".to_owned());
+            }
+            for code in codes {
+                if !(*code == LedCode::SingleZone
+                    || code.is_keyboard_zone()
+                    || code.is_lightbar_zone())
+                {
+                    reasons.push(format!("has {code:?} which is not a zone in advanced_type"));
+                }
+            }
+            let single = codes.contains(&LedCode::SingleZone);
+            let multi = codes.iter().any(LedCode::is_keyboard_zone);
+            if single && multi {
+                reasons.push("mixes SingleZone with ZonedKb* in advanced_type".to_owned());
+            }
+            if single && kb_zones != 0 {
+                reasons.push("has keyboard basic_zones but a SingleZone advanced_type".to_owned());
+            }
+        }
+        reasons
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct LedSupportFile(Vec<LedSupportData>);
 
 impl LedSupportFile {
@@ -89,22 +299,29 @@ impl LedSupportFile {
 
     /// The list is stored in ordered format, so the iterator must be reversed
     /// to ensure we match to *whole names* first before doing a glob match
-    fn match_device(&self, device_name: &str, product_id: &str) -> LedSupportData {
-        for config in self.0.iter().rev() {
+    fn find_device(&self, device_name: &str, product_id: &str) -> Option<usize> {
+        for (i, config) in self.0.iter().enumerate().rev() {
             if device_name.contains(&config.device_name) {
                 info!("Matched to {}", config.device_name);
                 if !config.product_id.is_empty() {
                     info!("Checking product ID");
                     if config.product_id == product_id {
                         info!("Matched to {}", config.product_id);
-                        return config.clone();
+                        return Some(i);
                     } else {
                         continue;
                     }
                 }
-                return config.clone();
+                return Some(i);
             }
         }
+        None
+    }
+
+    fn match_device(&self, device_name: &str, product_id: &str) -> LedSupportData {
+        if let Some(i) = self.find_device(device_name, product_id) {
+            return self.0[i].clone();
+        }
         warn!(
             "the aura_support.ron file has no entry for this model: {device_name}, {product_id}. \
              Using a default"
@@ -117,55 +334,212 @@ impl LedSupportFile {
             basic_zones: vec![],
             advanced_type: AdvancedAuraType::None,
             power_zones: vec![PowerZones::Keyboard],
+            colour_correction: ColourCorrection::default(),
         }
     }
 
-    /// Load `LedSupportFile` from the `aura_support.ron` file at
-    /// `/usr/share/asusd/aura_support.ron` and append with data from
-    /// `/etc/asusd/asusd_user_ledmodes.ron` if that file is available.
+    /// Load a single support file, sorted in the order `match_device()`
+    /// expects
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let buf = std::fs::read_to_string(path)
+            .map_err(|e| Error::IoPath(path.to_string_lossy().to_string(), e))?;
+        let mut data: LedSupportFile = ron::from_str(&buf)?;
+        data.0.sort_by(|a, b| a.device_name.cmp(&b.device_name));
+        Ok(data)
+    }
+
+    /// Check the entries for mistakes that `match_device()` can't report.
+    /// Entries are checked in their current order, which is the order of the
+    /// loaded and sorted support DB. Layouts are looked for in `layouts_dir`.
+    pub fn check(&self, layouts_dir: &Path) -> Vec<SupportIssue> {
+        let mut issues = Vec::new();
+        for (i, config) in self.0.iter().enumerate() {
+            let later = &self.0[i + 1..];
+            // Entries later in the list are matched first
+            if later.iter().any(|other| {
+                other.device_name == config.device_name && other.product_id == config.product_id
+            }) {
+                issues.push(SupportIssue::Duplicate {
+                    device_name: config.device_name.clone(),
+                    product_id: config.product_id.clone(),
+                });
+            } else if let Some(other) = later.iter().rev().find(|other| {
+                config.device_name.contains(&other.device_name)
+                    && (other.product_id.is_empty() || other.product_id == config.product_id)
+            }) {
+                issues.push(SupportIssue::Shadowed {
+                    device_name: config.device_name.clone(),
+                    product_id: config.product_id.clone(),
+                    by: other.device_name.clone(),
+                });
+            }
+
+            if !config.layout_name.is_empty()
+                && !layouts_dir
+                    .join(format!("{}_US.ron", config.layout_name))
+                    .exists()
+            {
+                issues.push(SupportIssue::MissingLayout {
+                    device_name: config.device_name.clone(),
+                    layout_name: config.layout_name.clone(),
+                });
+            }
+
+            for reason in config.zone_mismatches() {
+                issues.push(SupportIssue::ZoneMismatch {
+                    device_name: config.device_name.clone(),
+                    reason,
+                });
+            }
+        }
+        issues
+    }
+}
+
+/// The LED support DB built from the default file, the user file, and the
+/// drop-in dir, with the files that produced each entry
+#[derive(Debug, Default, Clone)]
+pub struct LedSupportDb {
+    data: LedSupportFile,
+    sources: Vec<Vec<String>>,
+}
+
+impl LedSupportDb {
+    pub fn data(&self) -> &LedSupportFile {
+        &self.data
+    }
+
+    /// Load from `/usr/share/asusd/aura_support.ron`, then
+    /// `/etc/asusd/asusd_user_ledmodes.ron`, then each `.ron` file in
+    /// `/etc/asusd/aura_support.d/` in name order.
     ///
-    /// Returns `None` if neither file exists or does not parse correctly.
-    pub fn load_from_supoprt_db() -> Option<Self> {
-        let mut loaded = false;
-        let mut data = LedSupportFile::default();
-        // Load user configs first so they are first to be checked
-        if let Ok(file) = std::fs::read_to_string(ASUS_LED_MODE_USER_CONF) {
+    /// Returns `None` if the default file does not exist.
+    pub fn load() -> Option<Self> {
+        Self::load_from(
+            Path::new(ASUS_LED_MODE_CONF),
+            Path::new(ASUS_LED_MODE_USER_CONF),
+            Path::new(ASUS_LED_MODE_DROPIN_DIR),
+        )
+    }
+
+    /// As `load()` but with the paths given
+    pub fn load_from(default: &Path, user: &Path, dropin_dir: &Path) -> Option<Self> {
+        let mut db = LedSupportDb::default();
+        // Load and append the default LED support data
+        if let Ok(file) = std::fs::read_to_string(default) {
             if file.is_empty() {
-                warn!("{} is empty", ASUS_LED_MODE_USER_CONF);
+                warn!("{} is empty", default.display());
             } else {
-                if let Ok(mut tmp) = ron::from_str::<LedSupportFile>(&file) {
-                    data.0.append(&mut tmp.0);
-                }
-                info!(
-                    "Loaded user-defined LED support data from {}",
-                    ASUS_LED_MODE_USER_CONF
-                );
+                let tmp: LedSupportFile = ron::from_str(&file)
+                    .map_err(|e| error!("{e}"))
+                    .unwrap_or_else(|_| panic!("Could not deserialise {}", default.display()));
+                db.add_entries(tmp.0, default);
+                info!("Loaded default LED support data from {}", default.display());
             }
         }
-        // Load and append the default LED support data
-        if let Ok(file) = std::fs::read_to_string(ASUS_LED_MODE_CONF) {
+        if db.data.0.is_empty() {
+            warn!("Does {} exist?", default.display());
+            return None;
+        }
+
+        // User entries replace the default entry for the same device
+        if let Ok(file) = std::fs::read_to_string(user) {
             if file.is_empty() {
-                warn!("{} is empty", ASUS_LED_MODE_CONF);
+                warn!("{} is empty", user.display());
             } else {
-                let mut tmp: LedSupportFile = ron::from_str(&file)
-                    .map_err(|e| error!("{e}"))
-                    .unwrap_or_else(|_| panic!("Could not deserialise {}", ASUS_LED_MODE_CONF));
-                data.0.append(&mut tmp.0);
-                loaded = true;
-                info!(
-                    "Loaded default LED support data from {}",
-                    ASUS_LED_MODE_CONF
-                );
+                match ron::from_str::<LedSupportFile>(&file) {
+                    Ok(tmp) => {
+                        db.add_entries(tmp.0, user);
+                        info!(
+                            "Loaded user-defined LED support data from {}",
+                            user.display()
+                        );
+                    }
+                    Err(e) => error!("{}: {e}", user.display()),
+                }
             }
         }
-        data.0.sort_by(|a, b| a.device_name.cmp(&b.device_name));
 
-        if loaded {
-            return Some(data);
+        let mut dropins: Vec<_> = std::fs::read_dir(dropin_dir)
+            .map(|dir| {
+                dir.filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "ron"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        dropins.sort();
+        for path in dropins {
+            let dropin = match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|f| ron::from_str::<LedSupportDropIn>(&f).map_err(|e| e.to_string()))
+            {
+                Ok(dropin) => dropin,
+                Err(e) => {
+                    error!("Skipping {}: {e}", path.display());
+                    continue;
+                }
+            };
+            db.add_entries(dropin.entries, &path);
+            for patch in &dropin.patches {
+                db.apply_patch(patch, &path);
+            }
+            info!("Loaded LED support drop-in {}", path.display());
         }
 
-        warn!("Does {} exist?", ASUS_LED_MODE_USER_CONF);
-        None
+        db.sort();
+        Some(db)
+    }
+
+    fn add_entries(&mut self, entries: Vec<LedSupportData>, source: &Path) {
+        for entry in entries {
+            let source = vec![source.display().to_string()];
+            if let Some(i) = self.data.0.iter().position(|e| {
+                e.device_name == entry.device_name && e.product_id == entry.product_id
+            }) {
+                self.data.0[i] = entry;
+                self.sources[i] = source;
+            } else {
+                self.data.0.push(entry);
+                self.sources.push(source);
+            }
+        }
+    }
+
+    fn apply_patch(&mut self, patch: &LedSupportPatch, source: &Path) {
+        let mut applied = false;
+        for (entry, sources) in self.data.0.iter_mut().zip(self.sources.iter_mut()) {
+            if patch.matches(entry) {
+                patch.apply(entry);
+                sources.push(source.display().to_string());
+                applied = true;
+            }
+        }
+        if !applied {
+            warn!(
+                "{}: no entry named {} to patch",
+                source.display(),
+                patch.device_name
+            );
+        }
+    }
+
+    fn sort(&mut self) {
+        let mut entries: Vec<_> = self.data.0.drain(..).zip(self.sources.drain(..)).collect();
+        entries.sort_by(|a, b| a.0.device_name.cmp(&b.0.device_name));
+        (self.data.0, self.sources) = entries.into_iter().unzip();
+    }
+
+    /// Find the entry for the device and the files that produced it. If there
+    /// is no entry a default is used with no sources.
+    pub fn match_device(
+        &self,
+        device_name: &str,
+        product_id: &str,
+    ) -> (LedSupportData, Vec<String>) {
+        if let Some(i) = self.data.find_device(device_name, product_id) {
+            return (self.data.0[i].clone(), self.sources[i].clone());
+        }
+        (self.data.match_device(device_name, product_id), Vec::new())
     }
 }
 
@@ -177,11 +551,11 @@ mod tests {
 
     use ron::ser::PrettyConfig;
 
-    use super::LedSupportData;
+    use super::{LedSupportData, LedSupportDb, SupportIssue};
     use crate::aura_detection::{LedSupportFile, PowerZones};
     use crate::keyboard::{AdvancedAuraType, LedCode};
     // use crate::zoned::Zone;
-    use crate::{AuraModeNum, AuraZone};
+    use crate::{AuraModeNum, AuraZone, ColourCorrection};
 
     #[test]
     fn check_data_parse() {
@@ -193,12 +567,125 @@ mod tests {
             basic_zones: vec![AuraZone::Key1, AuraZone::Logo, AuraZone::BarLeft],
             advanced_type: AdvancedAuraType::Zoned(vec![LedCode::LightbarRight]),
             power_zones: vec![PowerZones::Keyboard, PowerZones::RearGlow],
+            colour_correction: ColourCorrection::default(),
         };
 
         assert!(ron::to_string(&led).is_ok());
         // assert_eq!(json, String::new());
     }
 
+    #[test]
+    fn check_support_issues() {
+        let entry = |device_name: &str, product_id: &str| LedSupportData {
+            device_name: device_name.to_owned(),
+            product_id: product_id.to_owned(),
+            ..Default::default()
+        };
+        let mut zoned = entry("GA503", "");
+        zoned.basic_zones = vec![AuraZone::Key1, AuraZone::Key2];
+        zoned.advanced_type = AdvancedAuraType::Zoned(vec![LedCode::SingleZone, LedCode::Q]);
+        let mut layout = entry("GX502", "");
+        layout.layout_name = "does-not-exist".to_owned();
+
+        let data = LedSupportFile(vec![
+            entry("G513I", ""),
+            entry("G513I", ""),
+            entry("G513QY", "1866"),
+            entry("G513QY", "19b6"),
+            zoned,
+            layout,
+            entry("QY", "1866"),
+            entry("Z", "19b6"),
+        ]);
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/layouts");
+        let issues = data.check(&path);
+
+        assert!(issues.contains(&SupportIssue::Duplicate {
+            device_name: "G513I".to_owned(),
+            product_id: String::new(),
+        }));
+        assert!(issues.contains(&SupportIssue::Shadowed {
+            device_name: "G513QY".to_owned(),
+            product_id: "1866".to_owned(),
+            by: "QY".to_owned(),
+        }));
+        // The product ID differs so "QY" doesn't match first
+        assert!(!issues.iter().any(
+            |i| matches!(i, SupportIssue::Shadowed { product_id, .. } if product_id == "19b6")
+        ));
+        assert!(issues.contains(&SupportIssue::MissingLayout {
+            device_name: "GX502".to_owned(),
+            layout_name: "does-not-exist".to_owned(),
+        }));
+        let zone_issues = issues
+            .iter()
+            .filter(|i| matches!(i, SupportIssue::ZoneMismatch { .. }))
+            .count();
+        assert_eq!(zone_issues, 3);
+        assert_eq!(issues.len(), 6);
+    }
+
+    #[test]
+    fn check_data_file_issues() {
+        let mut data = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        data.push("data");
+        let file = LedSupportFile::from_file(&data.join("aura_support.ron")).unwrap();
+        let issues = file.check(&data.join("layouts"));
+        assert!(issues.is_empty(), "{issues:#?}");
+    }
+
+    #[test]
+    fn check_layered_db() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("rog-aura-layered-{}", std::process::id()));
+        let dropin_dir = dir.join("aura_support.d");
+        std::fs::create_dir_all(&dropin_dir).unwrap();
+
+        let default = dir.join("aura_support.ron");
+        std::fs::write(
+            &default,
+            r#"([(device_name: "GA401Q", basic_modes: [Static, Breathe], power_zones: [Keyboard]),
+                (device_name: "G513I", basic_modes: [Static], power_zones: [Keyboard])])"#,
+        )
+        .unwrap();
+        let user = dir.join("asusd_user_ledmodes.ron");
+        std::fs::write(
+            &user,
+            r#"([(device_name: "G513I", layout_name: "g513i", basic_modes: [Pulse], power_zones: [Keyboard])])"#,
+        )
+        .unwrap();
+        let patch = dropin_dir.join("10-comet.ron");
+        std::fs::write(
+            &patch,
+            r#"(patches: [(device_name: "GA401Q", add_basic_modes: [Comet], remove_basic_modes: [Breathe], layout_name: Some("ga401q"))])"#,
+        )
+        .unwrap();
+        std::fs::write(dropin_dir.join("20-ignored.txt"), "not ron").unwrap();
+
+        let db = LedSupportDb::load_from(&default, &user, &dropin_dir).unwrap();
+        let (ga401, sources) = db.match_device("GA401QM", "");
+        assert_eq!(
+            ga401.basic_modes,
+            vec![AuraModeNum::Static, AuraModeNum::Comet]
+        );
+        assert_eq!(ga401.layout_name, "ga401q");
+        assert_eq!(
+            sources,
+            vec![default.display().to_string(), patch.display().to_string()]
+        );
+
+        let (g513, sources) = db.match_device("G513IH", "");
+        assert_eq!(g513.basic_modes, vec![AuraModeNum::Pulse]);
+        assert_eq!(sources, vec![user.display().to_string()]);
+        assert_eq!(db.data().get().len(), 2);
+
+        let (_, sources) = db.match_device("GX502", "");
+        assert!(sources.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn check_data_file_parse() {
         let mut data = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
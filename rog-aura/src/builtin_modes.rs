@@ -97,14 +97,11 @@ impl Default for Colour {
 impl FromStr for Colour {
     type Err = Error;
 
+    /// Accepts `rrggbb`, `#rrggbb`, `#rgb`, or a CSS colour name
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() < 6 {
-            return Err(Error::ParseColour);
-        }
-        let r = u8::from_str_radix(&s[0..2], 16).or(Err(Error::ParseColour))?;
-        let g = u8::from_str_radix(&s[2..4], 16).or(Err(Error::ParseColour))?;
-        let b = u8::from_str_radix(&s[4..6], 16).or(Err(Error::ParseColour))?;
-        Ok(Colour { r, g, b })
+        Colour::from_hex(s)
+            .or_else(|| Colour::from_name(s))
+            .ok_or(Error::ParseColour)
     }
 }
 
@@ -571,6 +568,33 @@ impl From<&AuraEffect> for [u8; LED_MSG_LEN] {
     }
 }
 
+/// Parses packet data from `<[u8; LED_MSG_LEN]>::from(&AuraEffect)` back in
+/// to `AuraEffect`
+impl From<&[u8; LED_MSG_LEN]> for AuraEffect {
+    fn from(msg: &[u8; LED_MSG_LEN]) -> Self {
+        Self {
+            mode: msg[3].into(),
+            zone: i32::from(msg[2]).into(),
+            colour1: Colour {
+                r: msg[4],
+                g: msg[5],
+                b: msg[6],
+            },
+            colour2: Colour {
+                r: msg[10],
+                g: msg[11],
+                b: msg[12],
+            },
+            speed: match msg[7] {
+                0xe1 => Speed::Low,
+                0xf5 => Speed::High,
+                _ => Speed::Med,
+            },
+            direction: i32::from(msg[8]).into(),
+        }
+    }
+}
+
 impl From<&AuraEffect> for Vec<u8> {
     fn from(aura: &AuraEffect) -> Self {
         let mut msg = vec![0u8; LED_MSG_LEN];
@@ -618,6 +642,24 @@ mod tests {
         assert_eq!(ar, check);
     }
 
+    #[test]
+    fn parse_led_packet() {
+        let effect = AuraEffect {
+            mode: AuraModeNum::Breathe,
+            zone: AuraZone::Logo,
+            colour1: Colour {
+                r: 0xff,
+                g: 0x11,
+                b: 0xdd,
+            },
+            colour2: Colour { r: 1, g: 2, b: 3 },
+            speed: Speed::High,
+            direction: Direction::Down,
+        };
+        let ar = <[u8; LED_MSG_LEN]>::from(&effect);
+        assert_eq!(AuraEffect::from(&ar), effect);
+    }
+
     #[test]
     fn check_led_static_zone_packet() {
         let mut st = AuraEffect {
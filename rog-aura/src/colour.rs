@@ -0,0 +1,561 @@
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard::UsbPackets;
+use crate::{AuraEffect, Colour};
+
+/// The CSS named colours, <https://www.w3.org/TR/css-color-4/#named-colors>
+const CSS_COLOURS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+/// Red, green, and blue from hue in degrees and the chroma, offset by `m`
+fn rgb_from_hue(h: f32, chroma: f32, m: f32) -> Colour {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let to_u8 = |c: f32| ((c + m).clamp(0.0, 1.0) * 255.0).round() as u8;
+    Colour {
+        r: to_u8(r),
+        g: to_u8(g),
+        b: to_u8(b),
+    }
+}
+
+impl Colour {
+    pub const fn from_u32(rgb: u32) -> Self {
+        Self {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        }
+    }
+
+    /// Find a CSS named colour such as `orange` or `rebeccapurple`. Case
+    /// insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        CSS_COLOURS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, rgb)| Self::from_u32(*rgb))
+    }
+
+    /// Parse `rrggbb` or `rgb` hex, with or without a leading `#`
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        // `from_str_radix` also accepts a leading `+`
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        match hex.len() {
+            6 => u32::from_str_radix(hex, 16).ok().map(Self::from_u32),
+            3 => {
+                let short = u32::from_str_radix(hex, 16).ok()?;
+                let expand = |c: u32| ((c & 0xf) * 0x11) as u8;
+                Some(Self {
+                    r: expand(short >> 8),
+                    g: expand(short >> 4),
+                    b: expand(short),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Create from hue in degrees, and saturation and value in `0.0..=1.0`
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let chroma = v * s;
+        rgb_from_hue(h, chroma, v - chroma)
+    }
+
+    /// Create from hue in degrees, and saturation and lightness in `0.0..=1.0`
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let (s, l) = (s.clamp(0.0, 1.0), l.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        rgb_from_hue(h, chroma, l - chroma / 2.0)
+    }
+
+    /// Hue in degrees, and the largest and smallest channel
+    fn hue_max_min(self) -> (f32, f32, f32) {
+        let [r, g, b]: [f32; 3] = self.into();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        let h = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        (h, max, min)
+    }
+
+    /// Hue in degrees, and saturation and value in `0.0..=1.0`
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (h, max, min) = self.hue_max_min();
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (h, s, max)
+    }
+
+    /// Hue in degrees, and saturation and lightness in `0.0..=1.0`
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (h, max, min) = self.hue_max_min();
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        (h, s, l)
+    }
+
+    /// Blend linearly from `self` to `to`, `t` is clamped to `0.0..=1.0`
+    pub fn lerp(self, to: Colour, t: f32) -> Colour {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Colour {
+            r: lerp(self.r, to.r),
+            g: lerp(self.g, to.g),
+            b: lerp(self.b, to.b),
+        }
+    }
+
+    /// Blend from `self` to `to` around the colour wheel, which keeps
+    /// saturation where `lerp()` would pass through grey
+    pub fn lerp_hsv(self, to: Colour, t: f32) -> Colour {
+        let t = t.clamp(0.0, 1.0);
+        let (h0, s0, v0) = self.to_hsv();
+        let (h1, s1, v1) = to.to_hsv();
+        // Take the shortest way around
+        let mut dh = h1 - h0;
+        if dh > 180.0 {
+            dh -= 360.0;
+        } else if dh < -180.0 {
+            dh += 360.0;
+        }
+        Colour::from_hsv(h0 + dh * t, s0 + (s1 - s0) * t, v0 + (v1 - v0) * t)
+    }
+
+    /// Find the colour for `value` from a list of `(value, colour)` stops in
+    /// ascending order. Values between stops are blended with `lerp()`, and
+    /// values outside the stops use the nearest stop.
+    pub fn gradient(stops: &[(f32, Colour)], value: f32) -> Colour {
+        let Some(first) = stops.first() else {
+            return Colour { r: 0, g: 0, b: 0 };
+        };
+        if value <= first.0 {
+            return first.1;
+        }
+        for pair in stops.windows(2) {
+            let ((v0, c0), (v1, c1)) = (pair[0], pair[1]);
+            if value <= v1 {
+                let t = if v1 > v0 {
+                    (value - v0) / (v1 - v0)
+                } else {
+                    1.0
+                };
+                return c0.lerp(c1, t);
+            }
+        }
+        stops[stops.len() - 1].1
+    }
+
+    /// Multiply each channel by `factor`, which is clamped to `0.0..=1.0`
+    pub fn scale(self, factor: f32) -> Colour {
+        let factor = factor.clamp(0.0, 1.0);
+        let scale = |c: u8| (c as f32 * factor).round() as u8;
+        Colour {
+            r: scale(self.r),
+            g: scale(self.g),
+            b: scale(self.b),
+        }
+    }
+}
+
+/// Correction for LEDs that are far from sRGB, so that `ffffff` looks white.
+/// Each channel is raised to the power of `gamma`, then multiplied by its
+/// gain.
+///
+/// When loaded `gamma` must be above zero and the gains zero or more.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "UncheckedCorrection")]
+pub struct ColourCorrection {
+    pub gamma: f32,
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+impl Default for ColourCorrection {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        }
+    }
+}
+
+/// A `ColourCorrection` as read from a file, before it is checked
+#[derive(Deserialize)]
+#[serde(default)]
+struct UncheckedCorrection {
+    gamma: f32,
+    red: f32,
+    green: f32,
+    blue: f32,
+}
+
+impl Default for UncheckedCorrection {
+    fn default() -> Self {
+        let ColourCorrection {
+            gamma,
+            red,
+            green,
+            blue,
+        } = ColourCorrection::default();
+        Self {
+            gamma,
+            red,
+            green,
+            blue,
+        }
+    }
+}
+
+impl TryFrom<UncheckedCorrection> for ColourCorrection {
+    type Error = String;
+
+    fn try_from(c: UncheckedCorrection) -> Result<Self, Self::Error> {
+        if !(c.gamma.is_finite() && c.gamma > 0.0) {
+            return Err(format!(
+                "colour_correction gamma must be above 0, not {}",
+                c.gamma
+            ));
+        }
+        for (name, gain) in [("red", c.red), ("green", c.green), ("blue", c.blue)] {
+            if !(gain.is_finite() && gain >= 0.0) {
+                return Err(format!(
+                    "colour_correction {name} must be 0 or more, not {gain}"
+                ));
+            }
+        }
+        Ok(Self {
+            gamma: c.gamma,
+            red: c.red,
+            green: c.green,
+            blue: c.blue,
+        })
+    }
+}
+
+impl ColourCorrection {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, colour: Colour) -> Colour {
+        if self.is_identity() {
+            return colour;
+        }
+        let correct = |c: u8, gain: f32| {
+            let c = (c as f32 / 255.0).powf(self.gamma) * gain;
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Colour {
+            r: correct(colour.r, self.red),
+            g: correct(colour.g, self.green),
+            b: correct(colour.b, self.blue),
+        }
+    }
+
+    /// Correct both colours of a builtin mode
+    pub fn apply_effect(&self, effect: &AuraEffect) -> AuraEffect {
+        let mut effect = effect.clone();
+        effect.colour1 = self.apply(effect.colour1);
+        effect.colour2 = self.apply(effect.colour2);
+        effect
+    }
+
+    /// Correct every colour in per-key or zoned custom mode packets. Other
+    /// packets are left alone.
+    pub fn apply_packets(&self, packets: &mut UsbPackets) {
+        if self.is_identity() {
+            return;
+        }
+        for row in packets.iter_mut() {
+            let Some(range) = crate::keyboard::colour_positions(row) else {
+                continue;
+            };
+            for rgb in row[range].chunks_exact_mut(3) {
+                let c = self.apply(Colour {
+                    r: rgb[0],
+                    g: rgb[1],
+                    b: rgb[2],
+                });
+                rgb.copy_from_slice(&[c.r, c.g, c.b]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colours() {
+        assert_eq!(
+            Colour::from_hex("#ff8000"),
+            Some(Colour::from_u32(0xff8000))
+        );
+        assert_eq!(Colour::from_hex("f80"), Some(Colour::from_u32(0xff8800)));
+        assert_eq!(Colour::from_hex("ff80"), None);
+        assert_eq!(Colour::from_hex("+f8"), None);
+        assert_eq!(Colour::from_hex("#+ff800"), None);
+        assert_eq!(
+            Colour::from_name("RebeccaPurple"),
+            Some(Colour::from_u32(0x663399))
+        );
+        assert_eq!(
+            "orange".parse::<Colour>().unwrap(),
+            Colour::from_u32(0xffa500)
+        );
+        assert_eq!(
+            "#00ff00".parse::<Colour>().unwrap(),
+            Colour::from_u32(0x00ff00)
+        );
+        assert!("notacolour".parse::<Colour>().is_err());
+    }
+
+    #[test]
+    fn colour_spaces() {
+        let orange = Colour::from_u32(0xff8000);
+        let (h, s, v) = orange.to_hsv();
+        assert!((h - 30.0).abs() < 0.5 && s == 1.0 && v == 1.0);
+        assert_eq!(Colour::from_hsv(h, s, v), orange);
+        let (h, s, l) = orange.to_hsl();
+        assert!((l - 0.5).abs() < 0.01);
+        assert_eq!(Colour::from_hsl(h, s, l), orange);
+        assert_eq!(
+            Colour::from_hsv(240.0, 1.0, 1.0),
+            Colour::from_u32(0x0000ff)
+        );
+        assert_eq!(Colour::from_hsl(0.0, 0.0, 1.0), Colour::from_u32(0xffffff));
+    }
+
+    #[test]
+    fn blending() {
+        let red = Colour::from_u32(0xff0000);
+        let blue = Colour::from_u32(0x0000ff);
+        assert_eq!(red.lerp(blue, 0.5), Colour::from_u32(0x800080));
+        // Around the wheel through magenta at full saturation
+        assert_eq!(red.lerp_hsv(blue, 0.5), Colour::from_u32(0xff00ff));
+
+        let stops = [(0.0, red), (10.0, blue)];
+        assert_eq!(Colour::gradient(&stops, -5.0), red);
+        assert_eq!(Colour::gradient(&stops, 5.0), Colour::from_u32(0x800080));
+        assert_eq!(Colour::gradient(&stops, 50.0), blue);
+        assert_eq!(
+            Colour::from_u32(0x804020).scale(0.5),
+            Colour::from_u32(0x402010)
+        );
+    }
+
+    #[test]
+    fn correction() {
+        let correction = ColourCorrection {
+            gamma: 2.0,
+            red: 1.0,
+            green: 0.5,
+            blue: 1.0,
+        };
+        let c = correction.apply(Colour::from_u32(0xffff80));
+        assert_eq!(c, Colour::from_u32(0xff8040));
+        let white = Colour::from_u32(0xffffff);
+        assert_eq!(ColourCorrection::default().apply(white), white);
+
+        let mut packets = crate::keyboard::LedUsbPackets::new_per_key();
+        packets.fill(255, 255, 255);
+        let mut packets: UsbPackets = packets.into();
+        let header = packets[0][..9].to_vec();
+        correction.apply_packets(&mut packets);
+        assert_eq!(packets[0][..9], header);
+        assert_eq!(packets[0][9..12], [255, 128, 255]);
+    }
+
+    #[test]
+    fn correction_is_checked_on_load() {
+        let parsed: ColourCorrection = ron::from_str("(green: 0.5)").unwrap();
+        assert_eq!(
+            parsed,
+            ColourCorrection {
+                green: 0.5,
+                ..Default::default()
+            }
+        );
+        assert!(ron::from_str::<ColourCorrection>("(gamma: 0.0)").is_err());
+        assert!(ron::from_str::<ColourCorrection>("(gamma: NaN)").is_err());
+        assert!(ron::from_str::<ColourCorrection>("(red: -1.0)").is_err());
+        assert!(ron::from_str::<ColourCorrection>("(blue: inf)").is_err());
+    }
+}
@@ -107,6 +107,12 @@ impl AdvancedEffects {
     }
 
     pub fn create_packets(&self) -> UsbPackets {
+        self.create_packets_with_overlay(&[])
+    }
+
+    /// Create packets from the current effect states, then set each LED in
+    /// `overlay` on top of them. Effects on LEDs not in the overlay are kept.
+    pub fn create_packets_with_overlay(&self, overlay: &[(LedCode, Colour)]) -> UsbPackets {
         let mut usb_packets = if self.zoned {
             // TODO: figure out if that single byte difference for multizone actually
             // matters
@@ -119,6 +125,42 @@ impl AdvancedEffects {
             let c = effect.colour();
             usb_packets.set(effect.led(), c.r, c.g, c.b);
         }
+        for (led, c) in overlay {
+            usb_packets.set(*led, c.r, c.g, c.b);
+        }
+        usb_packets.into()
+    }
+
+    /// Create packets with every LED in the layout set to a single colour.
+    /// Useful as a temporary layer over the running effects.
+    pub fn create_fill_packets(&self, layout: &KeyLayout, colour: Colour) -> UsbPackets {
+        self.create_fill_packets_with_overlay(layout, colour, &[])
+    }
+
+    /// Create packets with every LED in the layout set to a single colour,
+    /// then each overlay colour set on its LED
+    pub fn create_fill_packets_with_overlay(
+        &self,
+        layout: &KeyLayout,
+        colour: Colour,
+        overlay: &[(LedCode, Colour)],
+    ) -> UsbPackets {
+        let mut usb_packets = if self.zoned {
+            LedUsbPackets::new_zoned(true)
+        } else {
+            LedUsbPackets::new_per_key()
+        };
+
+        for row in layout.rows() {
+            for (led, _) in row.row() {
+                if !led.is_placeholder() {
+                    usb_packets.set(*led, colour.r, colour.g, colour.b);
+                }
+            }
+        }
+        for (led, c) in overlay {
+            usb_packets.set(*led, c.r, c.g, c.b);
+        }
         usb_packets.into()
     }
 }
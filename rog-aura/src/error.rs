@@ -9,6 +9,7 @@ pub enum Error {
     IoPath(String, std::io::Error),
     Ron(ron::Error),
     RonParse(ron::error::SpannedError),
+    Json(serde_json::Error),
 }
 
 impl fmt::Display for Error {
@@ -22,6 +23,7 @@ impl fmt::Display for Error {
             Error::IoPath(path, io) => write!(f, "IO Error: {path}, {io}"),
             Error::Ron(e) => write!(f, "RON Parse Error: {e}"),
             Error::RonParse(e) => write!(f, "RON Parse Error: {e}"),
+            Error::Json(e) => write!(f, "JSON Parse Error: {e}"),
         }
     }
 }
@@ -39,3 +41,9 @@ impl From<ron::error::SpannedError> for Error {
         Self::RonParse(e)
     }
 }
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
@@ -1,9 +1,13 @@
+use std::ops::Range;
+
 use log::warn;
 use serde::{Deserialize, Serialize};
 use typeshare::typeshare;
 #[cfg(feature = "dbus")]
 use zbus::zvariant::Type;
 
+use crate::Colour;
+
 // TODO: GZ301Z
 // 5dbcd0010300000a00ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000ff0000
 
@@ -136,6 +140,9 @@ pub enum LedCode {
     MediaStop,
     MediaNext,
     MediaPrev,
+    /// The extra ISO key right of a short left shift, `<>|` on many layouts.
+    /// The ISO key left of a tall return uses `BackSlash`.
+    IsoBackslash,
     LidLogo,
     LidLeft,
     LidRight,
@@ -222,6 +229,21 @@ impl Default for LedUsbPackets {
     }
 }
 
+/// The range of RGB bytes in a per-key or zoned custom mode packet, or `None`
+/// if the packet is something else such as the init message
+pub fn colour_positions(row: &[u8]) -> Option<Range<usize>> {
+    if row.len() < 64 || row[0] != 0x5d || row[1] != 0xbc || row[3] != 0x01 {
+        return None;
+    }
+    // Positions end after the lightbar for zoned, and after column 54 for
+    // per-key
+    if row[2] == 0x01 {
+        Some(9..45)
+    } else {
+        Some(9..57)
+    }
+}
+
 impl LedUsbPackets {
     /// Set up a series of per-key packets. This includes setting all the
     /// required starting bytes per packet, but does not set any colours.
@@ -301,12 +323,51 @@ impl LedUsbPackets {
         }
     }
 
+    /// The RGB colour of an `LedCode`, or `None` if it has no location in
+    /// these packets
+    pub fn colour(&self, key: LedCode) -> Option<Colour> {
+        let (row, col) = self.address_for_led_code(key)?;
+        let rgb = &self.usb_packets[row][col..=col + 2];
+        Some(Colour {
+            r: rgb[0],
+            g: rgb[1],
+            b: rgb[2],
+        })
+    }
+
+    /// Set every colour position in the packets, including positions that
+    /// have no `LedCode`
+    pub fn fill(&mut self, r: u8, g: u8, b: u8) {
+        for row in &mut self.usb_packets {
+            let Some(range) = colour_positions(row) else {
+                continue;
+            };
+            for rgb in row[range].chunks_exact_mut(3) {
+                rgb.copy_from_slice(&[r, g, b]);
+            }
+        }
+    }
+
+    /// Check if the `LedCode` has a location in these packets. An `LedCode`
+    /// without one is silently ignored by `set()`.
+    #[inline]
+    pub fn has_address(&self, led_code: LedCode) -> bool {
+        self.address_for_led_code(led_code).is_some()
+    }
+
     /// Indexes in to `UsbPackets` at the correct row and column
     /// to set a series of three bytes to the chosen R,G,B values
+    fn rgb_for_led_code(&mut self, led_code: LedCode) -> Option<&mut [u8]> {
+        let (row, col) = self.address_for_led_code(led_code)?;
+        Some(&mut self.usb_packets[row][col..=col + 2])
+    }
+
+    /// The row and column of the first byte of the RGB values for the
+    /// `LedCode`
     ///
     /// Indexing is different for `zoned` and assumes that only one packet is
     /// generated for all the zones
-    fn rgb_for_led_code(&mut self, led_code: LedCode) -> Option<&mut [u8]> {
+    fn address_for_led_code(&self, led_code: LedCode) -> Option<(usize, usize)> {
         let zoned = self.zoned;
         // Tuples are indexes in to array
         #[allow(clippy::match_same_arms)]
@@ -389,6 +450,8 @@ impl LedUsbPackets {
             LedCode::LShift3_1 => (6, 36),
             LedCode::LShift3_2 => (6, 36),
             LedCode::LShift3_3 => (6, 36),
+            // The gap between left shift and Z in the key matrix
+            LedCode::IsoBackslash => (6, 39),
             LedCode::Z => (6, 42),
             LedCode::X => (6, 45),
             LedCode::C => (6, 48),
@@ -468,7 +531,12 @@ impl LedUsbPackets {
             return None;
         }
 
-        Some(&mut self.usb_packets[row][col..=col + 2])
+        // Not all LEDs have a row in every packet set, such as the lightbar
+        // with per-key packets
+        if self.usb_packets.get(row)?.len() < col + 3 {
+            return None;
+        }
+        Some((row, col))
     }
 
     #[inline]
@@ -622,6 +690,7 @@ impl From<&LedCode> for &str {
             LedCode::MediaStop => "Media Stop",
             LedCode::MediaNext => "Media Next",
             LedCode::MediaPrev => "Media Previous",
+            LedCode::IsoBackslash => "ISO Backslash",
             LedCode::LidLogo => "Lid Logo",
             LedCode::LidLeft => "Lid Left",
             LedCode::LidRight => "Lid Right",
@@ -683,6 +752,26 @@ mod tests {
         colour_check_zoned!(LedCode::LightbarLeft, 42);
     }
 
+    #[test]
+    fn fill_packets() {
+        let mut per_key = LedUsbPackets::new_per_key();
+        per_key.fill(1, 2, 3);
+        assert_eq!(per_key.rgb_for_led_code(LedCode::Esc).unwrap(), &[1, 2, 3]);
+        assert_eq!(per_key.rgb_for_led_code(LedCode::Left).unwrap(), &[1, 2, 3]);
+        let pkt: UsbPackets = per_key.into();
+        assert_eq!(pkt[10][7], 0x08);
+        assert_eq!(pkt[10][8], 0x00);
+
+        let mut zoned = LedUsbPackets::new_zoned(true);
+        zoned.fill(1, 2, 3);
+        assert_eq!(
+            zoned.rgb_for_led_code(LedCode::LightbarLeft).unwrap(),
+            &[1, 2, 3]
+        );
+        let pkt: UsbPackets = zoned.into();
+        assert_eq!(pkt[0][45], 0);
+    }
+
     #[test]
     fn perkey_to_packet_check() {
         let per_key = LedUsbPackets::new_per_key();
@@ -728,4 +817,13 @@ mod tests {
         assert_eq!(pkt[7][14], 0xff); // M
         assert_eq!(pkt[7][15], 0x00); // M
     }
+
+    #[test]
+    fn read_colour() {
+        let mut per_key = LedUsbPackets::new_per_key();
+        per_key.set(LedCode::D, 1, 2, 3);
+        let colour = per_key.colour(LedCode::D).unwrap();
+        assert_eq!((colour.r, colour.g, colour.b), (1, 2, 3));
+        assert_eq!(per_key.colour(LedCode::Backspace), None);
+    }
 }
@@ -0,0 +1,270 @@
+//! Import keyboard layouts from the JSON downloaded from
+//! [keyboard-layout-editor](http://www.keyboard-layout-editor.com).
+//!
+//! One KLE unit becomes `1.2` in a `KeyLayout`, which is a `1.0` key with
+//! `0.1` padding each side, the same as the hand written layouts. Key legends
+//! are mapped to `LedCode` using a `KleLegendMap`. Rotation and the second
+//! rectangle of stepped keys such as ISO Enter are not supported.
+
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::keyboard::{AddressingIssues, KeyLayout, KeyRow, KeyShape, LedCode};
+
+/// The size of one KLE unit in a `KeyLayout`
+const KLE_UNIT: f32 = 1.2;
+/// Padding each side of an LED key
+const KEY_PAD: f32 = 0.1;
+
+/// Maps key legends to a list of `LedCode`. Each time a legend is used the
+/// next code in its list is taken, so that legends used more than once, such
+/// as `Shift`, can map to the left then right keys. Matching ignores case.
+///
+/// Legends not in the map are also checked against the `LedCode` names, so
+/// `Q`, `F1`, or `PgUp` do not need to be listed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KleLegendMap(pub HashMap<String, Vec<LedCode>>);
+
+impl Default for KleLegendMap {
+    fn default() -> Self {
+        use LedCode::*;
+        let map: &[(&str, &[LedCode])] = &[
+            ("esc", &[Esc]),
+            ("escape", &[Esc]),
+            ("`", &[Tilde]),
+            ("~", &[Tilde]),
+            ("1", &[N1]),
+            ("2", &[N2]),
+            ("3", &[N3]),
+            ("4", &[N4]),
+            ("5", &[N5]),
+            ("6", &[N6]),
+            ("7", &[N7]),
+            ("8", &[N8]),
+            ("9", &[N9]),
+            ("0", &[N0]),
+            ("-", &[Hyphen]),
+            ("=", &[Equals]),
+            ("[", &[LBracket]),
+            ("]", &[RBracket]),
+            ("\\", &[BackSlash]),
+            ("|", &[BackSlash]),
+            (";", &[SemiColon]),
+            ("'", &[Quote]),
+            (",", &[Comma]),
+            (".", &[Period]),
+            ("/", &[FwdSlash]),
+            ("caps lock", &[Caps]),
+            ("enter", &[Return]),
+            ("shift", &[LShift, Rshift]),
+            ("ctrl", &[LCtrl, RCtrl]),
+            ("control", &[LCtrl, RCtrl]),
+            ("alt", &[LAlt, RAlt]),
+            ("fn", &[LFn, RFn]),
+            ("win", &[Meta]),
+            ("super", &[Meta]),
+            ("space", &[Spacebar]),
+            ("delete", &[Del]),
+            ("page up", &[PgUp]),
+            ("page down", &[PgDn]),
+            ("prtsc", &[PrtSc]),
+            ("print screen", &[PrtSc]),
+            ("num lock", &[NumLock]),
+            ("↑", &[Up]),
+            ("↓", &[Down]),
+            ("←", &[Left]),
+            ("→", &[Right]),
+        ];
+        Self(
+            map.iter()
+                .map(|(legend, codes)| ((*legend).to_owned(), codes.to_vec()))
+                .collect(),
+        )
+    }
+}
+
+impl KleLegendMap {
+    /// Add or replace mappings with those in `other`
+    pub fn extend(&mut self, other: KleLegendMap) {
+        for (legend, codes) in other.0 {
+            self.0.insert(legend.to_lowercase(), codes);
+        }
+    }
+
+    fn find(&self, legend: &str, used: &mut HashMap<String, usize>) -> Option<LedCode> {
+        let legend = legend.to_lowercase();
+        let codes = self
+            .0
+            .iter()
+            .find(|(k, _)| k.to_lowercase() == legend)
+            .map(|(_, v)| v)?;
+        let count = used.entry(legend).or_default();
+        let code = codes.get(*count).or(codes.last()).copied();
+        *count += 1;
+        code
+    }
+}
+
+/// The result of an import. The layout is always created, keys that could not
+/// be mapped are added as `Blocking` so that the layout still looks right.
+#[derive(Debug)]
+pub struct KleImport {
+    pub layout: KeyLayout,
+    /// Legends that had no `LedCode`
+    pub unmapped: Vec<String>,
+    /// Problems with the LEDs that were mapped
+    pub issues: AddressingIssues,
+}
+
+/// Round to two decimal places to tidy up the f32 math in the output
+fn tidy(v: f32) -> f32 {
+    (v * 100.0).round() / 100.0
+}
+
+/// The parts of a KLE legend in order, without any html
+fn legend_parts(legend: &str) -> impl Iterator<Item = String> + '_ {
+    legend.split('\n').filter_map(|part| {
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in part.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_owned())
+    })
+}
+
+impl KeyLayout {
+    /// Convert KLE JSON to a layout for `locale`, checking the result against
+    /// `LedUsbPackets` addressing
+    pub fn from_kle(json: &str, locale: &str, legends: &KleLegendMap) -> Result<KleImport, Error> {
+        let kle: Vec<Value> = serde_json::from_str(json)?;
+
+        let mut key_shapes = BTreeMap::new();
+        let mut key_rows = Vec::new();
+        let mut unmapped = Vec::new();
+        let mut used = HashMap::new();
+
+        // The first item may be keyboard metadata instead of a row
+        for row in kle.iter().filter_map(Value::as_array) {
+            let mut keys = Vec::new();
+            let mut pad_top = KEY_PAD;
+            let (mut width, mut height, mut decal) = (1.0, 1.0, false);
+
+            for item in row {
+                match item {
+                    Value::Object(props) => {
+                        let prop = |name: &str| props.get(name).and_then(Value::as_f64);
+                        if let Some(x) = prop("x").filter(|x| *x > 0.0) {
+                            let x = tidy(x as f32 * KLE_UNIT);
+                            let name = format!("spacing_{x}");
+                            key_shapes.insert(name.clone(), KeyShape::new_blank(x, 0.0));
+                            keys.push((LedCode::Spacing, name));
+                        }
+                        if let Some(y) = prop("y") {
+                            pad_top += y as f32 * KLE_UNIT;
+                        }
+                        width = prop("w").unwrap_or(width as f64) as f32;
+                        height = prop("h").unwrap_or(height as f64) as f32;
+                        decal = props.get("d").and_then(Value::as_bool).unwrap_or(decal);
+                        if props.contains_key("r") {
+                            warn!("KLE rotation is not supported, keys will be unrotated");
+                        }
+                    }
+                    Value::String(legend) => {
+                        let (w, h) = (tidy(width * KLE_UNIT), tidy(height * KLE_UNIT));
+                        if decal {
+                            let name = format!("spacing_{w}");
+                            key_shapes.insert(name.clone(), KeyShape::new_blank(w, 0.0));
+                            keys.push((LedCode::Spacing, name));
+                        } else {
+                            let mut parts: Vec<String> = legend_parts(legend).collect();
+                            // Space bars usually have no legend
+                            if parts.is_empty() && width >= 3.0 {
+                                parts.push("space".to_owned());
+                            }
+                            let led = parts.iter().find_map(|part| {
+                                legends.find(part, &mut used).or_else(|| {
+                                    ron::from_str::<LedCode>(part)
+                                        .ok()
+                                        .filter(|led| !led.is_placeholder())
+                                })
+                            });
+                            let led = led.unwrap_or_else(|| {
+                                unmapped.push(parts.join(" "));
+                                LedCode::Blocking
+                            });
+
+                            let name = format!("key_{}x{}", tidy(width), tidy(height));
+                            key_shapes.insert(
+                                name.clone(),
+                                KeyShape::new_led(
+                                    tidy(w - KEY_PAD * 2.0),
+                                    tidy(h - KEY_PAD * 2.0),
+                                    KEY_PAD,
+                                    KEY_PAD,
+                                    KEY_PAD,
+                                    KEY_PAD,
+                                ),
+                            );
+                            keys.push((led, name));
+                        }
+                        (width, height, decal) = (1.0, 1.0, false);
+                    }
+                    _ => {}
+                }
+            }
+            key_rows.push(KeyRow::new(KEY_PAD, tidy(pad_top), keys));
+        }
+
+        let layout = KeyLayout::new(locale.to_owned(), key_shapes, key_rows);
+        let issues = layout.check_addressing();
+        Ok(KleImport {
+            layout,
+            unmapped,
+            issues,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_rows() {
+        let json = r#"[
+            {"name": "test"},
+            ["Esc", {"x": 0.5}, "F1"],
+            [{"w": 1.5}, "Tab", "Q", "<b>?</b>"],
+            [{"w": 2.25}, "Shift", "!\n1", {"w": 2.75}, "Shift"],
+            [{"w": 6.25}, ""]
+        ]"#;
+        let import = KeyLayout::from_kle(json, "US", &KleLegendMap::default()).unwrap();
+
+        let rows: Vec<Vec<LedCode>> = import
+            .layout
+            .rows()
+            .map(|r| r.row().map(|(led, _)| *led).collect())
+            .collect();
+        assert_eq!(rows[0], vec![LedCode::Esc, LedCode::Spacing, LedCode::F1]);
+        assert_eq!(rows[1], vec![LedCode::Tab, LedCode::Q, LedCode::Blocking]);
+        assert_eq!(rows[2], vec![LedCode::LShift, LedCode::N1, LedCode::Rshift]);
+        assert_eq!(rows[3], vec![LedCode::Spacebar]);
+        assert_eq!(import.unmapped, vec!["?".to_owned()]);
+        // Spacebar is addressed by its 5 LEDs instead
+        assert_eq!(import.issues.unaddressable, vec![LedCode::Spacebar]);
+
+        let (_, tab) = import.layout.rows().nth(1).unwrap().row().next().unwrap();
+        assert!(matches!(tab, KeyShape::Led { width, .. } if *width == 1.6));
+    }
+}
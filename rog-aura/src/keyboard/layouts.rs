@@ -1,7 +1,7 @@
 //! A series of pre-defined layouts. These were mostly used to generate an
 //! editable config.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::slice::Iter;
 
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::aura_detection::LedSupportData;
 use crate::error::Error;
-use crate::keyboard::{AdvancedAuraType, LedCode};
+use crate::keyboard::{AdvancedAuraType, LedCode, LedUsbPackets};
 use crate::{AuraModeNum, AuraZone};
 
 /// The `key_type` plays a role in effects (eventually). You could for example
@@ -163,12 +163,89 @@ impl KeyRow {
     }
 }
 
+/// Locales with the ISO physical layout of a short left shift and tall return.
+/// These use an `ISO` layout file if there is no file for the locale.
+const ISO_LOCALES: [&str; 4] = ["UK", "DE", "FR", "NORDIC"];
+
+/// Files that can set the system keyboard layout, in order of preference
+const XKB_CONFIG_FILES: [&str; 3] = [
+    "/etc/X11/xorg.conf.d/00-keyboard.conf",
+    "/etc/vconsole.conf",
+    "/etc/default/keyboard",
+];
+
+/// The locales to try for a layout file in order, ending with `US`
+pub fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let locale = locale.to_uppercase();
+    let mut fallbacks = vec![locale.clone()];
+    if ISO_LOCALES.contains(&locale.as_str()) {
+        fallbacks.push("ISO".to_owned());
+    }
+    if locale != "US" {
+        fallbacks.push("US".to_owned());
+    }
+    fallbacks
+}
+
+/// Convert an XKB layout name such as `gb` to a layout file locale
+pub fn locale_from_xkb(layout: &str) -> String {
+    match layout.trim().to_lowercase().as_str() {
+        "gb" | "uk" => "UK".to_owned(),
+        "de" | "at" | "ch" => "DE".to_owned(),
+        "fr" | "be" => "FR".to_owned(),
+        "jp" => "JP".to_owned(),
+        "se" | "no" | "dk" | "fi" => "NORDIC".to_owned(),
+        other => other.to_uppercase(),
+    }
+}
+
+/// Find the XKB layout in config file contents, e.g. `XKBLAYOUT=gb` or
+/// `Option "XkbLayout" "gb"`. Only the first of multiple layouts is used.
+fn xkb_layout_from_config(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let line = line.trim();
+        let value = if let Some(value) = line.strip_prefix("XKBLAYOUT=") {
+            value
+        } else if line.starts_with("Option") && line.contains("\"XkbLayout\"") {
+            line.rsplit_once("\"XkbLayout\"")?.1
+        } else {
+            return None;
+        };
+        let layout = value.trim().trim_matches('"').split(',').next()?.trim();
+        (!layout.is_empty()).then_some(layout)
+    })
+}
+
+/// Find the layout file locale from the system keyboard config, as set by
+/// `localectl set-x11-keymap`
+pub fn system_keyboard_locale() -> Option<String> {
+    XKB_CONFIG_FILES.iter().find_map(|file| {
+        let contents = std::fs::read_to_string(file).ok()?;
+        xkb_layout_from_config(&contents).map(locale_from_xkb)
+    })
+}
+
+/// LEDs in a layout that won't work as expected with `LedUsbPackets`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddressingIssues {
+    /// LEDs that have no location in the per-key packets
+    pub unaddressable: Vec<LedCode>,
+    /// LEDs used by more than one key
+    pub duplicates: Vec<LedCode>,
+}
+
+impl AddressingIssues {
+    pub fn is_empty(&self) -> bool {
+        self.unaddressable.is_empty() && self.duplicates.is_empty()
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct KeyLayout {
     /// Localization of this keyboard layout
     locale: String,
     /// The shapes of keys used
-    key_shapes: HashMap<String, KeyShape>,
+    key_shapes: BTreeMap<String, KeyShape>,
     /// The rows of keys of this layout
     key_rows: Vec<KeyRow>,
     /// Should be copied from the `LaptopLedData` as laptops may have the same
@@ -225,6 +302,56 @@ impl KeyLayout {
         }
     }
 
+    /// Create a layout from shapes and rows, such as when importing from
+    /// another format. The modes, zones, and advanced type are left empty.
+    pub fn new(
+        locale: String,
+        key_shapes: BTreeMap<String, KeyShape>,
+        key_rows: Vec<KeyRow>,
+    ) -> Self {
+        let mut layout = Self {
+            locale,
+            key_shapes,
+            key_rows,
+            basic_modes: Vec::new(),
+            basic_zones: Vec::new(),
+            advanced_type: AdvancedAuraType::None,
+        };
+        for row in &mut layout.key_rows {
+            for k in &row.row {
+                if let Some(shape) = layout.key_shapes.get(&k.1) {
+                    row.built_row.push((k.0, shape.clone()));
+                }
+            }
+        }
+        layout
+    }
+
+    /// Check the LEDs of this layout against per-key `LedUsbPackets`
+    /// addressing. Any LEDs listed in the result will either not light, or
+    /// will light with another key.
+    pub fn check_addressing(&self) -> AddressingIssues {
+        let packets = LedUsbPackets::new_per_key();
+        let mut seen = Vec::new();
+        let mut issues = AddressingIssues::default();
+        for (led, _) in self.key_rows.iter().flat_map(|r| r.row.iter()) {
+            if led.is_placeholder() {
+                continue;
+            }
+            if !packets.has_address(*led) && !issues.unaddressable.contains(led) {
+                issues.unaddressable.push(*led);
+            }
+            if seen.contains(led) {
+                if !issues.duplicates.contains(led) {
+                    issues.duplicates.push(*led);
+                }
+            } else {
+                seen.push(*led);
+            }
+        }
+        issues
+    }
+
     pub fn rows(&self) -> Iter<'_, KeyRow> {
         self.key_rows.iter()
     }
@@ -277,21 +404,49 @@ impl KeyLayout {
         width
     }
 
+    /// Find a layout matching the name in `LaptopLedData` in the provided dir,
+    /// using the locale from `system_keyboard_locale()`, or `US` if not set
+    pub fn find_layout(led_data: LedSupportData, data_path: PathBuf) -> Result<Self, Error> {
+        let locale = system_keyboard_locale().unwrap_or_else(|| "US".to_owned());
+        Self::find_layout_for_locale(led_data, data_path, &locale)
+    }
+
     /// Find a layout matching the name in `LaptopLedData` in the provided dir
-    pub fn find_layout(led_data: LedSupportData, mut data_path: PathBuf) -> Result<Self, Error> {
-        // TODO: locales
+    /// for `locale`.
+    ///
+    /// Layout files are tried in the order of `locale_fallbacks()`, so for
+    /// `DE` the files `name_DE.ron`, `name_ISO.ron`, then `name_US.ron`. A
+    /// warning is logged when any locale other than `US` ends up on US.
+    pub fn find_layout_for_locale(
+        led_data: LedSupportData,
+        mut data_path: PathBuf,
+        locale: &str,
+    ) -> Result<Self, Error> {
         let layout_name = if led_data.layout_name.is_empty() {
             "ga401q".to_owned() // Need some sort of default here due to ROGCC
                                 // expecting it
         } else {
             led_data.layout_name
         };
-        let layout_file = format!("{layout_name}_US.ron");
         data_path.push("layouts");
-        data_path.push(layout_file);
-        let path = data_path.as_path();
+        let fallbacks = locale_fallbacks(locale);
+        let found = fallbacks
+            .iter()
+            .find(|locale| {
+                data_path
+                    .join(format!("{layout_name}_{locale}.ron"))
+                    .exists()
+            })
+            .map_or("US", |locale| locale.as_str());
+        if found == "US" && fallbacks[0] != "US" {
+            warn!(
+                "No {} layout for {layout_name}, using US so keys it doesn't have are not shown",
+                fallbacks[0]
+            );
+        }
+        let path = data_path.join(format!("{layout_name}_{found}.ron"));
 
-        let mut tmp = KeyLayout::from_file(path)?;
+        let mut tmp = KeyLayout::from_file(&path)?;
         tmp.basic_modes = led_data.basic_modes;
         tmp.basic_zones = led_data.basic_zones;
         tmp.advanced_type = led_data.advanced_type;
@@ -330,7 +485,7 @@ impl KeyLayout {
             ],
             basic_zones: vec![AuraZone::None],
             advanced_type: AdvancedAuraType::None,
-            key_shapes: HashMap::from([(
+            key_shapes: BTreeMap::from([(
                 "regular".to_owned(),
                 KeyShape::new_led(1.0, 1.0, 0.1, 0.1, 0.1, 0.1),
             )]),
@@ -458,8 +613,9 @@ mod tests {
     use std::io::Read;
     use std::path::PathBuf;
 
-    use crate::aura_detection::LedSupportFile;
-    use crate::keyboard::KeyLayout;
+    use super::{locale_fallbacks, locale_from_xkb, xkb_layout_from_config};
+    use crate::aura_detection::{LedSupportData, LedSupportFile};
+    use crate::keyboard::{KeyLayout, LedCode};
 
     #[test]
     fn check_parse_all() {
@@ -516,6 +672,53 @@ mod tests {
         // data).unwrap(); file.write_all(json.as_bytes()).unwrap();
     }
 
+    #[test]
+    fn check_locale_fallbacks() {
+        assert_eq!(locale_fallbacks("de"), vec!["DE", "ISO", "US"]);
+        assert_eq!(locale_fallbacks("JP"), vec!["JP", "US"]);
+        assert_eq!(locale_fallbacks("US"), vec!["US"]);
+
+        let xorg = "Section \"InputClass\"\n        Option \"XkbLayout\" \"gb,us\"\nEndSection";
+        assert_eq!(
+            xkb_layout_from_config(xorg).map(locale_from_xkb),
+            Some("UK".into())
+        );
+        let vconsole = "KEYMAP=de-latin1\nXKBLAYOUT=de\nXKBMODEL=pc105";
+        assert_eq!(
+            xkb_layout_from_config(vconsole).map(locale_from_xkb),
+            Some("DE".into())
+        );
+    }
+
+    #[test]
+    fn check_iso_layout_fallback() {
+        let data = LedSupportData {
+            layout_name: "g513i-per-key".to_owned(),
+            ..Default::default()
+        };
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data");
+        let layout = KeyLayout::find_layout_for_locale(data, path, "DE").unwrap();
+        assert_eq!(layout.locale, "ISO");
+        // The tall ISO return is drawn in two parts, one in each row
+        let return_rows = layout
+            .rows()
+            .filter(|r| r.row().any(|(led, _)| *led == LedCode::Return))
+            .count();
+        assert_eq!(return_rows, 2);
+        // Both extra ISO keys are lit and have an LED of their own
+        let issues = layout.check_addressing();
+        for key in [LedCode::IsoBackslash, LedCode::BackSlash] {
+            assert!(!issues.unaddressable.contains(&key), "{issues:?}");
+            assert!(!issues.duplicates.contains(&key), "{issues:?}");
+        }
+        let keys = layout
+            .rows()
+            .flat_map(|r| r.row().map(|(led, _)| *led))
+            .collect::<Vec<_>>();
+        assert!(keys.contains(&LedCode::IsoBackslash));
+        assert!(keys.contains(&LedCode::BackSlash));
+    }
+
     #[test]
     fn check_layout_file_links() {
         const DATA_DIR: &str = env!("CARGO_MANIFEST_DIR");
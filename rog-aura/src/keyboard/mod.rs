@@ -6,6 +6,10 @@ pub use advanced::*;
 mod layouts;
 pub use layouts::*;
 
+/// Import layouts from keyboard-layout-editor JSON
+mod kle;
+pub use kle::*;
+
 mod power;
 pub use power::*;
 
@@ -17,6 +17,10 @@ pub mod effects;
 mod builtin_modes;
 pub use builtin_modes::*;
 
+/// Colour parsing, colour spaces, blending, and per-device correction
+mod colour;
+pub use colour::*;
+
 /// Helper for detecting what is available
 pub mod aura_detection;
 pub mod error;
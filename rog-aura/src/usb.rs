@@ -1,3 +1,8 @@
+use std::fmt;
+
+use crate::keyboard::colour_positions;
+use crate::{AuraEffect, Colour, LED_MSG_LEN};
+
 // Only these two packets must be 17 bytes
 pub const LED_APPLY: [u8; 17] = [0x5d, 0xb4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
 pub const LED_SET: [u8; 17] = [0x5d, 0xb5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
@@ -8,3 +13,87 @@ pub const fn aura_brightness_bytes(brightness: u8) -> [u8; 17] {
         0x5a, 0xba, 0xc5, 0xc4, brightness, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     ]
 }
+
+/// A packet written to an aura keyboard, parsed back from the bytes made by
+/// this module, `AuraEffect` and `LedUsbPackets`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuraPacket {
+    /// A builtin mode, which is shown after `Set`
+    Effect(AuraEffect),
+    /// `LED_SET`
+    Set,
+    /// `LED_APPLY`, which keeps the mode over a reboot
+    Apply,
+    Brightness(u8),
+    /// The bytes from `LaptopAuraPower::to_bytes()`
+    PowerStates([u8; 4]),
+    /// Clears the keyboard before the first per-key or zoned packets
+    DirectInit,
+    /// One packet of per-key or zoned colours from `LedUsbPackets`
+    Direct(Vec<u8>),
+}
+
+impl AuraPacket {
+    /// Parse a packet. Returns `None` if it is not one that is known.
+    pub fn parse(pkt: &[u8]) -> Option<Self> {
+        let byte = |i: usize| pkt.get(i).copied().unwrap_or_default();
+        Some(match (byte(0), byte(1)) {
+            (0x5a, 0xba) if byte(2) == 0xc5 && byte(3) == 0xc4 => Self::Brightness(byte(4)),
+            (0x5d, 0xb3) => {
+                let msg: &[u8; LED_MSG_LEN] = pkt.get(..LED_MSG_LEN)?.try_into().ok()?;
+                Self::Effect(msg.into())
+            }
+            (0x5d, 0xb4) => Self::Apply,
+            (0x5d, 0xb5) => Self::Set,
+            (0x5d, 0xbd) if byte(2) == 0x01 => {
+                Self::PowerStates([byte(3), byte(4), byte(5), byte(6)])
+            }
+            (0x5d, 0xbc) => {
+                if colour_positions(pkt).is_some() {
+                    Self::Direct(pkt.to_vec())
+                } else {
+                    Self::DirectInit
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for AuraPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |c: &Colour| format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b);
+        match self {
+            Self::Effect(effect) => write!(
+                f,
+                "Aura set mode {}, zone {:?}, colours {} {}, speed {:?}, direction {:?}",
+                effect.mode_name(),
+                effect.zone,
+                hex(&effect.colour1),
+                hex(&effect.colour2),
+                effect.speed,
+                effect.direction
+            ),
+            Self::Set => write!(f, "Aura set, show the mode"),
+            Self::Apply => write!(f, "Aura apply, keep the mode over reboot"),
+            Self::Brightness(brightness) => write!(f, "Aura set brightness {brightness}"),
+            Self::PowerStates(bytes) => write!(f, "Aura set power states {bytes:02x?}"),
+            Self::DirectInit => write!(f, "Aura start per-key or zoned colours"),
+            Self::Direct(row) => {
+                if row[2] == 0x01 {
+                    write!(f, "Aura zoned colours")?;
+                } else {
+                    write!(f, "Aura per-key colours group {}", row[6] >> 4)?;
+                }
+                if let Some(range) = colour_positions(row) {
+                    let lit = row[range]
+                        .chunks_exact(3)
+                        .filter(|c| c != &[0, 0, 0])
+                        .count();
+                    write!(f, ", {lit} lit")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -5,6 +5,7 @@ pub mod zbus_aura;
 pub mod zbus_fan_curves;
 pub mod zbus_platform;
 pub mod zbus_slash;
+pub mod zbus_user_config;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -17,6 +17,9 @@ trait Anime {
     /// Write method
     fn write(&self, input: AnimeDataBuffer) -> zbus::Result<()>;
 
+    /// OpenStream method
+    fn open_stream(&self) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
     /// NotifyDeviceState signal
     #[zbus(signal)]
     fn notify_device_state(&self, data: AnimeDeviceState) -> zbus::Result<()>;
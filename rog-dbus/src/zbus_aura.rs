@@ -84,6 +84,16 @@ trait Aura {
     /// SupportedPowerZones property
     #[zbus(property)]
     fn supported_power_zones(&self) -> zbus::Result<Vec<PowerZones>>;
+
+    /// TransitionTime property
+    #[zbus(property)]
+    fn transition_time(&self) -> zbus::Result<u32>;
+    #[zbus(property)]
+    fn set_transition_time(&self, time_ms: u32) -> zbus::Result<()>;
+
+    /// SupportedDataSources property
+    #[zbus(property)]
+    fn supported_data_sources(&self) -> zbus::Result<Vec<String>>;
 }
 
 pub struct AuraProxyPerkey<'a>(AuraProxyBlocking<'a>);
@@ -0,0 +1,49 @@
+//! Proxy for the config interface of `asusd-user`, which is on the session
+//! bus
+
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.asuslinux.UserConfig",
+    default_service = "org.asuslinux.Daemon",
+    default_path = "/org/asuslinux/UserConfig"
+)]
+trait UserConfig {
+    /// AnimeConfigs method
+    fn anime_configs(&self) -> zbus::Result<Vec<String>>;
+
+    /// AuraConfigs method
+    fn aura_configs(&self) -> zbus::Result<Vec<String>>;
+
+    /// ActivateAnimeConfig method, an empty name deactivates
+    fn activate_anime_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// ActivateAuraConfig method, an empty name deactivates
+    fn activate_aura_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// CopyAnimeConfig method
+    fn copy_anime_config(&self, from: &str, to: &str) -> zbus::Result<()>;
+
+    /// CopyAuraConfig method
+    fn copy_aura_config(&self, from: &str, to: &str) -> zbus::Result<()>;
+
+    /// CreateAnimeConfig method
+    fn create_anime_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// CreateAuraConfig method
+    fn create_aura_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// DeleteAnimeConfig method
+    fn delete_anime_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// DeleteAuraConfig method
+    fn delete_aura_config(&self, name: &str) -> zbus::Result<()>;
+
+    /// ActiveAnime property
+    #[zbus(property)]
+    fn active_anime(&self) -> zbus::Result<String>;
+
+    /// ActiveAura property
+    #[zbus(property)]
+    fn active_aura(&self) -> zbus::Result<String>;
+}
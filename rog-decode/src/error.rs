@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, DecodeError>;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(std::io::Error),
+    /// The line number and what is wrong with it
+    Hex(usize, String),
+    /// The byte offset of the block and what is wrong with it
+    Pcapng(usize, String),
+    /// The capture is not of USB traffic
+    LinkType(u16),
+}
+
+impl fmt::Display for DecodeError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "Failed to read: {}", err),
+            DecodeError::Hex(line, detail) => write!(f, "Line {}: {}", line, detail),
+            DecodeError::Pcapng(offset, detail) => {
+                write!(f, "Block at byte {}: {}", offset, detail)
+            }
+            DecodeError::LinkType(link) => write!(
+                f,
+                "Link type {} is not usbmon, capture on a usbmon interface",
+                link
+            ),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<std::io::Error> for DecodeError {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::BufRead;
+
+use crate::error::{DecodeError, Result};
+
+/// Read one packet per line. The bytes may be separated by spaces, commas or
+/// colons and may have a `0x` prefix, or be a continuous string of hex such
+/// as copied from Wireshark. Empty lines and anything after a `#` are
+/// skipped.
+pub fn read_packets(reader: impl BufRead) -> Result<Vec<Vec<u8>>> {
+    let mut packets = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default();
+        let packet = parse_line(line).map_err(|e| DecodeError::Hex(i + 1, e))?;
+        if !packet.is_empty() {
+            packets.push(packet);
+        }
+    }
+    Ok(packets)
+}
+
+/// Parse the bytes of one line
+pub fn parse_line(line: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for word in line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+        .filter(|w| !w.is_empty())
+    {
+        let word = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if word.len() % 2 != 0 {
+            return Err(format!("{word} is not a whole number of bytes"));
+        }
+        for i in (0..word.len()).step_by(2) {
+            let byte = word
+                .get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| format!("{word} is not hex"))?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_formats() {
+        assert_eq!(parse_line("5d b5 00").unwrap(), [0x5d, 0xb5, 0x00]);
+        assert_eq!(parse_line("0x5d, 0xB5,0x00").unwrap(), [0x5d, 0xb5, 0x00]);
+        assert_eq!(parse_line("5d:b5:00").unwrap(), [0x5d, 0xb5, 0x00]);
+        assert_eq!(parse_line("5db500").unwrap(), [0x5d, 0xb5, 0x00]);
+        assert!(parse_line("5db50").is_err());
+        assert!(parse_line("5d zz").is_err());
+
+        let text = "# brightness\n5a ba c5 c4 02\n\n5d b5 # set\n";
+        let packets = read_packets(text.as_bytes()).unwrap();
+        assert_eq!(
+            packets,
+            [vec![0x5a, 0xba, 0xc5, 0xc4, 0x02], vec![0x5d, 0xb5]]
+        );
+
+        let text = format!("{text}5d xx\n");
+        match read_packets(text.as_bytes()) {
+            Err(DecodeError::Hex(line, _)) => assert_eq!(line, 5),
+            res => panic!("expected an error on line 5, got {res:?}"),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Decode the packets written to the `AniMe`, `Slash` and aura keyboards in to
+//! named messages, such as `Aura set mode Breathe, ...` or `AniMe pane 2
+//! data`. The packets can be read from hex dumps or from a usbmon capture.
+//!
+//! ```ignore
+//! for packet in pcapng::read_packets(File::open("capture.pcapng")?)? {
+//!     println!("{}", Message::decode(&packet.data, None));
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use rog_anime::usb::AnimePacket;
+use rog_aura::usb::AuraPacket;
+use rog_slash::usb::SlashPacket;
+
+pub mod error;
+/// Reading packets from hex text, one packet per line
+pub mod hex;
+/// Reading the outgoing packets from a usbmon pcapng capture
+pub mod pcapng;
+
+/// Which device the packets were written to. Used to restrict decoding when
+/// it is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Anime,
+    Slash,
+    Aura,
+}
+
+impl FromStr for Device {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "anime" => Ok(Self::Anime),
+            "slash" => Ok(Self::Slash),
+            "aura" => Ok(Self::Aura),
+            _ => Err(format!("unknown device {s}, expected anime, slash or aura")),
+        }
+    }
+}
+
+/// A decoded packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Anime(AnimePacket),
+    Slash(SlashPacket),
+    Aura(AuraPacket),
+    /// Not a packet known for the device, or for any device if none was given
+    Unknown(Vec<u8>),
+}
+
+impl Message {
+    /// Decode a packet. The `AniMe` and `Slash` share a product ID and report
+    /// ID, but not any commands, so the device only needs to be given to
+    /// rule out a mistaken match.
+    pub fn decode(pkt: &[u8], device: Option<Device>) -> Self {
+        let wanted = |d: Device| device.unwrap_or(d) == d;
+        if wanted(Device::Anime) {
+            if let Some(packet) = AnimePacket::parse(pkt) {
+                return Self::Anime(packet);
+            }
+        }
+        if wanted(Device::Slash) {
+            if let Some(packet) = SlashPacket::parse(pkt) {
+                return Self::Slash(packet);
+            }
+        }
+        if wanted(Device::Aura) {
+            if let Some(packet) = AuraPacket::parse(pkt) {
+                return Self::Aura(packet);
+            }
+        }
+        Self::Unknown(pkt.to_vec())
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Anime(packet) => packet.fmt(f),
+            Self::Slash(packet) => packet.fmt(f),
+            Self::Aura(packet) => packet.fmt(f),
+            Self::Unknown(pkt) => {
+                // Trailing zeroes are only padding
+                let end = pkt.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+                write!(f, "unknown packet {:02x?}", &pkt[..end])?;
+                if end < pkt.len() {
+                    write!(f, " and {} zero bytes", pkt.len() - end)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rog_anime::usb::{pkt_flush, pkt_set_brightness, Brightness};
+    use rog_aura::usb::LED_SET;
+    use rog_aura::{AuraEffect, AuraModeNum, Colour, LED_MSG_LEN};
+    use rog_slash::usb::pkt_set_options;
+
+    use super::*;
+
+    #[test]
+    fn decode_each_device() {
+        let msg = Message::decode(&pkt_set_brightness(Brightness::High), None);
+        assert_eq!(
+            msg,
+            Message::Anime(AnimePacket::Brightness(Brightness::High))
+        );
+        assert_eq!(msg.to_string(), "AniMe set brightness High");
+
+        let msg = Message::decode(&pkt_set_options(true, 200, 3), None);
+        assert_eq!(
+            msg.to_string(),
+            "Slash set options enabled true, brightness 200, interval 3"
+        );
+
+        let effect = AuraEffect {
+            mode: AuraModeNum::Breathe,
+            colour1: Colour { r: 255, g: 0, b: 0 },
+            ..Default::default()
+        };
+        let msg = Message::decode(&<[u8; LED_MSG_LEN]>::from(&effect), None);
+        assert_eq!(msg, Message::Aura(AuraPacket::Effect(effect)));
+        assert!(msg.to_string().starts_with("Aura set mode Breathe"));
+        assert_eq!(
+            Message::decode(&LED_SET, Some(Device::Aura)),
+            Message::Aura(AuraPacket::Set)
+        );
+    }
+
+    #[test]
+    fn decode_for_device() {
+        assert!(Message::decode(&pkt_flush(), Some(Device::Slash)).is_unknown());
+        assert!(!Message::decode(&pkt_flush(), Some(Device::Anime)).is_unknown());
+
+        let msg = Message::decode(&[0x5d, 0xff, 0x01, 0, 0], None);
+        assert_eq!(
+            msg.to_string(),
+            "unknown packet [5d, ff, 01] and 2 zero bytes"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use rog_decode::{hex, pcapng, Device, Message};
+
+const HELP: &str = "Usage: rog-decode [--device <anime|slash|aura>] <file|->
+
+Prints each packet written to an AniMe, Slash or aura keyboard as a named
+message. The file is either a usbmon pcapng capture, or hex text with one
+packet per line. Use - to read from stdin.
+
+Options:
+    --device <name>    only decode packets for this device";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut device = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" | "-d" => {
+                let name = args.next().ok_or("--device needs a device name")?;
+                device = Some(Device::from_str(&name)?);
+            }
+            "--help" | "-h" => {
+                println!("{HELP}");
+                return Ok(());
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let Some(path) = path else {
+        println!("{HELP}");
+        return Ok(());
+    };
+
+    let mut input = Vec::new();
+    if path == "-" {
+        io::stdin().read_to_end(&mut input)?;
+    } else {
+        File::open(&path)
+            .map_err(|e| format!("{path}: {e}"))?
+            .read_to_end(&mut input)?;
+    }
+
+    // Every pcapng file starts with a section header block
+    if input.starts_with(&[0x0a, 0x0d, 0x0d, 0x0a]) {
+        let packets = pcapng::read_packets(input.as_slice())?;
+        let start = packets.first().map(|p| p.time).unwrap_or_default();
+        for packet in packets {
+            println!(
+                "{:>10.6} {}.{:03}: {}",
+                packet.time.saturating_sub(start).as_secs_f64(),
+                packet.bus,
+                packet.device,
+                Message::decode(&packet.data, device)
+            );
+        }
+    } else {
+        for packet in hex::read_packets(input.as_slice())? {
+            println!("{}", Message::decode(&packet, device));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use std::io::Read;
+use std::time::Duration;
+
+use crate::error::{DecodeError, Result};
+
+const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+/// `LINKTYPE_USB_LINUX`, the 48 byte usbmon header
+const LINKTYPE_USB_LINUX: u16 = 189;
+/// `LINKTYPE_USB_LINUX_MMAPPED`, the 64 byte usbmon header
+const LINKTYPE_USB_LINUX_MMAPPED: u16 = 220;
+
+const URB_SUBMIT: u8 = b'S';
+const URB_INTERRUPT: u8 = 1;
+const URB_CONTROL: u8 = 2;
+const ENDPOINT_IN: u8 = 0x80;
+/// `bmRequestType` and `bRequest` of a HID `SET_REPORT`, which is what
+/// `USBRaw` and hidraw write with on devices without an interrupt OUT endpoint
+const SET_REPORT: [u8; 2] = [0x21, 0x09];
+
+/// A packet written to a device, as seen by usbmon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbPacket {
+    /// The time the URB was submitted
+    pub time: Duration,
+    pub bus: u16,
+    pub device: u8,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(b),
+            Endian::Big => u16::from_be_bytes(b),
+        }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(b),
+            Endian::Big => u32::from_be_bytes(b),
+        }
+    }
+
+    fn u64(self, b: &[u8]) -> u64 {
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        match self {
+            Endian::Little => u64::from_le_bytes(b),
+            Endian::Big => u64::from_be_bytes(b),
+        }
+    }
+}
+
+/// Read every packet written to a device from a pcapng capture of a usbmon
+/// interface, such as made by `wireshark` or `tshark -i usbmon1`. Interrupt
+/// OUT transfers and `SET_REPORT` control transfers are kept, everything
+/// else such as completions and reads is skipped.
+pub fn read_packets(mut reader: impl Read) -> Result<Vec<UsbPacket>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    let mut packets = Vec::new();
+    let mut endian = Endian::Little;
+    // The link type of each interface in the current section
+    let mut interfaces: Vec<u16> = Vec::new();
+    let mut any_usb = false;
+    let mut offset = 0;
+    while offset < buf.len() {
+        let err = |detail: &str| DecodeError::Pcapng(offset, detail.to_owned());
+        let header = buf
+            .get(offset..offset + 12)
+            .ok_or_else(|| err("truncated block header"))?;
+
+        let block_type = endian.u32(header);
+        if block_type == SECTION_HEADER {
+            endian = match Endian::Little.u32(&header[8..]) {
+                BYTE_ORDER_MAGIC => Endian::Little,
+                m if m.swap_bytes() == BYTE_ORDER_MAGIC => Endian::Big,
+                _ => return Err(err("bad byte order magic")),
+            };
+            interfaces.clear();
+        } else if offset == 0 {
+            return Err(err("not a pcapng file"));
+        }
+
+        let len = endian.u32(&header[4..]) as usize;
+        if len < 12 || len & 3 != 0 {
+            return Err(err("bad block length"));
+        }
+        let body = buf
+            .get(offset + 8..offset + len - 4)
+            .ok_or_else(|| err("truncated block"))?;
+
+        match block_type {
+            INTERFACE_DESCRIPTION => {
+                let link = body.get(..2).ok_or_else(|| err("truncated interface"))?;
+                let link = endian.u16(link);
+                any_usb |= usbmon_header_len(link).is_some();
+                interfaces.push(link);
+            }
+            ENHANCED_PACKET => {
+                if body.len() < 20 {
+                    return Err(err("truncated packet"));
+                }
+                let interface = endian.u32(body) as usize;
+                let link = *interfaces
+                    .get(interface)
+                    .ok_or_else(|| err("packet for an undescribed interface"))?;
+                let captured = endian.u32(&body[12..]) as usize;
+                let data = body
+                    .get(20..20 + captured)
+                    .ok_or_else(|| err("truncated packet data"))?;
+                if let Some(header_len) = usbmon_header_len(link) {
+                    if let Some(packet) = parse_usbmon(data, header_len, endian) {
+                        packets.push(packet);
+                    }
+                }
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+
+    if !any_usb {
+        if let Some(link) = interfaces.first() {
+            return Err(DecodeError::LinkType(*link));
+        }
+    }
+    Ok(packets)
+}
+
+fn usbmon_header_len(link: u16) -> Option<usize> {
+    match link {
+        LINKTYPE_USB_LINUX => Some(48),
+        LINKTYPE_USB_LINUX_MMAPPED => Some(64),
+        _ => None,
+    }
+}
+
+/// Parse the usbmon header, which is in the byte order of the machine that
+/// made the capture. Returns `None` if it is not data written to a device.
+fn parse_usbmon(data: &[u8], header_len: usize, endian: Endian) -> Option<UsbPacket> {
+    let header = data.get(..header_len)?;
+    let (event, transfer, endpoint, flag_setup) = (header[8], header[9], header[10], header[14]);
+    if event != URB_SUBMIT || endpoint & ENDPOINT_IN != 0 {
+        return None;
+    }
+    let setup = &header[40..48];
+    let write = match transfer {
+        URB_INTERRUPT => true,
+        URB_CONTROL => flag_setup == 0 && setup[..2] == SET_REPORT,
+        _ => false,
+    };
+    let payload = &data[header_len..];
+    if !write || payload.is_empty() {
+        return None;
+    }
+
+    let secs = endian.u64(&header[16..]);
+    let micros = endian.u32(&header[24..]);
+    Some(UsbPacket {
+        time: Duration::from_secs(secs) + Duration::from_micros(micros as u64),
+        bus: endian.u16(&header[12..]),
+        device: header[11],
+        data: payload.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize(body.len().next_multiple_of(4), 0);
+        let len = (body.len() + 12) as u32;
+        let mut block = Vec::new();
+        block.extend_from_slice(&block_type.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(&body);
+        block.extend_from_slice(&len.to_le_bytes());
+        block
+    }
+
+    fn section(link: u16) -> Vec<u8> {
+        let mut shb = BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        shb.extend_from_slice(&[1, 0, 0, 0]); // version 1.0
+        shb.extend_from_slice(&u64::MAX.to_le_bytes()); // unknown length
+        let mut idb = link.to_le_bytes().to_vec();
+        idb.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut out = block(SECTION_HEADER, &shb);
+        out.extend(block(INTERFACE_DESCRIPTION, &idb));
+        out
+    }
+
+    fn urb(event: u8, transfer: u8, endpoint: u8, setup: [u8; 2], data: &[u8]) -> Vec<u8> {
+        let mut usbmon = vec![0u8; 64];
+        usbmon[8] = event;
+        usbmon[9] = transfer;
+        usbmon[10] = endpoint;
+        usbmon[11] = 3; // device
+        usbmon[12..14].copy_from_slice(&1u16.to_le_bytes());
+        usbmon[14] = if transfer == URB_CONTROL { 0 } else { b'-' };
+        usbmon[16..24].copy_from_slice(&10u64.to_le_bytes());
+        usbmon[24..28].copy_from_slice(&500u32.to_le_bytes());
+        usbmon[40..42].copy_from_slice(&setup);
+        usbmon.extend_from_slice(data);
+
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_le_bytes()); // interface
+        epb.extend_from_slice(&[0; 8]); // timestamp
+        epb.extend_from_slice(&(usbmon.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(usbmon.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&usbmon);
+        block(ENHANCED_PACKET, &epb)
+    }
+
+    #[test]
+    fn read_usbmon_writes() {
+        let mut capture = section(LINKTYPE_USB_LINUX_MMAPPED);
+        capture.extend(urb(b'S', URB_CONTROL, 0, SET_REPORT, &[0x5d, 0xb5]));
+        // The completion of the same transfer
+        capture.extend(urb(b'C', URB_CONTROL, 0, SET_REPORT, &[0x5d, 0xb5]));
+        // A read from the device
+        capture.extend(urb(b'S', URB_INTERRUPT, 0x81, [0, 0], &[0x5a, 1]));
+        // A control transfer that is not a SET_REPORT
+        capture.extend(urb(b'S', URB_CONTROL, 0, [0x80, 0x06], &[0x12]));
+        capture.extend(urb(b'S', URB_INTERRUPT, 0x02, [0, 0], &[0x5e, 0xc0, 0x03]));
+
+        let packets = read_packets(capture.as_slice()).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].data, [0x5d, 0xb5]);
+        assert_eq!(packets[1].data, [0x5e, 0xc0, 0x03]);
+        assert_eq!((packets[1].bus, packets[1].device), (1, 3));
+        assert_eq!(packets[1].time, Duration::from_micros(10_000_500));
+    }
+
+    #[test]
+    fn reject_other_captures() {
+        let capture = section(1); // ethernet
+        assert!(matches!(
+            read_packets(capture.as_slice()),
+            Err(DecodeError::LinkType(1))
+        ));
+        assert!(matches!(
+            read_packets(&b"5d b5 00 00 00 00 00 00"[..]),
+            Err(DecodeError::Pcapng(0, _))
+        ));
+    }
+}
@@ -14,6 +14,46 @@ const ATTR_GOVERNOR: &str = "cpufreq/scaling_governor";
 const ATTR_AVAILABLE_EPP: &str = "cpufreq/energy_performance_available_preferences";
 const ATTR_EPP: &str = "cpufreq/energy_performance_preference";
 
+/// The hwmon drivers known to report CPU package temperature as `temp1`
+const CPU_HWMON_NAMES: [&str; 3] = ["k10temp", "coretemp", "zenpower"];
+
+/// Read the CPU package temperature in degrees celsius, from the first hwmon
+/// device with a known CPU driver
+pub fn cpu_temperature() -> Result<f32> {
+    let mut enumerator = udev::Enumerator::new().map_err(|err| {
+        warn!("{}", err);
+        PlatformError::Udev("enumerator failed".into(), err)
+    })?;
+    enumerator.match_subsystem("hwmon").map_err(|err| {
+        warn!("{}", err);
+        PlatformError::Udev("match_subsystem failed".into(), err)
+    })?;
+
+    for device in enumerator.scan_devices().map_err(|err| {
+        warn!("{}", err);
+        PlatformError::Udev("hwmon: scan_devices failed".into(), err)
+    })? {
+        let Some(name) = device.attribute_value("name") else {
+            continue;
+        };
+        if !CPU_HWMON_NAMES.contains(&name.to_string_lossy().trim()) {
+            continue;
+        }
+        if let Some(temp) = device.attribute_value("temp1_input") {
+            let temp: f32 = temp
+                .to_string_lossy()
+                .trim()
+                .parse()
+                .map_err(|_| PlatformError::ParseNum)?;
+            // hwmon reports millidegrees
+            return Ok(temp / 1000.0);
+        }
+    }
+    Err(PlatformError::MissingFunction(
+        "CPU temperature sensor not found".into(),
+    ))
+}
+
 /// Both modern AMD and Intel have cpufreq control if using `powersave`
 /// governor. What interests us the most here is `energy_performance_preference`
 /// which can drastically alter CPU performance.
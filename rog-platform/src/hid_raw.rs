@@ -7,6 +7,7 @@ use log::{info, warn};
 use udev::Device;
 
 use crate::error::{PlatformError, Result};
+use crate::observe_packet;
 
 /// A USB device that utilizes hidraw for I/O
 #[derive(Debug)]
@@ -109,6 +110,7 @@ impl HidRaw {
 
     /// Write an array of raw bytes to the device using the hidraw interface
     pub fn write_bytes(&self, message: &[u8]) -> Result<()> {
+        observe_packet(&self.prod_id, message);
         if let Ok(mut file) = self.file.try_borrow_mut() {
             // let mut file = self.file.borrow_mut();
             // TODO: re-get the file if error?
@@ -11,12 +11,31 @@ pub mod power;
 pub mod usb_raw;
 
 use std::path::Path;
+use std::sync::OnceLock;
 
 use error::{PlatformError, Result};
 use udev::Device;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Called with the product ID and bytes of every packet written by `HidRaw`
+/// or `USBRaw`
+pub type PacketObserver = fn(&str, &[u8]);
+
+static PACKET_OBSERVER: OnceLock<PacketObserver> = OnceLock::new();
+
+/// Set a function to see every packet written to a USB device, such as to
+/// log them for debugging. Only the first observer set is used.
+pub fn set_packet_observer(observer: PacketObserver) {
+    PACKET_OBSERVER.set(observer).ok();
+}
+
+pub(crate) fn observe_packet(prod_id: &str, message: &[u8]) {
+    if let Some(observer) = PACKET_OBSERVER.get() {
+        observer(prod_id, message);
+    }
+}
+
 pub(crate) fn to_device(sys_path: &Path) -> Result<Device> {
     Device::from_syspath(sys_path)
         .map_err(|e| PlatformError::Udev("Couldn't transform syspath to device".to_owned(), e))
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 use log::{info, warn};
 
 use crate::error::{PlatformError, Result};
-use crate::{attr_u8, to_device};
+use crate::{attr_u8, get_attr_u8, to_device};
 
 /// The "platform" device provides access to things like:
 /// - `dgpu_disable`
@@ -24,6 +24,11 @@ impl AsusPower {
 
     attr_u8!("online", mains);
 
+    get_attr_u8!(
+        /// Battery charge level as a percentage
+        "capacity" battery
+    );
+
     /// When checking for battery this will look in order:
     /// - if attr `manufacturer` contains `asus`
     /// - if attr `charge_control_end_threshold` exists and `energy_full_design`
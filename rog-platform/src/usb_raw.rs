@@ -3,9 +3,11 @@ use std::time::Duration;
 use rusb::{Device, DeviceHandle};
 
 use crate::error::{PlatformError, Result};
+use crate::observe_packet;
 
+/// The device handle and the product ID, kept for `observe_packet`
 #[derive(Debug, PartialEq, Eq)]
-pub struct USBRaw(DeviceHandle<rusb::GlobalContext>);
+pub struct USBRaw(DeviceHandle<rusb::GlobalContext>, String);
 
 impl USBRaw {
     pub fn new(id_product: u16) -> Result<Self> {
@@ -13,7 +15,7 @@ impl USBRaw {
             let device_desc = device.device_descriptor()?;
             if device_desc.vendor_id() == 0x0b05 && device_desc.product_id() == id_product {
                 let handle = Self::get_dev_handle(&device)?;
-                return Ok(Self(handle));
+                return Ok(Self(handle, format!("{id_product:04x}")));
             }
         }
 
@@ -35,6 +37,7 @@ impl USBRaw {
     }
 
     pub fn write_bytes(&self, message: &[u8]) -> Result<usize> {
+        observe_packet(&self.1, message);
         self.0
             .write_control(
                 0x21,  // request_type
@@ -8,6 +8,8 @@
 //!
 //! Step 1 needs to be applied only on fresh system boot.
 
+use std::fmt;
+
 use dmi_id::DMIID;
 
 #[cfg(feature = "dbus")]
@@ -130,3 +132,61 @@ pub const fn pkt_set_options(enabled: bool, brightness: u8, interval: u8) -> Sla
 
     pkt
 }
+
+/// A packet written to the `Slash`, parsed back from the bytes made by the
+/// `pkt_*` functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlashPacket {
+    /// Either of the packets from `pkts_for_init()`
+    Init,
+    Save,
+    /// The first of the two packets from `pkt_set_mode()`
+    PrepareMode,
+    SetMode(SlashMode),
+    SetOptions {
+        enabled: bool,
+        brightness: u8,
+        interval: u8,
+    },
+}
+
+impl SlashPacket {
+    /// Parse a packet. Returns `None` if it is not one that is known.
+    pub fn parse(pkt: &[u8]) -> Option<Self> {
+        if pkt.first() != Some(&DEV_PAGE) {
+            return None;
+        }
+        let byte = |i: usize| pkt.get(i).copied().unwrap_or_default();
+        Some(match (byte(1), byte(2)) {
+            (0xd7, 0x00) | (0xd2, 0x02) => Self::Init,
+            (0xd4, 0x00) => Self::Save,
+            (0x02, 0x03) => Self::PrepareMode,
+            (0xd3, 0x04) => Self::SetMode(SlashMode::try_from(byte(6)).ok()?),
+            (0xd3, 0x03) => Self::SetOptions {
+                enabled: byte(8) != 0x00,
+                brightness: byte(10),
+                interval: byte(12),
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for SlashPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Init => write!(f, "Slash init"),
+            Self::Save => write!(f, "Slash save"),
+            Self::PrepareMode => write!(f, "Slash prepare mode"),
+            Self::SetMode(mode) => write!(f, "Slash set mode {mode}"),
+            Self::SetOptions {
+                enabled,
+                brightness,
+                interval,
+            } => write!(
+                f,
+                "Slash set options enabled {enabled}, brightness {brightness}, interval {interval}"
+            ),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+
+use log::warn;
+use rog_anime::usb::{
+    AnimAwake, AnimBooting, AnimShutdown, AnimSleeping, AnimePacket, Brightness, PROD_ID,
+};
+use rog_anime::{AnimeDataBuffer, AnimeType};
+
+use crate::{on_off, vendor_report_descriptor, Simulated};
+
+/// The state of an `AniMe` matrix display as set by the packets written to it
+#[derive(Debug, Clone)]
+pub struct AnimeSim {
+    pub anime_type: AnimeType,
+    pub initialised: bool,
+    pub brightness: Brightness,
+    pub display_enabled: bool,
+    pub powersave_anim_enabled: bool,
+    pub boot: AnimBooting,
+    pub awake: AnimAwake,
+    pub sleep: AnimSleeping,
+    pub shutdown: AnimShutdown,
+    /// The last frame shown by a flush
+    pub frame: Option<AnimeDataBuffer>,
+    /// How many frames have been shown
+    pub frames: usize,
+    /// Panes written since the last flush
+    panes: AnimeDataBuffer,
+    packets: usize,
+    unknown: Vec<Vec<u8>>,
+}
+
+impl AnimeSim {
+    pub fn new(anime_type: AnimeType) -> Self {
+        Self {
+            anime_type,
+            initialised: false,
+            brightness: Brightness::Off,
+            display_enabled: false,
+            powersave_anim_enabled: false,
+            boot: AnimBooting::default(),
+            awake: AnimAwake::default(),
+            sleep: AnimSleeping::default(),
+            shutdown: AnimShutdown::default(),
+            frame: None,
+            frames: 0,
+            panes: AnimeDataBuffer::new(anime_type),
+            packets: 0,
+            unknown: Vec::new(),
+        }
+    }
+
+    fn apply(&mut self, packet: &AnimePacket) {
+        match packet {
+            AnimePacket::Init => self.initialised = true,
+            AnimePacket::Flush => {
+                self.frame = Some(self.panes.clone());
+                self.frames += 1;
+            }
+            AnimePacket::Brightness(brightness) => self.brightness = *brightness,
+            AnimePacket::EnableDisplay(enabled) => self.display_enabled = *enabled,
+            AnimePacket::EnablePowersaveAnim(enabled) => self.powersave_anim_enabled = *enabled,
+            AnimePacket::BuiltinAnimations {
+                boot,
+                awake,
+                sleep,
+                shutdown,
+            } => {
+                self.boot = *boot;
+                self.awake = *awake;
+                self.sleep = *sleep;
+                self.shutdown = *shutdown;
+            }
+            AnimePacket::Pane(pane, data) => {
+                let len = data.len();
+                let start = pane * len;
+                match self.panes.data_mut().get_mut(start..start + len) {
+                    Some(dest) => dest.copy_from_slice(data),
+                    None => warn!("AniMe: pane {pane} is not on a {:?}", self.anime_type),
+                }
+            }
+        }
+    }
+}
+
+impl Simulated for AnimeSim {
+    type Packet = AnimePacket;
+
+    fn name(&self) -> String {
+        "ROG_Virtual Anime Matrix".to_owned()
+    }
+
+    fn product_id(&self) -> u16 {
+        PROD_ID
+    }
+
+    fn report_descriptor(&self) -> Vec<u8> {
+        vendor_report_descriptor(&[(0x76, 0x5a, 63), (0x80, 0x5e, 639)])
+    }
+
+    fn handle(&mut self, data: &[u8]) -> Option<AnimePacket> {
+        self.packets += 1;
+        let packet = AnimePacket::parse(data);
+        match &packet {
+            Some(packet) => self.apply(packet),
+            None => self.unknown.push(data.to_vec()),
+        }
+        packet
+    }
+
+    fn packets(&self) -> usize {
+        self.packets
+    }
+
+    fn unknown(&self) -> &[Vec<u8>] {
+        &self.unknown
+    }
+}
+
+impl fmt::Display for AnimeSim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lit = self
+            .frame
+            .as_ref()
+            .map(|frame| frame.data().iter().filter(|b| **b != 0).count())
+            .unwrap_or_default();
+        write!(
+            f,
+            "AniMe {:?}: brightness {:?}, display {}, builtins {} ({:?}, {:?}, {:?}, {:?}), {} \
+             frames, {lit} LEDs lit",
+            self.anime_type,
+            self.brightness,
+            on_off(self.display_enabled),
+            on_off(self.powersave_anim_enabled),
+            self.boot,
+            self.awake,
+            self.sleep,
+            self.shutdown,
+            self.frames,
+        )
+    }
+}
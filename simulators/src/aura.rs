@@ -0,0 +1,145 @@
+use std::fmt;
+
+use rog_aura::keyboard::{LedCode, LedUsbPackets};
+use rog_aura::usb::AuraPacket;
+use rog_aura::{AuraEffect, Colour};
+
+use crate::{on_off, vendor_report_descriptor, Simulated};
+
+/// The state of an N-KEY aura keyboard as set by the packets written to it
+#[derive(Debug, Clone)]
+pub struct AuraSim {
+    pub product_id: u16,
+    /// The raw brightness, `0` to `3`
+    pub brightness: Option<u8>,
+    /// The builtin mode shown by the last `Set`
+    pub effect: Option<AuraEffect>,
+    /// How many times the mode was applied so that it is kept over a reboot
+    pub applies: usize,
+    /// The bytes of `LaptopAuraPower`
+    pub power: Option<[u8; 4]>,
+    /// Set from the first per-key or zoned packets until the next `Set` of a
+    /// builtin mode
+    pub direct: Option<LedUsbPackets>,
+    /// An effect not yet shown by a `Set`
+    pending: Option<AuraEffect>,
+    packets: usize,
+    unknown: Vec<Vec<u8>>,
+}
+
+impl AuraSim {
+    /// `product_id` is that of the keyboard to act as, such as `0x19b6`
+    pub fn new(product_id: u16) -> Self {
+        Self {
+            product_id,
+            brightness: None,
+            effect: None,
+            applies: 0,
+            power: None,
+            direct: None,
+            pending: None,
+            packets: 0,
+            unknown: Vec::new(),
+        }
+    }
+
+    /// The colour of a key while per-key or zoned colours are shown
+    pub fn key_colour(&self, key: LedCode) -> Option<Colour> {
+        self.direct.as_ref()?.colour(key)
+    }
+
+    fn apply(&mut self, packet: &AuraPacket) {
+        match packet {
+            AuraPacket::Effect(effect) => self.pending = Some(effect.clone()),
+            AuraPacket::Set => {
+                if let Some(effect) = self.pending.take() {
+                    self.effect = Some(effect);
+                    self.direct = None;
+                }
+            }
+            AuraPacket::Apply => self.applies += 1,
+            AuraPacket::Brightness(brightness) => self.brightness = Some(*brightness),
+            AuraPacket::PowerStates(bytes) => self.power = Some(*bytes),
+            AuraPacket::DirectInit => self.direct = None,
+            AuraPacket::Direct(row) => {
+                let zoned = row[2] == 0x01;
+                let direct = self.direct.get_or_insert_with(|| {
+                    if zoned {
+                        LedUsbPackets::new_zoned(row[4] == 0x04)
+                    } else {
+                        LedUsbPackets::new_per_key()
+                    }
+                });
+                // Per-key packets are one of the key groups, zoned packets
+                // hold every zone
+                let index = if zoned { 0 } else { (row[6] >> 4) as usize };
+                if let Some(dest) = direct.get_mut().get_mut(index) {
+                    dest.clone_from(row);
+                }
+            }
+        }
+    }
+}
+
+impl Simulated for AuraSim {
+    type Packet = AuraPacket;
+
+    fn name(&self) -> String {
+        "ROG_Virtual N-KEY Device".to_owned()
+    }
+
+    fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    fn report_descriptor(&self) -> Vec<u8> {
+        vendor_report_descriptor(&[(0x76, 0x5a, 63), (0x79, 0x5d, 63)])
+    }
+
+    fn handle(&mut self, data: &[u8]) -> Option<AuraPacket> {
+        self.packets += 1;
+        let packet = AuraPacket::parse(data);
+        match &packet {
+            Some(packet) => self.apply(packet),
+            None => self.unknown.push(data.to_vec()),
+        }
+        packet
+    }
+
+    fn packets(&self) -> usize {
+        self.packets
+    }
+
+    fn unknown(&self) -> &[Vec<u8>] {
+        &self.unknown
+    }
+}
+
+impl fmt::Display for AuraSim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Aura {:04x}: brightness ", self.product_id)?;
+        match self.brightness {
+            Some(brightness) => write!(f, "{brightness}")?,
+            None => write!(f, "unset")?,
+        }
+        match (&self.direct, &self.effect) {
+            (Some(_), _) => write!(f, ", per-key colours")?,
+            (None, Some(effect)) => write!(
+                f,
+                ", mode {} {:?} {:?} {:?} {:?}",
+                effect.mode_name(),
+                effect.colour1,
+                effect.colour2,
+                effect.speed,
+                effect.direction
+            )?,
+            (None, None) => write!(f, ", mode unset")?,
+        }
+        write!(
+            f,
+            ", power {}, applied {} times",
+            on_off(self.power.is_some()),
+            self.applies
+        )
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use uhid_virt::{Bus, CreateParams, OutputEvent, UHIDDevice};
+
+use crate::{Simulated, VENDOR_ID};
+
+type Shared<S> = Arc<(Mutex<S>, Condvar)>;
+
+/// A uhid device that passes every packet written to it to the simulated
+/// state. The packets are read on a thread, so the state can be checked
+/// while a daemon writes to the device.
+///
+/// The device is removed when the thread stops, which is once the process
+/// exits.
+pub struct VirtualDevice<S: Simulated> {
+    shared: Shared<S>,
+}
+
+impl<S: Simulated> VirtualDevice<S> {
+    pub fn new(sim: S) -> std::io::Result<Self> {
+        let mut device = UHIDDevice::create(create_params(&sim))?;
+        info!("Created {} {:04x}", sim.name(), sim.product_id());
+
+        let name = sim.name();
+        let shared: Shared<S> = Arc::new((Mutex::new(sim), Condvar::new()));
+        let reader = shared.clone();
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            loop {
+                match device.read() {
+                    Ok(OutputEvent::Output { data }) => {
+                        let (state, changed) = &*reader;
+                        if let Ok(mut state) = state.lock() {
+                            match state.handle(&data) {
+                                Some(packet) => debug!("{name}: {packet:?}"),
+                                None => warn!("{name}: unknown packet {data:02x?}"),
+                            }
+                        }
+                        changed.notify_all();
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("{name}: stopped reading: {e:?}");
+                        break;
+                    }
+                }
+            }
+            device.destroy().ok();
+        })?;
+
+        Ok(Self { shared })
+    }
+
+    /// A copy of the current state
+    pub fn state(&self) -> S {
+        let (state, _) = &*self.shared;
+        state
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// Wait until `check` is true for the state, checking after each packet.
+    /// Returns `None` if it is still not true after `timeout`.
+    pub fn wait_for(&self, timeout: Duration, check: impl Fn(&S) -> bool) -> Option<S> {
+        let (state, changed) = &*self.shared;
+        let guard = state.lock().ok()?;
+        let (guard, result) = changed
+            .wait_timeout_while(guard, timeout, |s| !check(s))
+            .ok()?;
+        (!result.timed_out()).then(|| guard.clone())
+    }
+
+    /// Wait for the next packet, returning the state after it
+    pub fn wait_for_packet(&self, timeout: Duration) -> Option<S> {
+        let (state, changed) = &*self.shared;
+        let guard = state.lock().ok()?;
+        let seen = guard.packets();
+        let (guard, result) = changed
+            .wait_timeout_while(guard, timeout, |s| s.packets() <= seen)
+            .ok()?;
+        (!result.timed_out()).then(|| guard.clone())
+    }
+}
+
+pub fn create_params<S: Simulated>(sim: &S) -> CreateParams {
+    CreateParams {
+        name: sim.name(),
+        phys: String::new(),
+        uniq: String::new(),
+        bus: Bus::USB,
+        vendor: VENDOR_ID as u32,
+        product: sim.product_id() as u32,
+        version: 0,
+        country: 0,
+        rd_data: sim.report_descriptor(),
+    }
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rog_anime::AnimeType;
+use rog_simulators::{AnimeSim, AuraSim, Simulated, SlashSim, VirtualDevice};
+
+const HELP: &str = "Usage: headless_sim <device>
+
+Creates a virtual device and prints its state each time a packet is written
+to it. Set RUST_LOG=debug to also print each decoded packet.
+
+Devices:
+    anime <GA401|GA402|GU604>
+    slash
+    aura [product id, default 19b6]";
+
+fn run<S: Simulated>(sim: S) -> Result<(), Box<dyn Error>> {
+    let device = VirtualDevice::new(sim)?;
+    println!("{}", device.state());
+    let mut last = String::new();
+    loop {
+        if let Some(state) = device.wait_for_packet(Duration::from_secs(60)) {
+            // Frames are written many times a second, so only print changes
+            let current = state.to_string();
+            if current != last {
+                println!("{current}");
+                last = current;
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut logger = env_logger::Builder::new();
+    logger
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["anime", model] => run(AnimeSim::new(AnimeType::from_str(model)?)),
+        ["slash"] => run(SlashSim::new()),
+        ["aura"] => run(AuraSim::new(0x19b6)),
+        ["aura", id] => run(AuraSim::new(u16::from_str_radix(id, 16)?)),
+        _ => {
+            println!("{HELP}");
+            Ok(())
+        }
+    }
+}
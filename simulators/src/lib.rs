@@ -0,0 +1,97 @@
+//! Virtual ROG devices created through `/dev/uhid`, which decode every packet
+//! written to them in to the state the real device would be in. This lets
+//! `asusd` be run and tested without the hardware.
+//!
+//! ```ignore
+//! let anime = VirtualDevice::new(AnimeSim::new(AnimeType::GA402))?;
+//! // start asusd, or write packets through hidraw
+//! let state = anime.wait_for(Duration::from_secs(5), |s| s.frames > 0);
+//! ```
+
+use std::fmt::{Debug, Display};
+
+/// The `AniMe` matrix display
+mod anime;
+pub use anime::*;
+
+/// The N-KEY keyboard with builtin modes, per-key and zoned colours
+mod aura;
+pub use aura::*;
+
+/// The `Slash` lightbar
+mod slash;
+pub use slash::*;
+
+/// Creates the uhid device and runs the state on a thread
+mod device;
+pub use device::*;
+
+pub const VENDOR_ID: u16 = 0x0b05;
+
+/// A device that can be simulated. It holds the state that the packets
+/// written to it have set.
+pub trait Simulated: Clone + Debug + Display + Send + 'static {
+    /// What each packet is decoded as
+    type Packet: Debug;
+
+    fn name(&self) -> String;
+
+    fn product_id(&self) -> u16;
+
+    fn report_descriptor(&self) -> Vec<u8>;
+
+    /// Decode a packet and apply it to the state. Returns `None` if it is not
+    /// a known packet.
+    fn handle(&mut self, data: &[u8]) -> Option<Self::Packet>;
+
+    /// How many packets have been handled
+    fn packets(&self) -> usize;
+
+    /// Every packet that could not be decoded
+    fn unknown(&self) -> &[Vec<u8>];
+}
+
+/// A HID report descriptor with one vendor defined collection for each
+/// `(usage, report id, feature report length)`. Each has a 5 byte input
+/// report, which is what the devices use.
+pub fn vendor_report_descriptor(reports: &[(u8, u8, u16)]) -> Vec<u8> {
+    let mut desc = Vec::new();
+    for (usage, report_id, len) in reports {
+        let [len_lo, len_hi] = len.to_le_bytes();
+        desc.extend_from_slice(&[
+            0x06, 0x31, 0xff, // Usage Page (Vendor Defined 0xFF31)
+            0x09, *usage, // Usage
+            0xa1, 0x01, // Collection (Application)
+            0x85, *report_id, //   Report ID
+            0x19, 0x00, //   Usage Minimum (0x00)
+            0x2a, 0xff, 0x00, //   Usage Maximum (0xFF)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x26, 0xff, 0x00, //   Logical Maximum (255)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x05, //   Report Count (5)
+            0x81, 0x00, //   Input (Data,Array,Abs)
+            0x19, 0x00, //   Usage Minimum (0x00)
+            0x2a, 0xff, 0x00, //   Usage Maximum (0xFF)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x26, 0xff, 0x00, //   Logical Maximum (255)
+        ]);
+        if len_hi == 0 {
+            desc.extend_from_slice(&[0x95, len_lo]); //   Report Count
+        } else {
+            desc.extend_from_slice(&[0x96, len_lo, len_hi]); //   Report Count
+        }
+        desc.extend_from_slice(&[
+            0xb1, 0x00, //   Feature (Data,Array,Abs)
+            0xc0, // End Collection
+        ]);
+    }
+    desc
+}
+
+pub(crate) fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
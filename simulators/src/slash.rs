@@ -0,0 +1,94 @@
+use std::fmt;
+
+use rog_slash::usb::{SlashPacket, PROD_ID};
+use rog_slash::SlashMode;
+
+use crate::{on_off, vendor_report_descriptor, Simulated};
+
+/// The state of a `Slash` lightbar as set by the packets written to it
+#[derive(Debug, Clone, Default)]
+pub struct SlashSim {
+    pub initialised: bool,
+    pub enabled: bool,
+    pub brightness: u8,
+    pub interval: u8,
+    pub mode: Option<SlashMode>,
+    /// How many times the settings were saved
+    pub saves: usize,
+    packets: usize,
+    unknown: Vec<Vec<u8>>,
+}
+
+impl SlashSim {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn apply(&mut self, packet: &SlashPacket) {
+        match packet {
+            SlashPacket::Init => self.initialised = true,
+            SlashPacket::Save => self.saves += 1,
+            SlashPacket::PrepareMode => {}
+            SlashPacket::SetMode(mode) => self.mode = Some(*mode),
+            SlashPacket::SetOptions {
+                enabled,
+                brightness,
+                interval,
+            } => {
+                self.enabled = *enabled;
+                self.brightness = *brightness;
+                self.interval = *interval;
+            }
+        }
+    }
+}
+
+impl Simulated for SlashSim {
+    type Packet = SlashPacket;
+
+    fn name(&self) -> String {
+        "ROG_Virtual Slash".to_owned()
+    }
+
+    fn product_id(&self) -> u16 {
+        PROD_ID
+    }
+
+    fn report_descriptor(&self) -> Vec<u8> {
+        vendor_report_descriptor(&[(0x76, 0x5a, 63), (0x80, 0x5e, 127)])
+    }
+
+    fn handle(&mut self, data: &[u8]) -> Option<SlashPacket> {
+        self.packets += 1;
+        let packet = SlashPacket::parse(data);
+        match &packet {
+            Some(packet) => self.apply(packet),
+            None => self.unknown.push(data.to_vec()),
+        }
+        packet
+    }
+
+    fn packets(&self) -> usize {
+        self.packets
+    }
+
+    fn unknown(&self) -> &[Vec<u8>] {
+        &self.unknown
+    }
+}
+
+impl fmt::Display for SlashSim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Slash: {}, mode {}, brightness {}, interval {}, saved {} times",
+            on_off(self.enabled),
+            self.mode
+                .map(|m| m.to_string())
+                .unwrap_or("unset".to_owned()),
+            self.brightness,
+            self.interval,
+            self.saves,
+        )
+    }
+}
@@ -0,0 +1,136 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use rog_anime::usb::{pkt_flush, pkt_set_brightness, pkts_for_init, Brightness};
+use rog_anime::{AnimeDataBuffer, AnimePacketType, AnimeType};
+use rog_aura::keyboard::{LedCode, LedUsbPackets};
+use rog_aura::usb::{aura_brightness_bytes, LED_APPLY, LED_SET};
+use rog_aura::{AuraEffect, AuraModeNum, Colour, LED_MSG_LEN};
+use rog_platform::hid_raw::HidRaw;
+use rog_simulators::{AnimeSim, AuraSim, Simulated, SlashSim, VirtualDevice};
+use rog_slash::usb::{pkt_save, pkt_set_mode, pkt_set_options, pkts_for_init as slash_init};
+use rog_slash::SlashMode;
+
+fn anime_frame(anime_type: AnimeType) -> (AnimeDataBuffer, AnimePacketType) {
+    let mut buffer = AnimeDataBuffer::new(anime_type);
+    for (i, b) in buffer.data_mut().iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let packets = AnimePacketType::try_from(buffer.clone()).unwrap();
+    (buffer, packets)
+}
+
+#[test]
+fn anime_state() {
+    for anime_type in [AnimeType::GA401, AnimeType::GA402, AnimeType::GU604] {
+        let mut sim = AnimeSim::new(anime_type);
+        for pkt in pkts_for_init() {
+            sim.handle(&pkt);
+        }
+        sim.handle(&pkt_set_brightness(Brightness::High));
+        assert!(sim.initialised);
+        assert_eq!(sim.brightness, Brightness::High);
+
+        let (buffer, packets) = anime_frame(anime_type);
+        for pkt in &packets {
+            sim.handle(pkt);
+        }
+        // Nothing is shown until the flush
+        assert!(sim.frame.is_none());
+        sim.handle(&pkt_flush());
+        assert_eq!(sim.frame.as_ref().unwrap().data(), buffer.data());
+        assert_eq!(sim.frames, 1);
+        assert!(sim.unknown().is_empty());
+    }
+}
+
+#[test]
+fn slash_state() {
+    let mut sim = SlashSim::new();
+    for pkt in slash_init() {
+        sim.handle(&pkt);
+    }
+    for pkt in pkt_set_mode(SlashMode::Spectrum) {
+        sim.handle(&pkt);
+    }
+    sim.handle(&pkt_set_options(true, 200, 3));
+    sim.handle(&pkt_save());
+
+    assert!(sim.initialised);
+    assert_eq!(sim.mode, Some(SlashMode::Spectrum));
+    assert!(sim.enabled);
+    assert_eq!((sim.brightness, sim.interval, sim.saves), (200, 3, 1));
+    assert_eq!(sim.packets(), 6);
+    assert!(sim.unknown().is_empty());
+}
+
+#[test]
+fn aura_state() {
+    let mut sim = AuraSim::new(0x19b6);
+    sim.handle(&aura_brightness_bytes(2));
+
+    let effect = AuraEffect {
+        mode: AuraModeNum::Breathe,
+        colour1: Colour { r: 255, g: 0, b: 0 },
+        ..Default::default()
+    };
+    sim.handle(&<[u8; LED_MSG_LEN]>::from(&effect));
+    // Not shown until it is set
+    assert!(sim.effect.is_none());
+    sim.handle(&LED_SET);
+    sim.handle(&LED_APPLY);
+    assert_eq!(sim.brightness, Some(2));
+    assert_eq!(sim.effect, Some(effect));
+    assert_eq!(sim.applies, 1);
+
+    let mut per_key = LedUsbPackets::new_per_key();
+    per_key.set(LedCode::W, 0, 255, 0);
+    sim.handle(&LedUsbPackets::get_init_msg());
+    for row in per_key.get_ref() {
+        sim.handle(row);
+    }
+    assert_eq!(
+        sim.key_colour(LedCode::W),
+        Some(Colour { r: 0, g: 255, b: 0 })
+    );
+    assert_eq!(
+        sim.key_colour(LedCode::A),
+        Some(Colour { r: 0, g: 0, b: 0 })
+    );
+
+    sim.handle(&[0x5d, 0xff]);
+    assert_eq!(sim.unknown(), &[vec![0x5d, 0xff]]);
+}
+
+/// Writes through the kernel in the same way that `asusd` does
+#[test]
+#[ignore = "needs write access to /dev/uhid"]
+fn anime_through_uhid() {
+    let anime_type = AnimeType::GA402;
+    let device = VirtualDevice::new(AnimeSim::new(anime_type)).unwrap();
+
+    // The hidraw node takes a moment to appear
+    let mut hid = None;
+    for _ in 0..50 {
+        if let Ok(found) = HidRaw::new("193b") {
+            hid = Some(found);
+            break;
+        }
+        sleep(Duration::from_millis(100));
+    }
+    let hid = hid.expect("virtual AniMe did not appear");
+
+    let (buffer, packets) = anime_frame(anime_type);
+    hid.write_bytes(&pkt_set_brightness(Brightness::Low))
+        .unwrap();
+    for pkt in &packets {
+        hid.write_bytes(pkt).unwrap();
+    }
+    hid.write_bytes(&pkt_flush()).unwrap();
+
+    let state = device
+        .wait_for(Duration::from_secs(5), |s| s.frames > 0)
+        .expect("no frame was shown");
+    assert_eq!(state.brightness, Brightness::Low);
+    assert_eq!(state.frame.unwrap().data(), buffer.data());
+}